@@ -0,0 +1,102 @@
+//! Bulk fills and blits against the per-pixel loops they replace, and
+//! direct against tiled rasterization, at 1440p.
+//!
+//! `cargo bench -p kevinpthornecom_core --bench fills`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use kevinpthornecom_core::{
+    gradient::Gradient,
+    pixels::{BlendMode, BlitOptions, Color, PixelBuffer, BLUE, WHITE},
+    tiles::{render_tiled, Sequential, Threads},
+    ui::Rect,
+};
+
+const SIZE: (usize, usize) = (2560, 1440);
+const WHOLE: Rect = ((0, 0), (SIZE.0 - 1, SIZE.1 - 1));
+
+/// Alternates so every iteration really writes
+fn colors() -> impl FnMut() -> Color {
+    let mut flip = false;
+    move || {
+        flip = !flip;
+        if flip {
+            Color::rgb(0, 0, 200)
+        } else {
+            Color::rgb(0, 0, 100)
+        }
+    }
+}
+
+fn fills(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fill 2560x1440");
+    let mut buffer = PixelBuffer::new(SIZE.0, SIZE.1);
+
+    let mut next = colors();
+    group.bench_function("set per pixel", |b| {
+        b.iter(|| {
+            let color = next();
+            for y in 0..SIZE.1 {
+                for x in 0..SIZE.0 {
+                    buffer.set((x, y), color);
+                }
+            }
+        })
+    });
+    let mut next = colors();
+    group.bench_function("clear", |b| b.iter(|| buffer.clear(next())));
+    let mut next = colors();
+    group.bench_function("fill_rect", |b| b.iter(|| buffer.fill_rect(WHOLE, next(), BlendMode::Over)));
+    let mut next = colors();
+    group.bench_function("fill_span", |b| {
+        b.iter(|| {
+            let color = next();
+            for y in 0..SIZE.1 {
+                buffer.fill_span(y, 0, SIZE.0 - 1, color, BlendMode::Replace);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn blits(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blit 2560x1440");
+    let mut buffer = PixelBuffer::new(SIZE.0, SIZE.1);
+    let mut sources = [PixelBuffer::new(SIZE.0, SIZE.1), PixelBuffer::new(SIZE.0, SIZE.1)];
+    sources[0].clear(Color::rgb(200, 0, 0));
+    sources[1].clear(Color::rgb(100, 0, 0));
+
+    let mut flip = 0;
+    group.bench_function("get and set per pixel", |b| {
+        b.iter(|| {
+            flip ^= 1;
+            for y in 0..SIZE.1 {
+                for x in 0..SIZE.0 {
+                    buffer.set((x, y), sources[flip].get((x, y)).unwrap_or_default());
+                }
+            }
+        })
+    });
+    group.bench_function("blit", |b| {
+        b.iter(|| {
+            flip ^= 1;
+            buffer.blit(&sources[flip], (0, 0), &BlitOptions::default());
+        })
+    });
+    group.finish();
+}
+
+fn tiled(c: &mut Criterion) {
+    let mut group = c.benchmark_group("radial gradient 2560x1440");
+    let mut buffer = PixelBuffer::new(SIZE.0, SIZE.1);
+    let gradient = Gradient::radial((1280.0, 720.0), 1000.0).with_stop(0.0, WHITE).with_stop(1.0, BLUE);
+    let draw = |buffer: &mut PixelBuffer| buffer.fill_rect(WHOLE, &gradient, BlendMode::Over);
+
+    group.bench_function("direct", |b| b.iter(|| draw(&mut buffer)));
+    group.bench_function("tiled, sequential", |b| b.iter(|| render_tiled(&mut buffer, &Sequential, draw)));
+    let threads = Threads::available();
+    group.bench_function("tiled, one thread per core", |b| b.iter(|| render_tiled(&mut buffer, &threads, draw)));
+    group.finish();
+}
+
+criterion_group!(benches, fills, blits, tiled);
+criterion_main!(benches);
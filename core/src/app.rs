@@ -0,0 +1,403 @@
+//! The site itself: its UI, animation and frame pipeline, with no idea
+//! whether it's running in a browser. Platform glue feeds it sizes, input
+//! and timestamps, and gives it a [Presenter] to draw to.
+
+use alloc::{boxed::Box, format, rc::Rc, string::String, string::ToString, vec, vec::Vec};
+
+use crate::{
+    color::ParseColorError,
+    font::Font,
+    gradient::{Gradient, Spread},
+    layers::{Layer, LayerStack},
+    palette::{Dither, IndexedBuffer, Palette},
+    pixels::{BlendMode, Color, PixelBuffer, BLUE, GREEN, RED, TRANSPARENT, WHITE},
+    postfx::FilterChain,
+    present::Presenter,
+    rich::{RichText, Style},
+    tiles::{render_tiled, Sequential, TileExecutor},
+    ui::{is_point_in_rect, Button, Gesture, GestureHandler, HCenter, Positioned, Rect, Rectangle, Renderable, Size, Text},
+    viewport::{Resolution, Viewport},
+};
+
+pub const FPS: u32 = 15;
+
+/// Tall enough for the UI, and small enough that 4K screens draw at a
+/// quarter of their resolution
+pub const DEFAULT_RESOLUTION: Resolution = Resolution::Height(480);
+
+/// The gradient backdrop, still unless animated
+pub const BACKGROUND_LAYER: &str = "background";
+/// The page's UI
+pub const CONTENT_LAYER: &str = "content";
+/// Frame stats. Anything that should float over the content but under
+/// this, like modals and toasts, goes between the two.
+pub const HUD_LAYER: &str = "hud";
+
+fn new_layers((width, height): Size) -> LayerStack {
+    let mut layers = LayerStack::new(width, height);
+    layers.add(BACKGROUND_LAYER, 0);
+    layers.add(CONTENT_LAYER, 10);
+    layers.add(HUD_LAYER, 30);
+    layers
+}
+
+/// Bands of blue, which roll down the screen a full cycle every ~4
+/// seconds when the background is animated
+fn background_gradient(height: usize) -> Gradient {
+    Gradient::linear((0.0, 0.0), (0.0, height as f32))
+        .with_stop(0.0, Color::rgb(0, 0, 40))
+        .with_stop(0.5, BLUE)
+        .with_stop(1.0, Color::rgb(0, 0, 40))
+        .with_spread(Spread::Repeat)
+        .scrolling(-1.0 / 64.0)
+}
+
+pub struct App {
+    resolution: Resolution,
+    /// The screen's size in CSS pixels, and its device pixel ratio
+    screen: ((f64, f64), f64),
+    viewport: Viewport,
+    /// What the presenter was last resized to
+    presenter_size: Option<(usize, usize)>,
+    /// What everything is drawn into, composited into `screenbuff`
+    layers: LayerStack,
+    /// The virtual screen
+    screenbuff: PixelBuffer,
+    /// Post-processing run over each finished frame
+    filters: FilterChain,
+    /// `screenbuff` with the filters applied, while any are enabled
+    filtered: Option<PixelBuffer>,
+    /// `screenbuff` scaled up to the canvas's size, unless the scale is 1
+    upscaled: Option<PixelBuffer>,
+    /// Fills the background layer
+    background: Gradient,
+    /// Whether the background moves every tick. Off by default, since it
+    /// changes every pixel and so makes every frame a full upload.
+    animate_background: bool,
+    /// Whether the background layer needs drawing even if it isn't animated
+    repaint_background: bool,
+    /// What all the site's text is drawn in
+    font: Rc<Font>,
+    /// Rasterizes the background and content layers
+    executor: Box<dyn TileExecutor>,
+    /// Whether the whole content layer needs redrawing, not just what the
+    /// UI elements say changed
+    repaint_content: bool,
+    /// Where the stats line was drawn last frame, so it can be erased
+    stats_rect: Option<Rect>,
+    /// When set, every frame is squeezed into this buffer's palette before
+    /// upload
+    indexed: Option<IndexedBuffer>,
+    tick: usize,
+    last_frame_time: u32,
+    ui_elements: Vec<Box<dyn Renderable>>,
+    // todo fix
+    gesturehandler_ui_elements: Vec<Box<dyn GestureHandler>>,
+}
+
+impl App {
+    /// An app for a screen `css_size` CSS pixels big
+    pub fn new(css_size: (f64, f64), device_pixel_ratio: f64) -> Self {
+        let viewport = Viewport::new(css_size, device_pixel_ratio, DEFAULT_RESOLUTION);
+        let (width, height) = viewport.virtual_size;
+
+        // TODO rip this out of the constructor
+        let test_text: String = "ABCDEFGHIJKLMNOPQRSTUVWXYZ 0123456789 !@#$%^&*()[]{}\\|;':\",./<>?-=_+`~".to_string();
+        let test_2: String = "THE QUICK, BROWN FOX JUMPS OVER THE LAZY DOG.".to_string();
+        let font = Rc::new(Font::builtin());
+        let title_style = Style { shadow: Some(Color::new(0, 0, 0, 160)), ..Style::new(WHITE, 4) };
+        let title = RichText::new()
+            .with_span("KEVIN P. ", title_style)
+            .with_span("THORNE", Style { color: Color::rgb(255, 210, 80), bold: true, ..title_style });
+        Self {
+            resolution: DEFAULT_RESOLUTION,
+            screen: (css_size, device_pixel_ratio),
+            viewport,
+            presenter_size: None,
+            layers: new_layers((width, height)),
+            screenbuff: PixelBuffer::new(width, height),
+            filters: FilterChain::new(),
+            filtered: None,
+            upscaled: None,
+            background: background_gradient(height),
+            animate_background: false,
+            repaint_background: true,
+            font: font.clone(),
+            executor: Box::new(Sequential),
+            repaint_content: true,
+            stats_rect: None,
+            indexed: None,
+            tick: 0,
+            last_frame_time: 0,
+            ui_elements: vec![
+                Box::new(HCenter::new((0, 10), Box::new(Text::rich(title, &font)))),
+                Box::new(Positioned::new((0, 100), Box::new(Text::new(test_text.clone().to_string(), &font, 1, GREEN)))),
+                Box::new(Positioned::new((0, 110), Box::new(Text::new(test_text.clone().to_string(), &font, 2, RED)))),
+                Box::new(Positioned::new((0, 125), Box::new(Text::new(test_text.clone().to_string(), &font, 3, BLUE)))),
+                Box::new(Positioned::new((0, 145), Box::new(Text::new(test_text.clone().to_string(), &font, 4, GREEN)))),
+                Box::new(Positioned::new((0, 170), Box::new(Text::new(test_2.clone().to_string(), &font, 3, GREEN)))),
+                Box::new(Positioned::new((10, 220), Box::new(Rectangle::new((10, 10), WHITE)))),
+            ],
+            gesturehandler_ui_elements: vec![
+                Box::new(Button::new((10, 190), "BOOP".to_string(), &font, 3, Color::rgb(120, 120, 120), WHITE)),
+            ],
+        }
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// Resizes the virtual screen to fit a screen `css_size` CSS pixels big
+    pub fn resize(&mut self, css_size: (f64, f64), device_pixel_ratio: f64) {
+        self.screen = (css_size, device_pixel_ratio);
+        self.viewport = Viewport::new(css_size, device_pixel_ratio, self.resolution);
+        let (canvas_width, canvas_height) = self.viewport.canvas_size;
+        let (width, height) = self.viewport.virtual_size;
+        self.layers.resize(width, height);
+        self.screenbuff = PixelBuffer::new(width, height);
+        self.filtered = None;
+        self.upscaled = if self.viewport.scale > 1 {
+            Some(PixelBuffer::new(canvas_width, canvas_height))
+        } else {
+            None
+        };
+        self.background = background_gradient(height);
+        self.repaint_background = true;
+        self.repaint_content = true;
+        self.stats_rect = None;
+        self.last_frame_time = 0;
+        if let Some(indexed) = self.indexed.take() {
+            self.indexed = Some(IndexedBuffer::new(width, height, indexed.palette().clone()));
+        }
+    }
+
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+        let (css_size, device_pixel_ratio) = self.screen;
+        self.resize(css_size, device_pixel_ratio);
+    }
+
+    /// Turns the CRT post-processing preset on or off, e.g. per page
+    pub fn set_crt_effect(&mut self, enabled: bool) {
+        self.filters = if enabled { FilterChain::crt() } else { FilterChain::new() };
+        self.filters_changed();
+    }
+
+    /// Rolls the background gradient down the screen every tick, or
+    /// leaves it still
+    pub fn set_background_animation(&mut self, enabled: bool) {
+        self.animate_background = enabled;
+        self.repaint_background = true;
+    }
+
+    /// Returns false if there's no filter called `name`
+    pub fn set_filter_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let found = self.filters.set_enabled(name, enabled);
+        self.filters_changed();
+        found
+    }
+
+    /// e.g. `set_filter_param("bloom", "strength", 0.8)`. Returns false if
+    /// there's no such filter or parameter.
+    pub fn set_filter_param(&mut self, filter: &str, param: &str, value: f32) -> bool {
+        let found = self.filters.set_param(filter, param, value);
+        self.filters_changed();
+        found
+    }
+
+    /// Switches to indexed color with the given hex colors, e.g.
+    /// `"#000 #fff"`, or back to full color with an empty string. Swapping
+    /// one palette for another just recolors, without redrawing anything.
+    pub fn set_palette(&mut self, colors: &str) -> Result<(), ParseColorError> {
+        // quantizing overwrites the frame, so switching needs it rebuilt
+        if colors.trim().is_empty() {
+            if self.indexed.take().is_some() {
+                self.layers.invalidate();
+            }
+            return Ok(());
+        }
+        let palette = Palette::from_hex(colors)?;
+        match &mut self.indexed {
+            Some(indexed) => indexed.set_palette(palette),
+            None => {
+                self.indexed = Some(IndexedBuffer::new(self.screenbuff.width, self.screenbuff.height, palette));
+                self.layers.invalidate();
+            }
+        }
+        Ok(())
+    }
+
+    /// e.g. [Threads](crate::tiles::Threads) to spread drawing across
+    /// cores. Frames come out the same whichever is used.
+    pub fn set_tile_executor(&mut self, executor: Box<dyn TileExecutor>) {
+        self.executor = executor;
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.get(name)
+    }
+
+    /// e.g. to draw toasts or a modal on their own layer
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.get_mut(name)
+    }
+
+    /// Returns false if there's no layer called `name`
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) -> bool {
+        self.layers.get_mut(name).map(|layer| layer.set_visible(visible)).is_some()
+    }
+
+    /// Returns false if there's no layer called `name`
+    pub fn set_layer_opacity(&mut self, name: &str, opacity: u8) -> bool {
+        self.layers.get_mut(name).map(|layer| layer.set_opacity(opacity)).is_some()
+    }
+
+    /// Sends `gesture` at a point in CSS pixels to whatever's under it.
+    /// Returns whether anything was.
+    pub fn on_gesture(&mut self, css_point: (f64, f64), gesture: Gesture) -> bool {
+        let Some(point) = self.viewport.to_virtual(css_point) else {
+            return false;
+        };
+        let mut handled = false;
+        for gesture_handler in self.gesturehandler_ui_elements.iter_mut().rev() {
+            if is_point_in_rect(point, gesture_handler.get_collision_rect()) {
+                gesture_handler.on_event(gesture);
+                handled = true;
+            }
+        }
+        handled
+    }
+
+    /// requestAnimationFrame usually calls as fast as the display is
+    /// configured for (i.e. 60 or 120Hz). This regulates drawing to
+    /// [FPS]
+    pub fn on_frame<P: Presenter>(&mut self, timestamp: u32, presenter: &mut P) -> Result<(), P::Error> {
+        let elapsed = timestamp.saturating_sub(self.last_frame_time);
+        if elapsed > (1000 / FPS) {
+            let delta = elapsed - (1000 / FPS);
+            self.render(delta, presenter)?;
+            self.last_frame_time = timestamp;
+            self.tick += 1;
+        }
+        Ok(())
+    }
+
+    /// Reprocesses the whole frame next render, since any filter change
+    /// can affect any pixel
+    fn filters_changed(&mut self) {
+        let (width, height) = (self.screenbuff.width, self.screenbuff.height);
+        self.screenbuff.invalidate(((0, 0), (width.saturating_sub(1), height.saturating_sub(1))));
+    }
+
+    /// Draws the next frame and presents whatever changed
+    pub fn render<P: Presenter>(&mut self, delta_time: u32, presenter: &mut P) -> Result<(), P::Error> {
+        if self.presenter_size != Some(self.viewport.canvas_size) {
+            presenter.resize(self.viewport.canvas_size)?;
+            self.presenter_size = Some(self.viewport.canvas_size);
+        }
+
+        let tick_str: String = self.tick.to_string();
+        let frametime_str: String = delta_time.to_string();
+        let stats_str = format!("FRAME {tick_str} . FRAMETIME {frametime_str}");
+        let (stats_width, stats_height) = self.font.measure(&stats_str, 2);
+        let stats_pos = (0, self.screenbuff.height.saturating_sub(self.font.cell_size().1 * 2));
+        // rects are inclusive, and an empty string covers nothing
+        let stats_rect = (stats_width > 0 && stats_height > 0)
+            .then(|| (stats_pos, (stats_pos.0 + stats_width - 1, stats_pos.1 + stats_height - 1)));
+
+        // fancy gradient background, only redrawn when it can have changed
+        let executor = &*self.executor;
+        if self.animate_background || self.repaint_background {
+            self.repaint_background = false;
+            if self.animate_background {
+                self.background.set_tick(self.tick);
+            }
+            let background = &self.background;
+            let buffer = self.layers.get_mut(BACKGROUND_LAYER).unwrap().buffer_mut();
+            let whole = buffer.clip_rect();
+            render_tiled(buffer, executor, |buffer| buffer.fill_rect(whole, background, BlendMode::Replace));
+        }
+
+        // only repaint content that changed since last frame
+        let content = self.layers.get_mut(CONTENT_LAYER).unwrap().buffer_mut();
+        let mut damage: Vec<Rect> = vec![];
+        for e in &mut self.ui_elements {
+            damage.extend(e.take_invalidated());
+        }
+        for e in &mut self.gesturehandler_ui_elements {
+            damage.extend(e.take_invalidated());
+        }
+        if self.repaint_content {
+            self.repaint_content = false;
+            damage = vec![content.clip_rect()];
+        }
+        let (ui_elements, gesturehandler_ui_elements) = (&mut self.ui_elements, &mut self.gesturehandler_ui_elements);
+        render_tiled(content, executor, |content| {
+            for region in damage {
+                content.push_clip(region);
+                let clip = content.clip_rect();
+                content.fill_rect(clip, TRANSPARENT, BlendMode::Replace);
+
+                // render UI elements
+                for e in ui_elements.iter_mut() {
+                    e.render(content);
+                }
+                for e in gesturehandler_ui_elements.iter_mut() {
+                    e.render(content);
+                }
+                content.pop_clip();
+            }
+        });
+
+        let hud = self.layers.get_mut(HUD_LAYER).unwrap().buffer_mut();
+        if let Some(last_stats_rect) = core::mem::replace(&mut self.stats_rect, stats_rect) {
+            hud.fill_rect(last_stats_rect, TRANSPARENT, BlendMode::Replace);
+        }
+        hud.render_text(&stats_str, stats_pos, &self.font, GREEN, 2);
+
+        self.layers.composite(&mut self.screenbuff);
+        let mut dirty_rects = self.screenbuff.take_dirty_rects();
+        // filters write to a copy, so partial repaints still start from the
+        // unfiltered frame
+        if self.filters.is_active() && self.filtered.is_none() {
+            self.filtered = Some(PixelBuffer::new(self.screenbuff.width, self.screenbuff.height));
+        }
+        let frame = match &mut self.filtered {
+            Some(filtered) if self.filters.is_active() => {
+                if !dirty_rects.is_empty() {
+                    self.filters.process(&self.screenbuff, filtered);
+                }
+                dirty_rects = filtered.take_dirty_rects();
+                filtered
+            }
+            _ => &mut self.screenbuff,
+        };
+        if let Some(indexed) = &mut self.indexed {
+            // ordered dithering keeps partial repaints seamless
+            for &rect in &dirty_rects {
+                indexed.quantize(frame, rect, rect.0, Dither::Ordered);
+            }
+            indexed.resolve(frame);
+            dirty_rects.extend(frame.take_dirty_rects());
+        }
+        let presented = match &mut self.upscaled {
+            Some(upscaled) => {
+                for &rect in &dirty_rects {
+                    frame.upscale_into(upscaled, rect, self.viewport.scale);
+                }
+                dirty_rects = upscaled.take_dirty_rects();
+                upscaled
+            }
+            None => frame,
+        };
+        if dirty_rects.is_empty() {
+            return Ok(());
+        }
+        presenter.present(presented, &dirty_rects)
+    }
+}
@@ -1,10 +1,13 @@
+use alloc::{vec, vec::Vec};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Bitset {
     data: Vec<u8>,
 }
 
 impl Bitset {
     pub fn new(size: usize) -> Self {
-        let bytes_needed = (size + 7) / 8;
+        let bytes_needed = size.div_ceil(8);
         Self { data: vec![0; bytes_needed] }
     }
 
@@ -43,6 +46,11 @@ impl Bitset {
         (self.data[byte_index] & (1 << bit_index)) != 0
     }
 
+    /// Whether any bit is set
+    pub fn any(&self) -> bool {
+        self.data.iter().any(|&byte| byte != 0)
+    }
+
     fn size(&self) -> usize {
         self.data.len() * 8
     }
@@ -50,6 +58,7 @@ impl Bitset {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,369 @@
+//! RGBA colors, and the conversions themes and animations need: hex
+//! strings, HSL/HSV, interpolation and premultiplied alpha.
+
+use core::{fmt, str::FromStr};
+
+/// (a * b) / 255, rounded
+pub(crate) fn mul_255(a: u32, b: u32) -> u32 {
+    let t = a * b + 128;
+    (t + (t >> 8)) >> 8
+}
+
+/// Maps 0.0..=1.0 to 0..=255, rounding and clamping
+fn unit_to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+/// Straight (not premultiplied) RGBA with 8 bits per channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// A color with r, g and b already scaled by alpha. Source-over is a
+/// single multiply-add per channel in this form, and averaging these never
+/// picks up the hue of a fully transparent pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PremultipliedColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseColorError {
+    /// Hex colors have to start with `#`
+    MissingHash,
+    /// Anything other than 3, 4, 6 or 8 digits
+    InvalidLength(usize),
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseColorError::MissingHash => write!(f, "hex color doesn't start with '#'"),
+            ParseColorError::InvalidLength(len) => write!(f, "hex color has {} digits, expected 3, 4, 6 or 8", len),
+            ParseColorError::InvalidDigit(c) => write!(f, "invalid hex digit {:?}", c),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseColorError {}
+
+/// Hue in degrees, shared by HSL and HSV
+fn hue(r: f32, g: f32, b: f32, max: f32, chroma: f32) -> f32 {
+    if chroma == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == r {
+        (g - b) / chroma + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    sector * 60.0
+}
+
+/// The opaque color with `hue`, `chroma` and `min` as its smallest channel
+fn from_chroma(hue: f32, chroma: f32, min: f32) -> Color {
+    let mut hue = hue % 360.0;
+    if hue < 0.0 {
+        hue += 360.0;
+    }
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::rgb(unit_to_byte(r + min), unit_to_byte(g + min), unit_to_byte(b + min))
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// An opaque color
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 255)
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+    pub fn from_hex(hex: &str) -> Result<Self, ParseColorError> {
+        let digits = hex.strip_prefix('#').ok_or(ParseColorError::MissingHash)?;
+        let mut nibbles = [0u8; 8];
+        let mut len = 0;
+        for c in digits.chars() {
+            let value = c.to_digit(16).ok_or(ParseColorError::InvalidDigit(c))?;
+            if len < nibbles.len() {
+                nibbles[len] = value as u8;
+            }
+            len += 1;
+        }
+        let n = &nibbles;
+        match len {
+            // short forms repeat each digit, so #f80 is #ff8800
+            3 | 4 => {
+                let a = if len == 4 { n[3] * 17 } else { 255 };
+                Ok(Self::new(n[0] * 17, n[1] * 17, n[2] * 17, a))
+            }
+            6 | 8 => {
+                let a = if len == 8 { n[6] << 4 | n[7] } else { 255 };
+                Ok(Self::new(n[0] << 4 | n[1], n[2] << 4 | n[3], n[4] << 4 | n[5], a))
+            }
+            _ => Err(ParseColorError::InvalidLength(len)),
+        }
+    }
+
+    /// An opaque color from hue in degrees, and saturation and lightness
+    /// from 0.0 to 1.0
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let (saturation, lightness) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        from_chroma(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// Hue in degrees from 0.0 up to 360.0, then saturation and lightness
+    /// from 0.0 to 1.0. Alpha is ignored.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+        let lightness = (max + min) / 2.0;
+        let saturation = if chroma == 0.0 { 0.0 } else { chroma / (1.0 - (2.0 * lightness - 1.0).abs()) };
+        (hue(r, g, b, max, chroma), saturation, lightness)
+    }
+
+    /// An opaque color from hue in degrees, and saturation and value from
+    /// 0.0 to 1.0
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        let chroma = value * saturation;
+        from_chroma(hue, chroma, value - chroma)
+    }
+
+    /// Hue in degrees from 0.0 up to 360.0, then saturation and value from
+    /// 0.0 to 1.0. Alpha is ignored.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (hue(r, g, b, max, chroma), saturation, max)
+    }
+
+    /// Interpolates from `self` at `t == 0.0` to `other` at `t == 1.0`.
+    ///
+    /// Works on premultiplied values, so fading from a color to
+    /// transparent keeps the color instead of darkening towards the
+    /// transparent end's (invisible) rgb.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let (from_a, to_a) = (self.a as f32, other.a as f32);
+        let a = from_a + (to_a - from_a) * t;
+        if a <= 0.0 {
+            return Color::default();
+        }
+        let channel = |from: u8, to: u8| {
+            let premultiplied = from as f32 * from_a * (1.0 - t) + to as f32 * to_a * t;
+            (premultiplied / a + 0.5).min(255.0) as u8
+        };
+        Color::new(channel(self.r, other.r), channel(self.g, other.g), channel(self.b, other.b), (a + 0.5) as u8)
+    }
+
+    /// Moves each channel `amount` (0.0 to 1.0) of the way towards white
+    pub fn lighten(self, amount: f32) -> Color {
+        let amount = amount.clamp(0.0, 1.0);
+        let channel = |c: u8| c + ((255 - c) as f32 * amount + 0.5) as u8;
+        Color::new(channel(self.r), channel(self.g), channel(self.b), self.a)
+    }
+
+    /// Moves each channel `amount` (0.0 to 1.0) of the way towards black
+    pub fn darken(self, amount: f32) -> Color {
+        let amount = amount.clamp(0.0, 1.0);
+        let channel = |c: u8| (c as f32 * (1.0 - amount) + 0.5) as u8;
+        Color::new(channel(self.r), channel(self.g), channel(self.b), self.a)
+    }
+
+    /// Multiplies alpha by `factor` (0.0 to 1.0), e.g. to fade something out
+    pub fn scale_alpha(self, factor: f32) -> Color {
+        self.with_alpha(unit_to_byte(self.a as f32 / 255.0 * factor))
+    }
+
+    pub fn premultiply(self) -> PremultipliedColor {
+        let a = self.a as u32;
+        PremultipliedColor {
+            r: mul_255(self.r as u32, a) as u8,
+            g: mul_255(self.g as u32, a) as u8,
+            b: mul_255(self.b as u32, a) as u8,
+            a: self.a,
+        }
+    }
+}
+
+impl PremultipliedColor {
+    /// Porter-Duff source-over
+    pub fn over(self, dst: PremultipliedColor) -> PremultipliedColor {
+        let inverse = 255 - self.a as u32;
+        let channel = |s: u8, d: u8| (s as u32 + mul_255(d as u32, inverse)).min(255) as u8;
+        PremultipliedColor {
+            r: channel(self.r, dst.r),
+            g: channel(self.g, dst.g),
+            b: channel(self.b, dst.b),
+            a: channel(self.a, dst.a),
+        }
+    }
+
+    /// Back to straight alpha. Fully transparent colors come back black,
+    /// since premultiplying threw their rgb away.
+    pub fn unpremultiply(self) -> Color {
+        if self.a == 0 {
+            return Color::default();
+        }
+        let a = self.a as u32;
+        let channel = |c: u8| ((c as u32 * 255 + a / 2) / a).min(255) as u8;
+        Color::new(channel(self.r), channel(self.g), channel(self.b), self.a)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+/// Formats as `#rrggbb`, or `#rrggbbaa` when not opaque
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<(u8, u8, u8, u8)> for Color {
+    fn from((r, g, b, a): (u8, u8, u8, u8)) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<Color> for (u8, u8, u8, u8) {
+    fn from(color: Color) -> Self {
+        (color.r, color.g, color.b, color.a)
+    }
+}
+
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<Color> for [u8; 4] {
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b, color.a]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!("#ff8000".parse(), Ok(Color::rgb(255, 128, 0)));
+        assert_eq!(Color::from_hex("#FF800080"), Ok(Color::new(255, 128, 0, 128)));
+        assert_eq!(Color::from_hex("#f80"), Ok(Color::rgb(255, 136, 0)));
+        assert_eq!(Color::from_hex("#f808"), Ok(Color::new(255, 136, 0, 136)));
+        assert_eq!(Color::from_hex("ff8000"), Err(ParseColorError::MissingHash));
+        assert_eq!(Color::from_hex("#ff80"), Ok(Color::new(255, 255, 136, 0)));
+        assert_eq!(Color::from_hex("#ff800"), Err(ParseColorError::InvalidLength(5)));
+        assert_eq!(Color::from_hex("#ff8000000"), Err(ParseColorError::InvalidLength(9)));
+        assert_eq!(Color::from_hex("#ff80g0"), Err(ParseColorError::InvalidDigit('g')));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for color in [Color::rgb(1, 2, 254), Color::new(255, 128, 0, 7)] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+        assert_eq!(Color::rgb(255, 128, 0).to_string(), "#ff8000");
+    }
+
+    #[test]
+    fn test_hsl_and_hsv() {
+        assert_eq!(Color::from_hsl(0.0, 1.0, 0.5), Color::rgb(255, 0, 0));
+        assert_eq!(Color::from_hsl(120.0, 1.0, 0.25), Color::rgb(0, 128, 0));
+        assert_eq!(Color::from_hsl(-120.0, 1.0, 0.5), Color::rgb(0, 0, 255));
+        assert_eq!(Color::from_hsv(60.0, 1.0, 1.0), Color::rgb(255, 255, 0));
+        assert_eq!(Color::from_hsv(300.0, 0.0, 0.5), Color::rgb(128, 128, 128));
+        assert_eq!(Color::rgb(0, 0, 255).to_hsv(), (240.0, 1.0, 1.0));
+
+        for color in [Color::rgb(200, 40, 90), Color::rgb(12, 250, 130), Color::rgb(77, 77, 77)] {
+            let (h, s, l) = color.to_hsl();
+            assert_eq!(Color::from_hsl(h, s, l), color);
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Color::from_hsv(h, s, v), color);
+        }
+    }
+
+    #[test]
+    fn test_lerp() {
+        let (black, white) = (Color::rgb(0, 0, 0), Color::rgb(255, 255, 255));
+        assert_eq!(black.lerp(white, 0.0), black);
+        assert_eq!(black.lerp(white, 0.5), Color::rgb(128, 128, 128));
+        assert_eq!(black.lerp(white, 2.0), white);
+        // the transparent end contributes no color
+        let red = Color::rgb(255, 0, 0);
+        assert_eq!(red.lerp(Color::new(0, 0, 255, 0), 0.5), Color::new(255, 0, 0, 128));
+    }
+
+    #[test]
+    fn test_lighten_darken() {
+        let color = Color::new(100, 200, 0, 50);
+        assert_eq!(color.lighten(0.5), Color::new(178, 228, 128, 50));
+        assert_eq!(color.darken(0.5), Color::new(50, 100, 0, 50));
+        assert_eq!(color.darken(1.0), Color::new(0, 0, 0, 50));
+        assert_eq!(color.scale_alpha(0.5), Color::new(100, 200, 0, 25));
+    }
+
+    #[test]
+    fn test_premultiplied() {
+        let color = Color::new(255, 128, 0, 128);
+        let premultiplied = color.premultiply();
+        assert_eq!(premultiplied, PremultipliedColor { r: 128, g: 64, b: 0, a: 128 });
+        assert_eq!(premultiplied.unpremultiply(), color);
+
+        let background = Color::rgb(0, 0, 255).premultiply();
+        assert_eq!(premultiplied.over(background).unpremultiply(), Color::rgb(128, 64, 127));
+    }
+
+    #[test]
+    fn test_tuple_conversion() {
+        let color: Color = (1, 2, 3, 4).into();
+        assert_eq!(color, Color::new(1, 2, 3, 4));
+        assert_eq!(<(u8, u8, u8, u8)>::from(color), (1, 2, 3, 4));
+        assert_eq!(<[u8; 4]>::from(color), [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,149 @@
+use alloc::{vec, vec::Vec};
+use crate::{bitset::Bitset, ui::{Point, Rect}};
+
+/// Dirty regions are tracked per tile rather than per pixel, trading a
+/// little over-upload for cheap bookkeeping.
+pub const DIRTY_TILE_SIZE: usize = 32;
+
+/// Records which parts of a [crate::pixels::PixelBuffer] changed since they
+/// were last taken, so only those need to be uploaded.
+pub struct DirtyRegions {
+    width: usize,
+    height: usize,
+    columns: usize,
+    rows: usize,
+    tiles: Bitset,
+}
+
+impl DirtyRegions {
+    /// Starts fully dirty, since nothing has been uploaded yet
+    pub fn new(width: usize, height: usize) -> Self {
+        let columns = width.div_ceil(DIRTY_TILE_SIZE);
+        let rows = height.div_ceil(DIRTY_TILE_SIZE);
+        let mut regions = Self {
+            width,
+            height,
+            columns,
+            rows,
+            tiles: Bitset::new(columns * rows),
+        };
+        regions.mark_all();
+        regions
+    }
+
+    pub fn mark(&mut self, (x, y): Point) {
+        if x < self.width && y < self.height {
+            self.tiles.set((y / DIRTY_TILE_SIZE) * self.columns + x / DIRTY_TILE_SIZE, true);
+        }
+    }
+
+    /// Marks every tile touched by the inclusive `rect`
+    pub fn mark_rect(&mut self, ((left, top), (right, bottom)): Rect) {
+        if left > right || top > bottom || left >= self.width || top >= self.height {
+            return;
+        }
+        let right = right.min(self.width - 1) / DIRTY_TILE_SIZE;
+        let bottom = bottom.min(self.height - 1) / DIRTY_TILE_SIZE;
+        for row in (top / DIRTY_TILE_SIZE)..=bottom {
+            for column in (left / DIRTY_TILE_SIZE)..=right {
+                self.tiles.set(row * self.columns + column, true);
+            }
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        for i in 0..(self.columns * self.rows) {
+            self.tiles.set(i, true);
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.tiles.any()
+    }
+
+    /// Returns the dirty area as a small set of non-overlapping inclusive
+    /// rects and marks everything clean.
+    ///
+    /// Runs of dirty tiles in a row become one rect, and runs with the same
+    /// span in consecutive rows are merged into one taller rect.
+    pub fn take_rects(&mut self) -> Vec<Rect> {
+        // (first column, last column, first row) of runs still growing down
+        let mut open: Vec<(usize, usize, usize)> = vec![];
+        let mut closed: Vec<(usize, usize, usize, usize)> = vec![];
+
+        for row in 0..self.rows {
+            let mut next_open = vec![];
+            let mut column = 0;
+            while column < self.columns {
+                if !self.tiles.get(row * self.columns + column) {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < self.columns && self.tiles.get(row * self.columns + column) {
+                    column += 1;
+                }
+                let end = column - 1;
+                let first_row = match open.iter().position(|&(s, e, _)| s == start && e == end) {
+                    Some(i) => open.swap_remove(i).2,
+                    None => row,
+                };
+                next_open.push((start, end, first_row));
+            }
+            for (start, end, first_row) in open {
+                closed.push((start, end, first_row, row - 1));
+            }
+            open = next_open;
+        }
+        for (start, end, first_row) in open {
+            closed.push((start, end, first_row, self.rows - 1));
+        }
+
+        self.tiles = Bitset::new(self.columns * self.rows);
+        closed
+            .into_iter()
+            .map(|(start, end, first_row, last_row)| {
+                (
+                    (start * DIRTY_TILE_SIZE, first_row * DIRTY_TILE_SIZE),
+                    (
+                        ((end + 1) * DIRTY_TILE_SIZE).min(self.width) - 1,
+                        ((last_row + 1) * DIRTY_TILE_SIZE).min(self.height) - 1,
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_fully_dirty() {
+        let mut regions = DirtyRegions::new(100, 50);
+        assert_eq!(regions.take_rects(), vec![((0, 0), (99, 49))]);
+        assert!(regions.is_clean());
+        assert_eq!(regions.take_rects(), vec![]);
+    }
+
+    #[test]
+    fn test_merges_tiles() {
+        let mut regions = DirtyRegions::new(128, 128);
+        regions.take_rects();
+        regions.mark((40, 5));
+        regions.mark((40, 40));
+        regions.mark((100, 40));
+        regions.mark_rect(((100, 100), (127, 127)));
+        let mut rects = regions.take_rects();
+        rects.sort();
+        assert_eq!(
+            rects,
+            vec![
+                ((32, 0), (63, 63)),
+                ((96, 32), (127, 63)),
+                ((96, 96), (127, 127)),
+            ]
+        );
+    }
+}
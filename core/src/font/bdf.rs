@@ -0,0 +1,275 @@
+//! BDF (Glyph Bitmap Distribution Format) parsing, per Adobe's 2.1 spec:
+//! the text format X11 bitmap fonts like Terminus and Spleen come in. Each
+//! glyph is placed in the font's bounding box, so every glyph gets the
+//! same cell, and its DWIDTH decides how much room it takes up.
+
+use alloc::vec::Vec;
+use core::{convert::TryFrom, str};
+
+use super::{check_cell_size, unpack_rows, Font, FontError, GlyphMetrics};
+use crate::bitset::Bitset;
+
+pub const MAGIC: &[u8] = b"STARTFONT";
+
+/// Width, height, and the offset of the bottom left corner from the
+/// origin, with y going up
+type BoundingBox = (usize, usize, isize, isize);
+
+struct Lines<'a> {
+    lines: core::iter::Enumerate<str::Lines<'a>>,
+}
+
+impl<'a> Lines<'a> {
+    /// The next non-empty line's number, keyword and the rest of it
+    fn next(&mut self) -> Result<(usize, &'a str, &'a str), FontError> {
+        loop {
+            let (i, line) = self.lines.next().ok_or(FontError::UnexpectedEof)?;
+            let line = line.trim();
+            if !line.is_empty() {
+                let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                return Ok((i + 1, keyword, rest.trim()));
+            }
+        }
+    }
+}
+
+/// Parses exactly `N` whitespace-separated integers
+fn integers<const N: usize>(line: usize, text: &str) -> Result<[isize; N], FontError> {
+    let mut values = [0; N];
+    let mut words = text.split_whitespace();
+    for value in &mut values {
+        let word = words.next().ok_or(FontError::InvalidLine(line, "too few numbers"))?;
+        *value = word.parse().map_err(|_| FontError::InvalidLine(line, "invalid number"))?;
+    }
+    if words.next().is_some() {
+        return Err(FontError::InvalidLine(line, "too many numbers"));
+    }
+    Ok(values)
+}
+
+fn bounding_box(line: usize, text: &str) -> Result<BoundingBox, FontError> {
+    let [width, height, x, y] = integers::<4>(line, text)?;
+    if width < 0 || height < 0 {
+        return Err(FontError::InvalidLine(line, "negative size"));
+    }
+    let (width, height) = (width as usize, height as usize);
+    if width > super::MAX_CELL || height > super::MAX_CELL {
+        return Err(FontError::Unsupported("glyphs too large"));
+    }
+    // no real font puts a glyph further from the origin than it is big
+    if x.unsigned_abs() > super::MAX_CELL || y.unsigned_abs() > super::MAX_CELL {
+        return Err(FontError::InvalidLine(line, "offset out of range"));
+    }
+    Ok((width, height, x, y))
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+/// Reads from just after STARTCHAR to ENDCHAR. Returns the glyph's
+/// encoding, which is `None` for glyphs outside the font's encoding, its
+/// bitmap drawn into the font's cell, and its advance width if it has one.
+fn glyph(lines: &mut Lines, font_box: BoundingBox) -> Result<(Option<u32>, Bitset, Option<isize>), FontError> {
+    let (cell_width, cell_height, cell_x, cell_y) = font_box;
+    let (mut encoding, mut glyph_box, mut advance) = (None, None, None);
+    loop {
+        let (line, keyword, rest) = lines.next()?;
+        match keyword {
+            "ENCODING" => {
+                // a second number gives a non-standard code for unencoded
+                // glyphs, which there's no use for here
+                let code = rest.split_whitespace().next().unwrap_or_default();
+                let code: i64 = code.parse().map_err(|_| FontError::InvalidLine(line, "invalid number"))?;
+                encoding = Some(u32::try_from(code).ok());
+            }
+            "BBX" => glyph_box = Some(bounding_box(line, rest)?),
+            // the vertical part is only for vertical writing
+            "DWIDTH" => {
+                let [width, _] = integers::<2>(line, rest)?;
+                if width.unsigned_abs() > super::MAX_CELL {
+                    return Err(FontError::InvalidLine(line, "advance out of range"));
+                }
+                advance = Some(width);
+            }
+            "BITMAP" => {
+                let encoding = encoding.ok_or(FontError::InvalidLine(line, "glyph without an ENCODING"))?;
+                let (width, height, x, y) = glyph_box.ok_or(FontError::InvalidLine(line, "glyph without a BBX"))?;
+                let row_bytes = width.div_ceil(8);
+                let mut data = Vec::with_capacity(row_bytes * height);
+                for _ in 0..height {
+                    let (line, row, _) = lines.next()?;
+                    let row = row.as_bytes();
+                    if row.len() < row_bytes * 2 {
+                        return Err(FontError::InvalidLine(line, "bitmap row too short"));
+                    }
+                    for pair in row[..row_bytes * 2].chunks_exact(2) {
+                        let (high, low) = hex_digit(pair[0]).zip(hex_digit(pair[1])).ok_or(FontError::InvalidLine(line, "invalid hex in bitmap"))?;
+                        data.push(high << 4 | low);
+                    }
+                }
+                let (line, keyword, _) = lines.next()?;
+                if keyword != "ENDCHAR" {
+                    return Err(FontError::InvalidLine(line, "expected ENDCHAR after the bitmap"));
+                }
+
+                let pixels = unpack_rows(&data, (width, height));
+                let mut bitmap = Bitset::new(cell_width * cell_height);
+                // the cell's top row, in the same y-up coordinates as the boxes
+                let cell_top = cell_y + cell_height as isize - 1;
+                for glyph_y in 0..height {
+                    let row = cell_top - (y + (height - 1 - glyph_y) as isize);
+                    for glyph_x in 0..width {
+                        let column = x + glyph_x as isize - cell_x;
+                        let inside = (0..cell_width as isize).contains(&column) && (0..cell_height as isize).contains(&row);
+                        if inside && pixels.get(glyph_y * width + glyph_x) {
+                            bitmap.set(row as usize * cell_width + column as usize, true);
+                        }
+                    }
+                }
+                return Ok((encoding, bitmap, advance));
+            }
+            "ENDCHAR" => return Err(FontError::InvalidLine(line, "glyph without a BITMAP")),
+            _ => {}
+        }
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Font, FontError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(FontError::UnknownFormat);
+    }
+    let text = str::from_utf8(bytes).map_err(|_| FontError::Corrupt("not a text file"))?;
+    let mut lines = Lines { lines: text.lines().enumerate() };
+    let mut font_box = None;
+    let mut default_char = char::REPLACEMENT_CHARACTER as u32;
+    let mut glyphs = Vec::new();
+    loop {
+        let (line, keyword, rest) = lines.next()?;
+        match keyword {
+            "FONTBOUNDINGBOX" => {
+                let bounds = bounding_box(line, rest)?;
+                check_cell_size(bounds.0, bounds.1)?;
+                font_box = Some(bounds);
+            }
+            "DEFAULT_CHAR" => {
+                default_char = rest.parse().map_err(|_| FontError::InvalidLine(line, "invalid number"))?;
+            }
+            "STARTCHAR" => {
+                let font_box = font_box.ok_or(FontError::InvalidLine(line, "glyph before the FONTBOUNDINGBOX"))?;
+                glyphs.push(glyph(&mut lines, font_box)?);
+            }
+            "ENDFONT" => break,
+            _ => {}
+        }
+    }
+
+    let (width, height, x, y) = font_box.ok_or(FontError::Corrupt("no FONTBOUNDINGBOX"))?;
+    // y is usually negative: how far descenders reach below the baseline
+    let baseline = (height as isize + y).clamp(0, height as isize) as usize;
+    let mut font = Font::new((width, height)).with_baseline(baseline).with_line_height(height).with_spacing(0);
+    if let Some((_, bitmap, _)) = glyphs.iter().find(|(encoding, _, _)| *encoding == Some(default_char)) {
+        font = font.with_fallback(bitmap.clone());
+    }
+    for (encoding, bitmap, advance) in glyphs {
+        if let Some(c) = encoding.and_then(char::from_u32) {
+            font.set_glyph(c, bitmap);
+            if let Some(advance) = advance {
+                // the cell's left edge is x from the origin
+                let (ink_x, ink_width) = font.ink(c);
+                let left_bearing = x + ink_x as isize;
+                let right_bearing = advance - left_bearing - ink_width as isize;
+                font.set_metrics(c, GlyphMetrics { left_bearing, right_bearing });
+            }
+        }
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::{assert_glyph, A, E_ACUTE, J, REPLACEMENT};
+    use alloc::string::String;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/font6x8.bdf");
+
+    #[test]
+    fn test_decode() {
+        let font = decode(FIXTURE.as_bytes()).unwrap();
+        assert_eq!((font.cell_size(), font.baseline(), font.spacing()), ((6, 8), 6, 0));
+        assert_glyph(&font, 'A', &A);
+        assert_glyph(&font, 'é', &E_ACUTE);
+        // reaches below the baseline
+        assert_glyph(&font, 'j', &J);
+        // DEFAULT_CHAR
+        assert_glyph(&font, 'Z', &REPLACEMENT);
+        // every glyph is 6 wide, with A's ink in the first 5 columns
+        assert_eq!(font.metrics('A'), GlyphMetrics { left_bearing: 0, right_bearing: 1 });
+        assert_eq!(font.measure("AA", 1), (12, 8));
+    }
+
+    #[test]
+    fn test_advance_widths() {
+        let font = broken("DWIDTH 6 0", "DWIDTH 8 0").unwrap();
+        assert_eq!(font.metrics('A'), GlyphMetrics { left_bearing: 0, right_bearing: 3 });
+        assert_eq!(font.measure("AA", 1), (16, 8));
+        // with the cell starting a pixel left of the origin, A moves right
+        // in its cell but still sits on the origin
+        let font = broken("FONTBOUNDINGBOX 6 8 0 -2", "FONTBOUNDINGBOX 6 8 -1 -2").unwrap();
+        assert_eq!(font.ink('A'), (1, 5));
+        assert_eq!(font.metrics('A'), GlyphMetrics { left_bearing: 0, right_bearing: 1 });
+        assert_eq!(
+            broken("DWIDTH 6 0", "DWIDTH 6").err(),
+            Some(FontError::InvalidLine(line_of("DWIDTH"), "too few numbers"))
+        );
+    }
+
+    /// The fixture with its first `from` replaced by `to`
+    fn broken(from: &str, to: &str) -> Result<Font, FontError> {
+        decode(FIXTURE.replacen(from, to, 1).as_bytes())
+    }
+
+    fn line_of(needle: &str) -> usize {
+        FIXTURE.lines().position(|line| line.starts_with(needle)).unwrap() + 1
+    }
+
+    #[test]
+    fn test_errors() {
+        let bitmap_line = line_of("BITMAP") + 1;
+        let bad_hex = broken("\nBITMAP\n2", "\nBITMAP\nG");
+        assert_eq!(bad_hex.err(), Some(FontError::InvalidLine(bitmap_line, "invalid hex in bitmap")));
+        assert_eq!(
+            broken("BBX 5 6 0 0", "BBX 5 x 0 0").err(),
+            Some(FontError::InvalidLine(line_of("BBX"), "invalid number"))
+        );
+        assert_eq!(
+            broken("ENCODING 65\n", "").err(),
+            Some(FontError::InvalidLine(line_of("BITMAP") - 1, "glyph without an ENCODING"))
+        );
+        assert_eq!(
+            broken("FONTBOUNDINGBOX 6 8 0 -2", "FONTBOUNDINGBOX 6 8 0").err(),
+            Some(FontError::InvalidLine(line_of("FONTBOUNDINGBOX"), "too few numbers"))
+        );
+        assert_eq!(
+            broken("FONTBOUNDINGBOX 6 8", "FONTBOUNDINGBOX 6000 8").err(),
+            Some(FontError::Unsupported("glyphs too large"))
+        );
+        assert_eq!(
+            broken("BBX 5 6 0 0", "BBX 5 6 9223372036854775807 0").err(),
+            Some(FontError::InvalidLine(line_of("BBX"), "offset out of range"))
+        );
+        assert_eq!(
+            broken("FONTBOUNDINGBOX 6 8 0 -2", "FONTBOUNDINGBOX 6 8 0 9223372036854775807").err(),
+            Some(FontError::InvalidLine(line_of("FONTBOUNDINGBOX"), "offset out of range"))
+        );
+        assert_eq!(
+            broken("DWIDTH 6 0", "DWIDTH -9223372036854775807 0").err(),
+            Some(FontError::InvalidLine(line_of("DWIDTH"), "advance out of range"))
+        );
+        let truncated: String = FIXTURE.lines().take(line_of("ENDCHAR") - 1).collect::<alloc::vec::Vec<_>>().join("\n");
+        assert_eq!(decode(truncated.as_bytes()).err(), Some(FontError::UnexpectedEof));
+        assert_eq!(decode(b"STARTFONT 2.1\nENDFONT\n").err(), Some(FontError::Corrupt("no FONTBOUNDINGBOX")));
+        assert_eq!(decode(b"\xff").err(), Some(FontError::UnknownFormat));
+    }
+}
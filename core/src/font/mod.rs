@@ -0,0 +1,710 @@
+//! Bitmap fonts. A [Font] owns its glyphs and metrics, so faces of any
+//! cell size can be used side by side. [Font::builtin] is the site's own
+//! 5x5 display face; others can be loaded from BDF and PSF files.
+
+pub mod bdf;
+pub mod psf;
+
+use alloc::collections::BTreeMap;
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{bitset::Bitset, ui::Size};
+
+/// Glyphs larger than this on either side are rejected rather than risk
+/// allocating huge bitmaps for a corrupt header.
+pub const MAX_CELL: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontError {
+    /// The data ended before the font did
+    UnexpectedEof,
+    /// The data doesn't start with any signature we know
+    UnknownFormat,
+    /// A valid file that uses a feature these parsers don't handle
+    Unsupported(&'static str),
+    /// The file breaks its format's rules
+    Corrupt(&'static str),
+    /// A line of a text format that doesn't make sense, numbered from 1
+    InvalidLine(usize, &'static str),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::UnexpectedEof => write!(f, "unexpected end of font data"),
+            FontError::UnknownFormat => write!(f, "unknown font format"),
+            FontError::Unsupported(what) => write!(f, "unsupported font feature: {}", what),
+            FontError::Corrupt(what) => write!(f, "corrupt font: {}", what),
+            FontError::InvalidLine(line, what) => write!(f, "line {}: {}", line, what),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FontError {}
+
+pub(crate) fn check_cell_size(width: usize, height: usize) -> Result<(), FontError> {
+    if width == 0 || height == 0 {
+        return Err(FontError::Corrupt("zero width or height"));
+    }
+    if width > MAX_CELL || height > MAX_CELL {
+        return Err(FontError::Unsupported("glyphs too large"));
+    }
+    Ok(())
+}
+
+/// Unpacks a glyph stored a row at a time, each row padded to whole bytes
+/// with the leftmost pixel in the top bit, as both BDF and PSF do
+pub(crate) fn unpack_rows(data: &[u8], (width, height): Size) -> Bitset {
+    let row_bytes = width.div_ceil(8);
+    let mut bitmap = Bitset::new(width * height);
+    for (y, row) in data.chunks_exact(row_bytes).take(height).enumerate() {
+        for x in 0..width {
+            if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                bitmap.set(y * width + x, true);
+            }
+        }
+    }
+    bitmap
+}
+
+/// Loads a BDF, PSF1 or PSF2 font, picking the format from its signature
+pub fn decode(bytes: &[u8]) -> Result<Font, FontError> {
+    if bytes.starts_with(bdf::MAGIC) {
+        bdf::decode(bytes)
+    } else if bytes.starts_with(&psf::PSF1_MAGIC) || bytes.starts_with(&psf::PSF2_MAGIC) {
+        psf::decode(bytes)
+    } else {
+        Err(FontError::UnknownFormat)
+    }
+}
+
+/// A glyph of the built-in face, packed into the low 25 bits
+pub type FontGlyph = u32;
+
+/// Glyphs are a 5x5 grid
+/// 
+/// most significant 7 bits are currently unused.
+/// 
+/// ```text
+/// Layout
+/// ----------------
+/// |24|23|22|21|20|
+/// |19|18|17|16|15|
+/// |14|13|12|11|10|
+/// | 9| 8| 7| 6| 5|
+/// | 4| 3| 2| 1| 0|
+/// ----------------
+///                            0-----------------------24
+/// ```
+const A: FontGlyph = 0b0000000_1000110001111111000101110;
+const B: FontGlyph = 0b0000000_0111110001011111000101111;
+const C: FontGlyph = 0b0000000_0111010001000011000101110;
+const D: FontGlyph = 0b0000000_0011101001100010100100111;
+const E: FontGlyph = 0b0000000_1111100001111110000111111;
+const F: FontGlyph = 0b0000000_0001000010111110001011111;
+const G: FontGlyph = 0b0000000_0111010001111010000101110;
+const H: FontGlyph = 0b0000000_1000110001111111000110001;
+const I: FontGlyph = 0b0000000_1111100100001000010011111;
+const J: FontGlyph = 0b0000000_0011001001010000100011111;
+const K: FontGlyph = 0b0000000_0100100101000110010101001;
+const L: FontGlyph = 0b0000000_1111100001000010000100001;
+const M: FontGlyph = 0b0000000_1000110001101011101110001;
+const N: FontGlyph = 0b0000000_1000111001101011001110001;
+const O: FontGlyph = 0b0000000_0111010001100011000101110;
+const P: FontGlyph = 0b0000000_0000100001011111000101111;
+const Q: FontGlyph = 0b0000000_1011001001100011000101110;
+const R: FontGlyph = 0b0000000_1100101001001110100100111;
+const S: FontGlyph = 0b0000000_0111110000011100000111110;
+const T: FontGlyph = 0b0000000_0010000100001000010011111;
+const U: FontGlyph = 0b0000000_0111010001100011000110001;
+const V: FontGlyph = 0b0000000_0010001010100011000110001;
+const W: FontGlyph = 0b0000000_0101010101100011000110001;
+const X: FontGlyph = 0b0000000_1000101010001000101010001;
+const Y: FontGlyph = 0b0000000_0010000100001000101010001;
+const Z: FontGlyph = 0b0000000_1111100001011101000011111;
+const ONE: FontGlyph =      0b0000000_1111100100001000011000100;
+const TWO: FontGlyph =      0b0000000_1111100001111111000011111;
+const THREE: FontGlyph =    0b0000000_0111110000111101000001111;
+const FOUR: FontGlyph =     0b0000000_1000010000111111000110001;
+const FIVE: FontGlyph =     0b0000000_1111110000111110000111111;
+const SIX: FontGlyph =      0b0000000_0111010001011110000101110;
+const SEVEN: FontGlyph =    0b0000000_0010000100010001000011111;
+const EIGHT: FontGlyph =    0b0000000_0111010001011101000101110;
+const NINE: FontGlyph =     0b0000000_0111010000111101000101110;
+const ZERO: FontGlyph =     0b0000000_0111010011101011100101110;
+const DASH: FontGlyph =     0b0000000_0000000000011100000000000;
+const PERIOD: FontGlyph =   0b0000000_0000000100000000000000000;
+const COMMA: FontGlyph =    0b0000000_0001000100000000000000000;
+const LOWER_A: FontGlyph = 0b0000000_1111001001010010111000000;
+const LOWER_B: FontGlyph = 0b0000000_0111110001011110000100001;
+const LOWER_C: FontGlyph = 0b0000000_1111000001000011111000000;
+const LOWER_D: FontGlyph = 0b0000000_1111010001111101000010000;
+const LOWER_E: FontGlyph = 0b0000000_1111000001111110111000000;
+const LOWER_F: FontGlyph = 0b0000000_0001000010011110001001100;
+const LOWER_G: FontGlyph = 0b0000000_0111111110100011111000000;
+const LOWER_H: FontGlyph = 0b0000000_1000110001011110000100001;
+const LOWER_I: FontGlyph = 0b0000000_0111000100001100000000100;
+const LOWER_J: FontGlyph = 0b0000000_0011001001010000000001000;
+const LOWER_K: FontGlyph = 0b0000000_0100100111001010100100001;
+const LOWER_L: FontGlyph = 0b0000000_0111000100001000010000110;
+const LOWER_M: FontGlyph = 0b0000000_1000110101101010101100000;
+const LOWER_N: FontGlyph = 0b0000000_1000110001100010111100000;
+const LOWER_O: FontGlyph = 0b0000000_0111010001100010111000000;
+const LOWER_P: FontGlyph = 0b0000000_0000101111100010111100000;
+const LOWER_Q: FontGlyph = 0b0000000_1000011110100011111000000;
+const LOWER_R: FontGlyph = 0b0000000_0000100001100110110100000;
+const LOWER_S: FontGlyph = 0b0000000_0111111100001111111000000;
+const LOWER_T: FontGlyph = 0b0000000_0110010010000100111100010;
+const LOWER_U: FontGlyph = 0b0000000_1111010001100011000100000;
+const LOWER_V: FontGlyph = 0b0000000_0010001010100011000100000;
+const LOWER_W: FontGlyph = 0b0000000_0101010101101011000100000;
+const LOWER_X: FontGlyph = 0b0000000_0100100110001100100100000;
+const LOWER_Y: FontGlyph = 0b0000000_0001000100010101000100000;
+const LOWER_Z: FontGlyph = 0b0000000_1111100010010001111100000;
+const EXCLAMATION: FontGlyph =   0b0000000_0010000000001000010000100;
+const QUOTE: FontGlyph =         0b0000000_0000000000000000101001010;
+const HASH: FontGlyph =          0b0000000_0101011111010101111101010;
+const DOLLAR: FontGlyph =        0b0000000_0111110100011100010111110;
+const PERCENT: FontGlyph =       0b0000000_1100111010001000101110011;
+const AMPERSAND: FontGlyph =     0b0000000_1011001001101100100100110;
+const APOSTROPHE: FontGlyph =    0b0000000_0000000000000000010000100;
+const LEFT_PAREN: FontGlyph =    0b0000000_0100000100001000010001000;
+const RIGHT_PAREN: FontGlyph =   0b0000000_0001000100001000010000010;
+const ASTERISK: FontGlyph =      0b0000000_0000010101011101010100000;
+const PLUS: FontGlyph =          0b0000000_0000000100011100010000000;
+const SLASH: FontGlyph =         0b0000000_0000100010001000100010000;
+const COLON: FontGlyph =         0b0000000_0000000100000000010000000;
+const SEMICOLON: FontGlyph =     0b0000000_0001000100000000010000000;
+const LESS_THAN: FontGlyph =     0b0000000_0100000100000100010001000;
+const EQUALS: FontGlyph =        0b0000000_0000001110000000111000000;
+const GREATER_THAN: FontGlyph =  0b0000000_0001000100010000010000010;
+const QUESTION: FontGlyph =      0b0000000_0010000000011001000101110;
+const AT: FontGlyph =            0b0000000_1111000001111011000101110;
+const LEFT_BRACKET: FontGlyph =  0b0000000_0111000010000100001001110;
+const BACKSLASH: FontGlyph =     0b0000000_1000001000001000001000001;
+const RIGHT_BRACKET: FontGlyph = 0b0000000_0111001000010000100001110;
+const CARET: FontGlyph =         0b0000000_0000000000100010101000100;
+const UNDERSCORE: FontGlyph =    0b0000000_1111100000000000000000000;
+const BACKTICK: FontGlyph =      0b0000000_0000000000000000010000010;
+const LEFT_BRACE: FontGlyph =    0b0000000_0110000100000100010001100;
+const PIPE: FontGlyph =          0b0000000_0010000100001000010000100;
+const RIGHT_BRACE: FontGlyph =   0b0000000_0011000100010000010000110;
+const TILDE: FontGlyph =         0b0000000_0000001000101010001000000;
+
+const UNKNOWN: FontGlyph = 0b0000000_1010101010101010101010101;
+const EMPTY: FontGlyph =   0b0000000_0000000000000000000000000;
+
+const GLYPH_SIZE: usize = 5;
+const SPACING: usize = 1;
+/// Narrower than a glyph, as wide as most glyphs are apart
+const SPACE_WIDTH: usize = 3;
+
+/// Pairs whose shapes leave a gap between them, in the built-in face.
+/// Pulling them in a pixel still keeps their ink from touching.
+const KERNING_PAIRS: &[(char, char, isize)] = &[
+    ('L', 'T', -1),
+    ('L', 'V', -1),
+    ('L', 'Y', -1),
+    ('T', '.', -1),
+    ('T', ',', -1),
+    ('P', '.', -1),
+    ('P', ',', -1),
+    ('F', '.', -1),
+    ('F', ',', -1),
+    ('V', '.', -1),
+    ('Y', '.', -1),
+];
+
+/// The built-in face's glyph for `c`
+pub const fn get_glyph(c: char) -> FontGlyph {
+    match c {
+        'A' => A,
+        'B' => B,
+        'C' => C,
+        'D' => D,
+        'E' => E,
+        'F' => F,
+        'G' => G,
+        'H' => H,
+        'I' => I,
+        'J' => J,
+        'K' => K,
+        'L' => L,
+        'M' => M,
+        'N' => N,
+        'O' => O,
+        'P' => P,
+        'Q' => Q,
+        'R' => R,
+        'S' => S,
+        'T' => T,
+        'U' => U,
+        'V' => V,
+        'W' => W,
+        'X' => X,
+        'Y' => Y,
+        'Z' => Z,
+        '1' => ONE,
+        '2' => TWO,
+        '3' => THREE,
+        '4' => FOUR,
+        '5' => FIVE,
+        '6' => SIX,
+        '7' => SEVEN,
+        '8' => EIGHT,
+        '9' => NINE,
+        '0' => ZERO,
+        '-' => DASH,
+        '.' => PERIOD,
+        ',' => COMMA,
+        ' ' => EMPTY,
+        'a' => LOWER_A,
+        'b' => LOWER_B,
+        'c' => LOWER_C,
+        'd' => LOWER_D,
+        'e' => LOWER_E,
+        'f' => LOWER_F,
+        'g' => LOWER_G,
+        'h' => LOWER_H,
+        'i' => LOWER_I,
+        'j' => LOWER_J,
+        'k' => LOWER_K,
+        'l' => LOWER_L,
+        'm' => LOWER_M,
+        'n' => LOWER_N,
+        'o' => LOWER_O,
+        'p' => LOWER_P,
+        'q' => LOWER_Q,
+        'r' => LOWER_R,
+        's' => LOWER_S,
+        't' => LOWER_T,
+        'u' => LOWER_U,
+        'v' => LOWER_V,
+        'w' => LOWER_W,
+        'x' => LOWER_X,
+        'y' => LOWER_Y,
+        'z' => LOWER_Z,
+        '!' => EXCLAMATION,
+        '"' => QUOTE,
+        '#' => HASH,
+        '$' => DOLLAR,
+        '%' => PERCENT,
+        '&' => AMPERSAND,
+        '\'' => APOSTROPHE,
+        '(' => LEFT_PAREN,
+        ')' => RIGHT_PAREN,
+        '*' => ASTERISK,
+        '+' => PLUS,
+        '/' => SLASH,
+        ':' => COLON,
+        ';' => SEMICOLON,
+        '<' => LESS_THAN,
+        '=' => EQUALS,
+        '>' => GREATER_THAN,
+        '?' => QUESTION,
+        '@' => AT,
+        '[' => LEFT_BRACKET,
+        '\\' => BACKSLASH,
+        ']' => RIGHT_BRACKET,
+        '^' => CARET,
+        '_' => UNDERSCORE,
+        '`' => BACKTICK,
+        '{' => LEFT_BRACE,
+        '|' => PIPE,
+        '}' => RIGHT_BRACE,
+        '~' => TILDE,
+        _ => UNKNOWN,
+    }
+}
+
+// every printable ASCII character has its own glyph, or this won't compile
+const _: () = {
+    let mut c = b' ';
+    while c <= b'~' {
+        assert!(get_glyph(c as char) != UNKNOWN, "printable ASCII character without a glyph");
+        c += 1;
+    }
+};
+
+/// Tells fonts apart in glyph caches
+fn next_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How a glyph sits between its neighbours when text is set
+/// proportionally, in the glyph's own pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlyphMetrics {
+    /// Blank pixels before the ink. Negative to reach back over the glyph
+    /// before it.
+    pub left_bearing: isize,
+    /// Blank pixels after the ink, before the font's spacing
+    pub right_bearing: isize,
+}
+
+#[derive(Clone)]
+struct Glyph {
+    bitmap: Bitset,
+    /// The first column with any pixels set, and how many columns from
+    /// there on have some
+    ink: (usize, usize),
+    metrics: GlyphMetrics,
+}
+
+impl Glyph {
+    /// Bearings that put the glyph exactly where it is in its cell
+    fn new(bitmap: Bitset, (width, height): Size) -> Self {
+        let inked = |x: usize| (0..height).any(|y| bitmap.get(y * width + x));
+        let ink = match ((0..width).find(|&x| inked(x)), (0..width).rfind(|&x| inked(x))) {
+            (Some(left), Some(right)) => (left, right + 1 - left),
+            _ => (0, 0),
+        };
+        let metrics = GlyphMetrics { left_bearing: ink.0 as isize, right_bearing: (width - ink.0 - ink.1) as isize };
+        Self { bitmap, ink, metrics }
+    }
+
+    /// Pixels the pen moves past the glyph, before spacing and kerning
+    fn width(&self) -> isize {
+        self.metrics.left_bearing + self.ink.1 as isize + self.metrics.right_bearing
+    }
+}
+
+/// A bitmap font. Every glyph is drawn from a cell of the same size,
+/// stored row by row, one bit per pixel. Text is set proportionally, by
+/// each glyph's [GlyphMetrics] and the font's kerning pairs, unless the
+/// font is monospaced.
+#[derive(Clone)]
+pub struct Font {
+    /// Changes whenever a glyph's bitmap does, so cached glyphs never go
+    /// stale
+    id: usize,
+    cell: Size,
+    baseline: usize,
+    line_height: usize,
+    spacing: usize,
+    monospace: bool,
+    glyphs: BTreeMap<char, Glyph>,
+    /// Drawn for characters without a glyph
+    fallback: Glyph,
+    /// Extra room between pairs of characters, in glyph pixels; usually
+    /// negative, to tuck them together
+    kerning: BTreeMap<(char, char), isize>,
+}
+
+impl Font {
+    /// A font with no glyphs yet, where everything draws as blank
+    /// `cell`-sized glyphs. The baseline starts at the bottom of the cell,
+    /// with one pixel between glyphs and lines.
+    pub fn new(cell: Size) -> Self {
+        Self {
+            id: next_id(),
+            cell,
+            baseline: cell.1,
+            line_height: cell.1 + 1,
+            spacing: 1,
+            monospace: false,
+            glyphs: BTreeMap::new(),
+            fallback: Glyph::new(Bitset::new(cell.0 * cell.1), cell),
+            kerning: BTreeMap::new(),
+        }
+    }
+
+    /// The 5x5 display face, covering printable ASCII. Glyphs are only as
+    /// wide as their ink; [Font::with_monospace] lines them up in columns
+    /// again, e.g. for code.
+    pub fn builtin() -> Self {
+        let mut font = Self::new((GLYPH_SIZE, GLYPH_SIZE)).with_spacing(SPACING).with_fallback(Bitset::from_u32(UNKNOWN));
+        for c in ' '..='~' {
+            font.set_glyph(c, Bitset::from_u32(get_glyph(c)));
+        }
+        let mut font = font.with_tight_metrics(SPACE_WIDTH);
+        for &(left, right, adjustment) in KERNING_PAIRS {
+            font.set_kerning(left, right, adjustment);
+        }
+        font
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Width and height of every glyph, in pixels
+    pub fn cell_size(&self) -> Size {
+        self.cell
+    }
+
+    /// Rows from the top of the cell down to where text sits
+    pub fn baseline(&self) -> usize {
+        self.baseline
+    }
+
+    pub fn with_baseline(mut self, baseline: usize) -> Self {
+        self.baseline = baseline;
+        self
+    }
+
+    /// Distance from one line's top to the next
+    pub fn line_height(&self) -> usize {
+        self.line_height
+    }
+
+    pub fn with_line_height(mut self, line_height: usize) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    /// Blank pixels between neighbouring glyphs. Stays the same at every
+    /// scale.
+    pub fn spacing(&self) -> usize {
+        self.spacing
+    }
+
+    pub fn with_spacing(mut self, spacing: usize) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Whether every glyph takes up a whole cell, ignoring metrics and
+    /// kerning
+    pub fn monospace(&self) -> bool {
+        self.monospace
+    }
+
+    pub fn with_monospace(mut self, monospace: bool) -> Self {
+        self.monospace = monospace;
+        self
+    }
+
+    /// `bitmap` holds the cell's pixels row by row, top left first. The
+    /// glyph starts out with bearings that keep it where it is in the cell.
+    pub fn set_glyph(&mut self, c: char, bitmap: Bitset) {
+        self.glyphs.insert(c, Glyph::new(bitmap, self.cell));
+        self.id = next_id();
+    }
+
+    pub fn with_glyph(mut self, c: char, bitmap: Bitset) -> Self {
+        self.set_glyph(c, bitmap);
+        self
+    }
+
+    pub fn with_fallback(mut self, bitmap: Bitset) -> Self {
+        self.fallback = Glyph::new(bitmap, self.cell);
+        self.id = next_id();
+        self
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyphs.contains_key(&c)
+    }
+
+    fn entry(&self, c: char) -> &Glyph {
+        self.glyphs.get(&c).unwrap_or(&self.fallback)
+    }
+
+    /// What `c` draws as, which is the fallback if it has no glyph
+    pub fn glyph(&self, c: char) -> &Bitset {
+        &self.entry(c).bitmap
+    }
+
+    /// The first column of `c`'s glyph with any pixels set, and how many
+    /// columns from there on have some. Blank glyphs have no ink: `(0, 0)`.
+    pub fn ink(&self, c: char) -> (usize, usize) {
+        self.entry(c).ink
+    }
+
+    pub fn metrics(&self, c: char) -> GlyphMetrics {
+        self.entry(c).metrics
+    }
+
+    /// Does nothing if `c` has no glyph of its own
+    pub fn set_metrics(&mut self, c: char, metrics: GlyphMetrics) {
+        if let Some(glyph) = self.glyphs.get_mut(&c) {
+            glyph.metrics = metrics;
+        }
+    }
+
+    pub fn with_metrics(mut self, c: char, metrics: GlyphMetrics) -> Self {
+        self.set_metrics(c, metrics);
+        self
+    }
+
+    /// Trims every glyph's bearings down to its ink, so narrow glyphs take
+    /// up less room. Blank glyphs, like space, are `blank_width` wide.
+    pub fn with_tight_metrics(mut self, blank_width: usize) -> Self {
+        for glyph in self.glyphs.values_mut().chain(Some(&mut self.fallback)) {
+            let right_bearing = if glyph.ink.1 == 0 { blank_width as isize } else { 0 };
+            glyph.metrics = GlyphMetrics { left_bearing: 0, right_bearing };
+        }
+        self
+    }
+
+    /// Extra room between `left` and `right` when they're next to each
+    /// other, in glyph pixels
+    pub fn kerning(&self, left: char, right: char) -> isize {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0)
+    }
+
+    pub fn set_kerning(&mut self, left: char, right: char, adjustment: isize) {
+        self.kerning.insert((left, right), adjustment);
+    }
+
+    pub fn with_kerning(mut self, left: char, right: char, adjustment: isize) -> Self {
+        self.set_kerning(left, right, adjustment);
+        self
+    }
+
+    /// Horizontal distance from one glyph's left edge to the next's, when
+    /// monospaced
+    pub fn advance(&self, scale: usize) -> usize {
+        self.cell.0 * scale + self.spacing
+    }
+
+    /// How far the pen moves past `c` when `next` follows it, spacing
+    /// included
+    pub fn pen_advance(&self, c: char, next: Option<char>, scale: usize) -> isize {
+        if self.monospace {
+            return self.advance(scale) as isize;
+        }
+        let kerning = next.map_or(0, |next| self.kerning(c, next));
+        (self.entry(c).width() + kerning) * scale as isize + self.spacing as isize
+    }
+
+    /// Where the left edge of `c`'s cell goes, relative to the pen
+    pub fn cell_offset(&self, c: char, scale: usize) -> isize {
+        if self.monospace {
+            return 0;
+        }
+        let glyph = self.entry(c);
+        (glyph.metrics.left_bearing - glyph.ink.0 as isize) * scale as isize
+    }
+
+    /// Each character of `text` with where the left edge of its cell goes
+    /// at `scale`, relative to the start of the text. Glyphs with a
+    /// negative left bearing can start left of it.
+    pub fn layout<'a>(&'a self, text: &'a str, scale: usize) -> impl Iterator<Item = (char, isize)> + 'a {
+        let mut chars = text.chars().peekable();
+        let mut pen = 0;
+        core::iter::from_fn(move || {
+            let c = chars.next()?;
+            let x = pen + self.cell_offset(c, scale);
+            pen += self.pen_advance(c, chars.peek().copied(), scale);
+            Some((c, x))
+        })
+    }
+
+    /// How much room `text` takes up on one line at `scale`
+    pub fn measure(&self, text: &str, scale: usize) -> Size {
+        if text.is_empty() {
+            return (0, 0);
+        }
+        let mut chars = text.chars().peekable();
+        let mut width = -(self.spacing as isize);
+        while let Some(c) = chars.next() {
+            width += self.pen_advance(c, chars.peek().copied(), scale);
+        }
+        (width.max(0) as usize, self.cell.1 * scale)
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // What the font fixtures contain; see tests/fixtures/generate.py
+    pub const A: [&str; 8] = ["..X...", ".X.X..", "X...X.", "X...X.", "XXXXX.", "X...X.", "......", "......"];
+    pub const E_ACUTE: [&str; 8] = ["...X..", "..X...", ".XXX..", "XXXXX.", "X.....", ".XXXX.", "......", "......"];
+    pub const J: [&str; 8] = ["....X.", "......", "....X.", "....X.", "....X.", "X...X.", ".XXX..", "......"];
+    pub const REPLACEMENT: [&str; 8] = ["XXXXX.", "X...X.", "X.X.X.", "X...X.", "XXXXX.", "......", "......", "......"];
+
+    /// Checks `c`'s glyph against rows of `X`s and `.`s, with anything
+    /// right of them blank
+    pub fn assert_glyph(font: &Font, c: char, expected: &[&str]) {
+        let (width, height) = font.cell_size();
+        assert_eq!(height, expected.len());
+        for (y, row) in expected.iter().enumerate() {
+            let actual: alloc::string::String =
+                (0..width).map(|x| if font.glyph(c).get(y * width + x) { 'X' } else { '.' }).collect();
+            assert_eq!(actual, alloc::format!("{:.<1$}", row, width), "row {} of {:?}", y, c);
+        }
+    }
+
+    #[test]
+    fn test_sniffs_format() {
+        assert!(decode(include_bytes!("../../tests/fixtures/font6x8.bdf")).is_ok());
+        assert!(decode(include_bytes!("../../tests/fixtures/font8x8.psf")).is_ok());
+        assert!(decode(include_bytes!("../../tests/fixtures/font6x8.psfu")).is_ok());
+        assert_eq!(decode(b"GIF89a").err(), Some(FontError::UnknownFormat));
+    }
+
+    #[test]
+    fn test_builtin() {
+        let font = Font::builtin();
+        assert!(font.has_glyph('a') && !font.has_glyph('é'));
+        assert!(font.glyph('é').get(0));
+        // ! is a single column of ink
+        assert_eq!(font.measure("A!", 2), (13, 10));
+        assert_eq!(font.measure("", 2), (0, 0));
+        let font = font.with_monospace(true);
+        assert_eq!(font.measure("A!", 2), (21, 10));
+        assert_eq!(font.layout("A!", 2).collect::<alloc::vec::Vec<_>>(), [('A', 0), ('!', 11)]);
+    }
+
+    #[test]
+    fn test_proportional_layout() {
+        // a single column of ink in the middle of a 3x1 cell
+        let mut dot = Bitset::new(3);
+        dot.set(1, true);
+        let font = Font::new((3, 1)).with_glyph('.', dot).with_glyph(' ', Bitset::new(3));
+        assert_eq!(font.ink('.'), (1, 1));
+        // to begin with, glyphs keep their place in the cell
+        assert_eq!(font.layout("..", 1).collect::<alloc::vec::Vec<_>>(), [('.', 0), ('.', 4)]);
+        let font = font.with_metrics('.', GlyphMetrics { left_bearing: -1, right_bearing: 2 }).with_kerning('.', ' ', -1);
+        // the cell shifts so the ink lands a pixel left of the pen
+        assert_eq!(font.layout(". .", 2).collect::<alloc::vec::Vec<_>>(), [('.', -4), (' ', 3), ('.', 6)]);
+        assert_eq!(font.measure(". .", 2), (14, 2));
+        let font = font.with_tight_metrics(2);
+        assert_eq!(font.metrics('.'), GlyphMetrics { left_bearing: 0, right_bearing: 0 });
+        assert_eq!(font.metrics(' '), GlyphMetrics { left_bearing: 0, right_bearing: 2 });
+    }
+
+    #[test]
+    fn test_builtin_kerning_keeps_glyphs_apart() {
+        let font = Font::builtin();
+        for &(left, right, _) in KERNING_PAIRS {
+            let text = alloc::format!("{}{}", left, right);
+            let mut ink = alloc::vec::Vec::new();
+            for (c, x) in font.layout(&text, 1) {
+                for y in 0..GLYPH_SIZE {
+                    for glyph_x in 0..GLYPH_SIZE {
+                        if font.glyph(c).get(y * GLYPH_SIZE + glyph_x) {
+                            ink.push((c == right, x + glyph_x as isize, y as isize));
+                        }
+                    }
+                }
+            }
+            for &(_, x, y) in ink.iter().filter(|pixel| !pixel.0) {
+                let touching = ink.iter().any(|&(second, x2, y2)| second && (x2 - x).abs() + (y2 - y).abs() <= 1);
+                assert!(!touching, "{:?} touch", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_editing_glyphs_changes_the_id() {
+        let mut font = Font::new((8, 8));
+        let id = font.id();
+        font.set_glyph('x', Bitset::new(64));
+        assert_ne!(font.id(), id);
+    }
+}
@@ -0,0 +1,191 @@
+//! PC Screen Font parsing, versions 1 and 2: the format Linux console
+//! fonts like Terminus and Spleen ship in. Neither version records a
+//! baseline, so it's left at the bottom of the cell.
+
+use alloc::vec::Vec;
+use core::str;
+
+use super::{check_cell_size, unpack_rows, Font, FontError};
+use crate::bitset::Bitset;
+
+pub const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+pub const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+const PSF1_HEADER_LEN: usize = 4;
+/// 512 glyphs instead of 256
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODESEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_STARTSEQ: u16 = 0xfffe;
+
+const PSF2_HEADER_LEN: usize = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_STARTSEQ: u8 = 0xfe;
+
+pub fn decode(bytes: &[u8]) -> Result<Font, FontError> {
+    if bytes.starts_with(&PSF1_MAGIC) {
+        decode_psf1(bytes)
+    } else if bytes.starts_with(&PSF2_MAGIC) {
+        decode_psf2(bytes)
+    } else {
+        Err(FontError::UnknownFormat)
+    }
+}
+
+fn decode_psf1(bytes: &[u8]) -> Result<Font, FontError> {
+    if bytes.len() < PSF1_HEADER_LEN {
+        return Err(FontError::UnexpectedEof);
+    }
+    let (mode, height) = (bytes[2], bytes[3] as usize);
+    check_cell_size(8, height)?;
+    let count = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+    let end = PSF1_HEADER_LEN + count * height;
+    let data = bytes.get(PSF1_HEADER_LEN..end).ok_or(FontError::UnexpectedEof)?;
+    let glyphs: Vec<Bitset> = data.chunks_exact(height).map(|glyph| unpack_rows(glyph, (8, height))).collect();
+
+    let mut chars = Vec::new();
+    if mode & (PSF1_MODEHASTAB | PSF1_MODESEQ) != 0 {
+        // each glyph's code points, then any sequences, then a separator
+        let mut table = bytes[end..].chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+        for index in 0..count {
+            let mut in_sequence = false;
+            loop {
+                match table.next().ok_or(FontError::UnexpectedEof)? {
+                    PSF1_SEPARATOR => break,
+                    PSF1_STARTSEQ => in_sequence = true,
+                    // lone surrogates can't be drawn anyway
+                    code if !in_sequence => chars.extend(char::from_u32(code as u32).map(|c| (c, index))),
+                    _ => {}
+                }
+            }
+        }
+    } else {
+        chars.extend((0..count as u32).filter_map(char::from_u32).map(|c| (c, c as usize)));
+    }
+    Ok(build((8, height), glyphs, chars))
+}
+
+fn decode_psf2(bytes: &[u8]) -> Result<Font, FontError> {
+    if bytes.len() < PSF2_HEADER_LEN {
+        return Err(FontError::UnexpectedEof);
+    }
+    let field = |i: usize| u32::from_le_bytes([bytes[4 * i], bytes[4 * i + 1], bytes[4 * i + 2], bytes[4 * i + 3]]);
+    let (version, header_len, flags) = (field(1), field(2) as usize, field(3));
+    let (count, glyph_len, height, width) = (field(4) as usize, field(5) as usize, field(6) as usize, field(7) as usize);
+    if version != 0 {
+        return Err(FontError::Unsupported("PSF2 version"));
+    }
+    if header_len < PSF2_HEADER_LEN {
+        return Err(FontError::Corrupt("header too short"));
+    }
+    check_cell_size(width, height)?;
+    if glyph_len != width.div_ceil(8) * height {
+        return Err(FontError::Corrupt("glyph size doesn't match width and height"));
+    }
+    let end = count.checked_mul(glyph_len).and_then(|len| len.checked_add(header_len)).ok_or(FontError::UnexpectedEof)?;
+    let data = bytes.get(header_len..end).ok_or(FontError::UnexpectedEof)?;
+    let glyphs: Vec<Bitset> = data.chunks_exact(glyph_len).map(|glyph| unpack_rows(glyph, (width, height))).collect();
+
+    let mut chars = Vec::new();
+    if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+        // each glyph's characters in UTF-8, then any sequences, then a
+        // separator
+        let mut table = &bytes[end..];
+        for index in 0..count {
+            let entry_len = table.iter().position(|&byte| byte == PSF2_SEPARATOR).ok_or(FontError::UnexpectedEof)?;
+            let entry = &table[..entry_len];
+            let singles = entry.split(|&byte| byte == PSF2_STARTSEQ).next().unwrap_or_default();
+            let singles = str::from_utf8(singles).map_err(|_| FontError::Corrupt("invalid UTF-8 in unicode table"))?;
+            chars.extend(singles.chars().map(|c| (c, index)));
+            table = &table[entry_len + 1..];
+        }
+    } else {
+        chars.extend((0..count as u32).filter_map(char::from_u32).map(|c| (c, c as usize)));
+    }
+    Ok(build((width, height), glyphs, chars))
+}
+
+/// Console fonts pack glyphs edge to edge, with any gaps drawn into them.
+/// Uses the replacement character's glyph, if there is one, for anything
+/// missing.
+fn build(cell: (usize, usize), glyphs: Vec<Bitset>, chars: Vec<(char, usize)>) -> Font {
+    let mut font = Font::new(cell).with_spacing(0).with_line_height(cell.1);
+    if let Some(&(_, index)) = chars.iter().find(|&&(c, _)| c == char::REPLACEMENT_CHARACTER) {
+        font = font.with_fallback(glyphs[index].clone());
+    }
+    for (c, index) in chars {
+        font.set_glyph(c, glyphs[index].clone());
+    }
+    font
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::{assert_glyph, A, E_ACUTE, REPLACEMENT};
+    use alloc::vec;
+
+    #[test]
+    fn test_psf1() {
+        let font = decode(include_bytes!("../../tests/fixtures/font8x8.psf")).unwrap();
+        assert_eq!(font.cell_size(), (8, 8));
+        assert_glyph(&font, 'A', &A);
+        // without a unicode table, glyphs are in Latin-1 order
+        assert_glyph(&font, 'é', &E_ACUTE);
+        assert!(font.has_glyph('\0') && !font.has_glyph('Ā'));
+    }
+
+    #[test]
+    fn test_psf2_with_unicode_table() {
+        let font = decode(include_bytes!("../../tests/fixtures/font6x8.psfu")).unwrap();
+        assert_eq!(font.cell_size(), (6, 8));
+        assert_eq!(font.spacing(), 0);
+        assert_glyph(&font, 'A', &A);
+        // Greek capital alpha shares A's glyph
+        assert_glyph(&font, 'Α', &A);
+        assert_glyph(&font, 'é', &E_ACUTE);
+        // only in a sequence, so not mapped on its own
+        assert!(!font.has_glyph('\u{301}'));
+        assert_glyph(&font, 'Z', &REPLACEMENT);
+    }
+
+    #[test]
+    fn test_psf1_with_unicode_table() {
+        let mut bytes = vec![0x36, 0x04, PSF1_MODEHASTAB, 1];
+        bytes.extend((0..256).map(|i| if i == 1 { 0x80 } else { 0 }));
+        for index in 0..256u16 {
+            if index == 1 {
+                // 'x', then the sequence "x" + U+0301
+                bytes.extend([b'x', 0, 0xfe, 0xff, b'x', 0, 0x01, 0x03]);
+            }
+            bytes.extend([0xff, 0xff]);
+        }
+        let font = decode(&bytes).unwrap();
+        assert!(font.glyph('x').get(0));
+        assert!(!font.has_glyph('\u{301}') && !font.has_glyph('\u{1}'));
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = include_bytes!("../../tests/fixtures/font6x8.psfu");
+        assert_eq!(decode(&bytes[..20]).err(), Some(FontError::UnexpectedEof));
+        assert_eq!(decode(&bytes[..40]).err(), Some(FontError::UnexpectedEof));
+        // cut off in the middle of the unicode table
+        assert_eq!(decode(&bytes[..bytes.len() - 2]).err(), Some(FontError::UnexpectedEof));
+
+        let mut wrong_size = bytes.to_vec();
+        wrong_size[20] += 1;
+        assert_eq!(decode(&wrong_size).err(), Some(FontError::Corrupt("glyph size doesn't match width and height")));
+        let mut huge = bytes.to_vec();
+        huge[24..28].copy_from_slice(&100_000u32.to_le_bytes());
+        assert_eq!(decode(&huge).err(), Some(FontError::Unsupported("glyphs too large")));
+        let mut too_many = bytes.to_vec();
+        too_many[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&too_many).err(), Some(FontError::UnexpectedEof));
+
+        assert_eq!(decode(&[0x36, 0x04, 0, 0]).err(), Some(FontError::Corrupt("zero width or height")));
+        assert_eq!(decode(b"not a font").err(), Some(FontError::UnknownFormat));
+    }
+}
@@ -0,0 +1,320 @@
+//! Gradients, and [Paint] for anything that can fill with either a flat
+//! color or a gradient.
+//!
+//! Like the Canvas API, a gradient's geometry is in the local space of
+//! whatever's drawn with it, so it moves with the transform. Drawables that
+//! take one, like [Rectangle](crate::ui::Rectangle), measure it from their
+//! own top left.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::PI;
+
+use crate::{
+    math,
+    pixels::{Color, TRANSPARENT},
+    ui::Size,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientShape {
+    /// Offset 0 at `from`, 1 at `to`, constant along lines perpendicular
+    /// to the two
+    Linear { from: (f32, f32), to: (f32, f32) },
+    /// Offset 0 at `center`, 1 at `radius` away
+    Radial { center: (f32, f32), radius: f32 },
+    /// Sweeps clockwise around `center`, offset 0 at `angle` (radians, 0
+    /// pointing right) and 1 back at the start
+    Conic { center: (f32, f32), angle: f32 },
+}
+
+/// What happens past the first and last stops
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Spread {
+    /// Keep the end colors
+    #[default]
+    Pad,
+    /// Start over from the first stop
+    Repeat,
+    /// Run back and forth, like a ping-pong
+    Reflect,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    shape: GradientShape,
+    /// Sorted by offset
+    stops: Vec<(f32, Color)>,
+    spread: Spread,
+    /// How far the stops slide along the gradient each tick
+    scroll: f32,
+    /// Radians the shape turns each tick
+    spin: f32,
+    tick: usize,
+}
+
+impl Gradient {
+    /// Starts with no stops, which paints nothing
+    pub fn new(shape: GradientShape) -> Self {
+        Self {
+            shape,
+            stops: vec![],
+            spread: Spread::default(),
+            scroll: 0.0,
+            spin: 0.0,
+            tick: 0,
+        }
+    }
+
+    pub fn linear(from: (f32, f32), to: (f32, f32)) -> Self {
+        Self::new(GradientShape::Linear { from, to })
+    }
+
+    /// A linear gradient across a box `size` big with its top left at the
+    /// origin, running at `angle` radians clockwise from left-to-right.
+    /// Like CSS, the line is long enough that the box's corners land
+    /// exactly on the first and last stops.
+    pub fn linear_across((width, height): Size, angle: f32) -> Self {
+        let (width, height) = (width as f32, height as f32);
+        let (sin, cos) = (math::sin(angle), math::cos(angle));
+        let half_length = (width * cos.abs() + height * sin.abs()) / 2.0;
+        let (cx, cy) = (width / 2.0, height / 2.0);
+        let (dx, dy) = (cos * half_length, sin * half_length);
+        Self::linear((cx - dx, cy - dy), (cx + dx, cy + dy))
+    }
+
+    pub fn radial(center: (f32, f32), radius: f32) -> Self {
+        Self::new(GradientShape::Radial { center, radius })
+    }
+
+    pub fn conic(center: (f32, f32), angle: f32) -> Self {
+        Self::new(GradientShape::Conic { center, angle })
+    }
+
+    /// Adds `color` at `offset` along the gradient, usually 0.0 to 1.0.
+    /// Stops at the same offset make a hard edge, in the order added.
+    pub fn with_stop(mut self, offset: f32, color: Color) -> Self {
+        let index = self.stops.iter().position(|&(existing, _)| existing > offset).unwrap_or(self.stops.len());
+        self.stops.insert(index, (offset, color));
+        self
+    }
+
+    pub fn with_spread(mut self, spread: Spread) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    /// Slides the stops `per_tick` further along each tick, so e.g. 0.01
+    /// loops every 100 ticks. Pair with [Spread::Repeat] or
+    /// [Spread::Reflect], or everything ends up the last stop's color.
+    pub fn scrolling(mut self, per_tick: f32) -> Self {
+        self.scroll = per_tick;
+        self
+    }
+
+    /// Turns the gradient clockwise about its middle, `radians_per_tick`
+    /// each tick. Radial gradients look the same at any angle.
+    pub fn spinning(mut self, radians_per_tick: f32) -> Self {
+        self.spin = radians_per_tick;
+        self
+    }
+
+    /// Which frame of the animation to paint
+    pub fn set_tick(&mut self, tick: usize) {
+        self.tick = tick;
+    }
+
+    pub fn shape(&self) -> GradientShape {
+        self.shape
+    }
+
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+
+    /// The same gradient with every stop's color passed through `f`, e.g.
+    /// to darken a pressed button
+    pub fn map_colors(&self, f: impl Fn(Color) -> Color) -> Self {
+        Self {
+            stops: self.stops.iter().map(|&(offset, color)| (offset, f(color))).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Where `(x, y)` falls along the gradient, before spreading
+    fn offset_at(&self, (x, y): (f32, f32)) -> f32 {
+        let turn = self.spin * self.tick as f32;
+        match self.shape {
+            GradientShape::Linear { from, to } => {
+                let (mid_x, mid_y) = ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0);
+                let (sin, cos) = (math::sin(turn), math::cos(turn));
+                let (half_x, half_y) = ((to.0 - from.0) / 2.0, (to.1 - from.1) / 2.0);
+                let (dx, dy) = (half_x * cos - half_y * sin, half_x * sin + half_y * cos);
+                let length_squared = 4.0 * (dx * dx + dy * dy);
+                if length_squared == 0.0 {
+                    return 0.0;
+                }
+                // projected onto the line, measured from `from`
+                ((x - mid_x) * 2.0 * dx + (y - mid_y) * 2.0 * dy) / length_squared + 0.5
+            }
+            GradientShape::Radial { center, radius } => {
+                if radius <= 0.0 {
+                    return 1.0;
+                }
+                let (dx, dy) = (x - center.0, y - center.1);
+                math::sqrt(dx * dx + dy * dy) / radius
+            }
+            GradientShape::Conic { center, angle } => {
+                let swept = math::atan2(y - center.1, x - center.0) - angle - turn;
+                let t = swept / (2.0 * PI);
+                t - math::floor(t)
+            }
+        }
+    }
+
+    /// The color at local point `(x, y)`
+    pub fn color_at(&self, point: (f32, f32)) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return TRANSPARENT,
+        };
+        let mut t = self.offset_at(point) - self.scroll * self.tick as f32;
+        // repeating works over the stops' own range, which needn't be 0-1
+        let span = last.0 - first.0;
+        if span > 0.0 {
+            let cycles = (t - first.0) / span;
+            t = match self.spread {
+                Spread::Pad => t,
+                Spread::Repeat => first.0 + (cycles - math::floor(cycles)) * span,
+                Spread::Reflect => {
+                    let bounce = cycles - 2.0 * math::floor(cycles / 2.0);
+                    first.0 + (1.0 - (bounce - 1.0).abs()) * span
+                }
+            };
+        }
+
+        if t < first.0 || t.is_nan() {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        // the last stop at or before `t`, so hard edges pick the later color
+        let next = self.stops.iter().position(|&(offset, _)| offset > t).unwrap_or(self.stops.len() - 1);
+        let (start, end) = (self.stops[next - 1], self.stops[next]);
+        start.1.lerp(end.1, (t - start.0) / (end.0 - start.0))
+    }
+}
+
+/// What to fill with. Anything that takes `impl Into<Paint>` takes a
+/// [Color] or a `&Gradient`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Paint<'a> {
+    Solid(Color),
+    Gradient(&'a Gradient),
+}
+
+impl Paint<'_> {
+    /// The color at local point `(x, y)`
+    pub fn color_at(&self, point: (f32, f32)) -> Color {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(gradient) => gradient.color_at(point),
+        }
+    }
+}
+
+impl From<Color> for Paint<'_> {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl<'a> From<&'a Gradient> for Paint<'a> {
+    fn from(gradient: &'a Gradient) -> Self {
+        Paint::Gradient(gradient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{BLACK, BLUE, RED, WHITE};
+    use core::f32::consts::FRAC_PI_2;
+
+    fn black_to_white(gradient: Gradient) -> Gradient {
+        gradient.with_stop(0.0, BLACK).with_stop(1.0, WHITE)
+    }
+
+    #[test]
+    fn test_linear() {
+        let gradient = black_to_white(Gradient::linear((0.0, 0.0), (10.0, 0.0)));
+        assert_eq!(gradient.color_at((-5.0, 3.0)), BLACK);
+        assert_eq!(gradient.color_at((5.0, 100.0)), Color::rgb(128, 128, 128));
+        assert_eq!(gradient.color_at((20.0, 0.0)), WHITE);
+
+        // top to bottom, corners on the ends
+        let down = black_to_white(Gradient::linear_across((4, 10), FRAC_PI_2));
+        assert_eq!(down.color_at((0.0, 0.0)), BLACK);
+        assert_eq!(down.color_at((4.0, 10.0)), WHITE);
+        let diagonal = black_to_white(Gradient::linear_across((10, 10), FRAC_PI_2 / 2.0));
+        assert_eq!(diagonal.color_at((0.0, 0.0)), BLACK);
+        assert_eq!(diagonal.color_at((10.0, 0.0)), Color::rgb(128, 128, 128));
+    }
+
+    #[test]
+    fn test_radial_and_conic() {
+        let radial = black_to_white(Gradient::radial((5.0, 5.0), 4.0));
+        assert_eq!(radial.color_at((5.0, 5.0)), BLACK);
+        assert_eq!(radial.color_at((5.0, 7.0)), Color::rgb(128, 128, 128));
+        assert_eq!(radial.color_at((0.0, 0.0)), WHITE);
+
+        let conic = black_to_white(Gradient::conic((0.0, 0.0), 0.0));
+        assert_eq!(conic.color_at((1.0, 0.0)), BLACK);
+        // a quarter turn clockwise, i.e. down
+        assert_eq!(conic.color_at((0.0, 1.0)), Color::rgb(64, 64, 64));
+        assert_eq!(conic.color_at((-1.0, 0.0)), Color::rgb(128, 128, 128));
+    }
+
+    #[test]
+    fn test_stops() {
+        let gradient = Gradient::linear((0.0, 0.0), (4.0, 0.0))
+            .with_stop(1.0, BLUE)
+            .with_stop(0.5, RED)
+            .with_stop(0.5, WHITE);
+        assert_eq!(gradient.stops().iter().map(|stop| stop.1).collect::<Vec<_>>(), vec![RED, WHITE, BLUE]);
+        // hard edge at the middle
+        assert_eq!(gradient.color_at((1.0, 0.0)), RED);
+        assert_eq!(gradient.color_at((2.0, 0.0)), WHITE);
+        assert_eq!(Gradient::radial((0.0, 0.0), 1.0).color_at((0.0, 0.0)), TRANSPARENT);
+    }
+
+    #[test]
+    fn test_spread() {
+        let pad = black_to_white(Gradient::linear((0.0, 0.0), (10.0, 0.0)));
+        let repeat = pad.clone().with_spread(Spread::Repeat);
+        let reflect = pad.clone().with_spread(Spread::Reflect);
+        assert_eq!(pad.color_at((12.5, 0.0)), WHITE);
+        assert_eq!(repeat.color_at((12.5, 0.0)), Color::rgb(64, 64, 64));
+        assert_eq!(repeat.color_at((-7.5, 0.0)), Color::rgb(64, 64, 64));
+        assert_eq!(reflect.color_at((12.5, 0.0)), Color::rgb(191, 191, 191));
+        assert_eq!(reflect.color_at((-2.5, 0.0)), Color::rgb(64, 64, 64));
+    }
+
+    #[test]
+    fn test_animation() {
+        let mut scrolling = black_to_white(Gradient::linear((0.0, 0.0), (10.0, 0.0)))
+            .with_spread(Spread::Repeat)
+            .scrolling(0.25);
+        assert_eq!(scrolling.color_at((5.0, 0.0)), Color::rgb(128, 128, 128));
+        scrolling.set_tick(1);
+        assert_eq!(scrolling.color_at((5.0, 0.0)), Color::rgb(64, 64, 64));
+        scrolling.set_tick(4);
+        assert_eq!(scrolling.color_at((5.0, 0.0)), Color::rgb(128, 128, 128));
+
+        // a quarter turn makes it run top to bottom
+        let mut spinning = black_to_white(Gradient::linear((0.0, 5.0), (10.0, 5.0))).spinning(FRAC_PI_2);
+        spinning.set_tick(1);
+        assert_eq!(spinning.color_at((0.0, 0.0)), BLACK);
+        assert_eq!(spinning.color_at((10.0, 10.0)), WHITE);
+    }
+}
@@ -0,0 +1,198 @@
+//! Windows BMP decoding for uncompressed and bitfield images at 1, 4, 8,
+//! 16, 24 and 32 bits per pixel. RLE and embedded JPEG/PNG aren't handled.
+
+use alloc::vec;
+use super::{check_dimensions, ImageError};
+use crate::pixels::{PixelBuffer, NUM_CHANNELS};
+
+pub const MAGIC: [u8; 2] = *b"BM";
+const FILE_HEADER_LEN: usize = 14;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+fn u16_at(bytes: &[u8], pos: usize) -> Result<u16, ImageError> {
+    let b = bytes.get(pos..pos + 2).ok_or(ImageError::UnexpectedEof)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> Result<u32, ImageError> {
+    let b = bytes.get(pos..pos + 4).ok_or(ImageError::UnexpectedEof)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Extracts and rescales one channel described by a bitfield mask to 8 bits
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    // in 64 bits, as masks can be up to 32 bits wide
+    ((((value & mask) >> shift) as u64) * 255 / max) as u8
+}
+
+pub fn decode(bytes: &[u8]) -> Result<PixelBuffer, ImageError> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err(ImageError::UnknownFormat);
+    }
+    let data_offset = u32_at(bytes, 10)? as usize;
+    let info_len = u32_at(bytes, FILE_HEADER_LEN)? as usize;
+    if info_len < 40 {
+        return Err(ImageError::Unsupported("OS/2 bitmap header"));
+    }
+    let info = FILE_HEADER_LEN;
+    let width = u32_at(bytes, info + 4)? as i32;
+    let raw_height = u32_at(bytes, info + 8)? as i32;
+    let bits_per_pixel = u16_at(bytes, info + 14)? as usize;
+    let compression = u32_at(bytes, info + 16)?;
+    let palette_len = u32_at(bytes, info + 32)? as usize;
+
+    if width <= 0 || raw_height == 0 {
+        return Err(ImageError::Corrupt("invalid dimensions"));
+    }
+    // positive heights are stored bottom row first
+    let bottom_up = raw_height > 0;
+    let (width, height) = (width as usize, raw_height.unsigned_abs() as usize);
+    check_dimensions(width, height)?;
+
+    let (masks, has_alpha_mask) = match (compression, bits_per_pixel) {
+        (BI_RGB, 16) => ([0x7c00, 0x03e0, 0x001f, 0], false),
+        (BI_RGB, 32) => ([0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000], true),
+        (BI_RGB, 1 | 4 | 8 | 24) => ([0; 4], false),
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            // masks follow a 40 byte header, or are part of a V4/V5 one
+            let alpha = if compression == BI_ALPHABITFIELDS || info_len >= 56 {
+                u32_at(bytes, info + 52)?
+            } else {
+                0
+            };
+            let masks = [u32_at(bytes, info + 40)?, u32_at(bytes, info + 44)?, u32_at(bytes, info + 48)?, alpha];
+            (masks, alpha != 0)
+        }
+        (BI_RGB | BI_BITFIELDS | BI_ALPHABITFIELDS, _) => return Err(ImageError::Corrupt("invalid bit depth")),
+        _ => return Err(ImageError::Unsupported("compressed bitmap")),
+    };
+
+    let mut palette = vec![];
+    if bits_per_pixel <= 8 {
+        let entries = if palette_len == 0 { 1 << bits_per_pixel } else { palette_len.min(256) };
+        // the header length comes from the file, so stop before it runs off the end
+        let mut pos = info.checked_add(info_len).filter(|&pos| pos <= bytes.len()).ok_or(ImageError::UnexpectedEof)?;
+        if compression == BI_BITFIELDS && info_len == 40 {
+            pos += 12;
+        }
+        for _ in 0..entries {
+            let bgr = bytes.get(pos..pos + 4).ok_or(ImageError::UnexpectedEof)?;
+            palette.push([bgr[2], bgr[1], bgr[0], 255]);
+            pos += 4;
+        }
+    }
+
+    let stride = (width * bits_per_pixel).div_ceil(32) * 4;
+    let data_end = stride
+        .checked_mul(height)
+        .and_then(|len| data_offset.checked_add(len))
+        .ok_or(ImageError::UnexpectedEof)?;
+    let pixels = bytes.get(data_offset..data_end).ok_or(ImageError::UnexpectedEof)?;
+
+    let mut rgba = vec![0u8; width * height * NUM_CHANNELS];
+    let mut any_alpha = false;
+    for row in 0..height {
+        let y = if bottom_up { height - 1 - row } else { row };
+        let line = &pixels[row * stride..(row + 1) * stride];
+        for x in 0..width {
+            let pixel = match bits_per_pixel {
+                1 | 4 | 8 => {
+                    let bit = x * bits_per_pixel;
+                    let shift = 8 - bits_per_pixel - bit % 8;
+                    let index = (line[bit / 8] >> shift) & ((1u16 << bits_per_pixel) - 1) as u8;
+                    *palette.get(index as usize).ok_or(ImageError::Corrupt("palette index out of range"))?
+                }
+                24 => [line[x * 3 + 2], line[x * 3 + 1], line[x * 3], 255],
+                _ => {
+                    let value = if bits_per_pixel == 16 {
+                        u16::from_le_bytes([line[x * 2], line[x * 2 + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes([line[x * 4], line[x * 4 + 1], line[x * 4 + 2], line[x * 4 + 3]])
+                    };
+                    let alpha = if has_alpha_mask { channel(value, masks[3]) } else { 255 };
+                    [channel(value, masks[0]), channel(value, masks[1]), channel(value, masks[2]), alpha]
+                }
+            };
+            any_alpha |= pixel[3] != 0;
+            let target = (y * width + x) * NUM_CHANNELS;
+            rgba[target..target + NUM_CHANNELS].copy_from_slice(&pixel);
+        }
+    }
+
+    // plenty of writers leave the unused byte of 32-bit pixels at zero
+    if !any_alpha {
+        for pixel in rgba.chunks_exact_mut(NUM_CHANNELS) {
+            pixel[3] = 255;
+        }
+    }
+
+    Ok(PixelBuffer::from_rgba(width, height, rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::tests::{assert_pattern, pattern},
+        pixels::Color,
+    };
+
+    fn opaque(x: usize, y: usize) -> Color {
+        pattern(x, y).with_alpha(255)
+    }
+
+    #[test]
+    fn test_24_bit_bottom_up() {
+        let image = decode(include_bytes!("../../tests/fixtures/rgb24.bmp")).unwrap();
+        assert_pattern(&image, opaque);
+    }
+
+    #[test]
+    fn test_32_bit_bitfields_top_down() {
+        let image = decode(include_bytes!("../../tests/fixtures/bitfields32.bmp")).unwrap();
+        assert_pattern(&image, pattern);
+    }
+
+    #[test]
+    fn test_4_bit_palette() {
+        let image = decode(include_bytes!("../../tests/fixtures/indexed4.bmp")).unwrap();
+        assert_pattern(&image, |x, y| match (x + y) % 3 {
+            0 => Color::new(255, 0, 0, 255),
+            1 => Color::new(0, 255, 0, 255),
+            _ => Color::new(0, 0, 255, 255),
+        });
+    }
+
+    #[test]
+    fn test_rle_unsupported() {
+        let mut bytes = include_bytes!("../../tests/fixtures/indexed4.bmp").to_vec();
+        bytes[FILE_HEADER_LEN + 16] = 2;
+        assert_eq!(decode(&bytes).err(), Some(ImageError::Unsupported("compressed bitmap")));
+    }
+
+    #[test]
+    fn test_wide_masks() {
+        assert_eq!(channel(0x0fff_ffff, 0x0fff_ffff), 255);
+        assert_eq!(channel(0x0800_0000, 0x0fff_ffff), 127);
+        assert_eq!(channel(u32::MAX, u32::MAX), 255);
+    }
+
+    #[test]
+    fn test_huge_offsets() {
+        let original = include_bytes!("../../tests/fixtures/indexed4.bmp");
+        let mut bytes = original.to_vec();
+        bytes[FILE_HEADER_LEN..FILE_HEADER_LEN + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&bytes).err(), Some(ImageError::UnexpectedEof));
+        let mut bytes = original.to_vec();
+        bytes[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&bytes).err(), Some(ImageError::UnexpectedEof));
+    }
+}
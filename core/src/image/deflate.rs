@@ -0,0 +1,168 @@
+//! DEFLATE and zlib compression: greedy LZ77 matching over a hash chain,
+//! written as a single fixed-Huffman block. Nowhere near zlib's ratios, but
+//! small, and plenty for UI screenshots that are mostly flat color.
+
+use alloc::{vec, vec::Vec};
+use super::inflate::{adler32, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+/// How many earlier candidates to try per position before settling
+const MAX_CHAIN: usize = 64;
+
+struct BitWriter {
+    out: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    /// Writes `count` bits of `value`, least significant first
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= value << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed most significant bit first
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.bit_buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Writes a literal/length symbol with the fixed code from RFC 1951 3.2.6
+fn fixed_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// Index of the last entry in `bases` that's <= `value`
+fn bucket(bases: &[u16], value: usize) -> usize {
+    bases.iter().rposition(|&base| base as usize <= value).unwrap_or(0)
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let key = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Records position `i` as the newest candidate for its hash
+fn insert(data: &[u8], head: &mut [usize], previous: &mut [usize], i: usize) {
+    if i + MIN_MATCH <= data.len() {
+        let h = hash(data, i);
+        previous[i % WINDOW_SIZE] = head[h];
+        head[h] = i;
+    }
+}
+
+/// Compresses `data` into a raw DEFLATE stream
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { out: vec![], bit_buffer: 0, bit_count: 0 };
+    // final block, fixed huffman codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    // most recent position for each hash, and the one before it for each position
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW_SIZE];
+                // entries older than the window have been overwritten
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        let (length, distance) = best;
+        if length >= MIN_MATCH {
+            let l = bucket(&LENGTH_BASE, length);
+            fixed_literal(&mut writer, 257 + l as u32);
+            writer.bits((length - LENGTH_BASE[l] as usize) as u32, LENGTH_EXTRA[l] as u32);
+            let d = bucket(&DISTANCE_BASE, distance);
+            writer.code(d as u32, 5);
+            writer.bits((distance - DISTANCE_BASE[d] as usize) as u32, DISTANCE_EXTRA[d] as u32);
+            for j in i..i + length {
+                insert(data, &mut head, &mut previous, j);
+            }
+            i += length;
+        } else {
+            fixed_literal(&mut writer, data[i] as u32);
+            insert(data, &mut head, &mut previous, i);
+            i += 1;
+        }
+    }
+
+    fixed_literal(&mut writer, 256);
+    writer.finish()
+}
+
+/// Compresses `data` into a zlib stream
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::inflate::zlib_decompress;
+
+    #[test]
+    fn test_round_trip() {
+        let text = b"the quick brown fox jumps over the lazy dog";
+        let data: Vec<u8> = (0..5000usize).map(|i| text[(i * i * 7 + i * 13) % text.len()]).collect();
+        for input in [&b""[..], b"a", b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &data] {
+            assert_eq!(zlib_decompress(&zlib_compress(input), usize::MAX).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_long_window() {
+        // a run longer than the window, so the hash chain wraps around
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8 ^ (i / 40_000) as u8).collect();
+        let compressed = zlib_compress(&data);
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(zlib_decompress(&compressed, usize::MAX).unwrap(), data);
+    }
+}
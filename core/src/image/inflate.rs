@@ -0,0 +1,330 @@
+//! DEFLATE (RFC 1951) and zlib (RFC 1950) decompression. Favors being
+//! small and obviously correct over speed, like puff.c.
+
+use alloc::{vec, vec::Vec};
+use super::ImageError;
+
+const MAX_BITS: usize = 15;
+
+/// Base match lengths for length symbols 257..=285, and their extra bits
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+/// Base distances for distance symbols 0..=29, and their extra bits
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+/// Order code length code lengths are stored in for dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, bit_buffer: 0, bit_count: 0 }
+    }
+
+    /// Reads `count` bits, least significant first
+    fn bits(&mut self, count: u32) -> Result<u32, ImageError> {
+        while self.bit_count < count {
+            let byte = *self.data.get(self.pos).ok_or(ImageError::UnexpectedEof)?;
+            self.pos += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer = if count == 32 { 0 } else { self.bit_buffer >> count };
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Drops the rest of the current byte
+    fn align(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ImageError> {
+        let end = self.pos.checked_add(count).ok_or(ImageError::UnexpectedEof)?;
+        let bytes = self.data.get(self.pos..end).ok_or(ImageError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+/// Canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, ImageError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        // over-subscribed codes can't be decoded; incomplete ones are allowed
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(ImageError::Corrupt("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ImageError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ImageError::Corrupt("invalid huffman code"))
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), ImageError> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ImageError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(ImageError::Corrupt("too many huffman codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => {
+                lengths[i] = symbol as u8;
+                i += 1;
+                continue;
+            }
+            16 => {
+                let previous = *lengths[..i].last().ok_or(ImageError::Corrupt("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(ImageError::Corrupt("code lengths overflow"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(ImageError::Corrupt("missing end of block code"));
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literal_lengths)?, Huffman::new(distance_lengths)?))
+}
+
+const TOO_LONG: ImageError = ImageError::Corrupt("decompressed data too long");
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Result<(), ImageError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 if out.len() >= limit => return Err(TOO_LONG),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                let length = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = distances.decode(reader)? as usize;
+                if d >= DISTANCE_BASE.len() {
+                    return Err(ImageError::Corrupt("invalid distance symbol"));
+                }
+                let distance = DISTANCE_BASE[d] as usize + reader.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(ImageError::Corrupt("distance too far back"));
+                }
+                if length > limit - out.len() {
+                    return Err(TOO_LONG);
+                }
+                // byte at a time, since a match may overlap what it's copying
+                let start = out.len() - distance;
+                for j in 0..length {
+                    out.push(out[start + j]);
+                }
+            }
+            _ => return Err(ImageError::Corrupt("invalid literal/length symbol")),
+        }
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, failing if it would come to more
+/// than `limit` bytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, ImageError> {
+    let mut reader = BitReader::new(data);
+    let mut out = vec![];
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader.bytes(4)?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                let complement = u16::from_le_bytes([header[2], header[3]]);
+                if length != !complement {
+                    return Err(ImageError::Corrupt("stored block length mismatch"));
+                }
+                if length as usize > limit - out.len() {
+                    return Err(TOO_LONG);
+                }
+                out.extend_from_slice(reader.bytes(length as usize)?);
+            }
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            _ => return Err(ImageError::Corrupt("invalid block type")),
+        }
+        if is_final {
+            return Ok(out);
+        }
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before b can overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompresses a zlib stream, checking its header and checksum. Streams
+/// that would come to more than `limit` bytes are an error, so a small
+/// file can't make for a huge allocation.
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ImageError> {
+    if data.len() < 6 {
+        return Err(ImageError::UnexpectedEof);
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return Err(ImageError::Unsupported("zlib compression method"));
+    }
+    if !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) {
+        return Err(ImageError::Corrupt("zlib header check"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(ImageError::Unsupported("zlib preset dictionary"));
+    }
+
+    let out = inflate(&data[2..], limit)?;
+    let trailer = &data[data.len() - 4..];
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if adler32(&out) != expected {
+        return Err(ImageError::Corrupt("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_block() {
+        let data = [0b001, 5, 0, !5, !0, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(inflate(&data, 5).unwrap(), b"hello");
+        assert_eq!(inflate(&data, 4), Err(TOO_LONG));
+    }
+
+    #[test]
+    fn test_fixed_block() {
+        // zlib.compress(b"abcabcabcabc")
+        let data = [0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00, 0x1d, 0xe0, 0x04, 0x99];
+        assert_eq!(zlib_decompress(&data, 12).unwrap(), b"abcabcabcabc");
+        // the limit holds part way through a match too
+        assert_eq!(zlib_decompress(&data, 11), Err(TOO_LONG));
+    }
+
+    #[test]
+    fn test_dynamic_block() {
+        let text = b"the quick brown fox jumps over the lazy dog";
+        let expected: Vec<u8> = (0..4000usize).map(|i| text[(i * i * 7 + i * 13) % text.len()]).collect();
+        let compressed = include_bytes!("../../tests/fixtures/dynamic.zlib");
+        assert_eq!(zlib_decompress(compressed, usize::MAX).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let data = [0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00, 0x1d, 0xe0, 0x04, 0x98];
+        assert_eq!(zlib_decompress(&data, usize::MAX), Err(ImageError::Corrupt("zlib checksum mismatch")));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(inflate(&[0b011], usize::MAX), Err(ImageError::UnexpectedEof));
+    }
+}
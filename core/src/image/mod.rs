@@ -0,0 +1,157 @@
+//! Decoding PNG, QOI and BMP files into [PixelBuffer]s, encoding them back
+//! out as PNG or PPM, and the [Image] widget for drawing them. None of this
+//! touches the browser, so it all runs under a native `cargo test`.
+
+pub mod bmp;
+pub mod deflate;
+pub mod inflate;
+pub mod png;
+pub mod ppm;
+pub mod qoi;
+
+use core::fmt;
+
+use crate::{
+    pixels::{BlendMode, BlitOptions, PixelBuffer, Sampling},
+    ui::{Drawable, Point, Rect, Size},
+};
+
+/// Images larger than this on either side are rejected rather than risk
+/// allocating gigabytes for a corrupt header.
+pub const MAX_DIMENSION: usize = 16384;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    /// The data ended before the image did
+    UnexpectedEof,
+    /// The data doesn't start with any signature we know
+    UnknownFormat,
+    /// A valid file that uses a feature these decoders don't handle
+    Unsupported(&'static str),
+    /// The file breaks its format's rules
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::UnexpectedEof => write!(f, "unexpected end of image data"),
+            ImageError::UnknownFormat => write!(f, "unknown image format"),
+            ImageError::Unsupported(what) => write!(f, "unsupported image feature: {}", what),
+            ImageError::Corrupt(what) => write!(f, "corrupt image: {}", what),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImageError {}
+
+pub(crate) fn check_dimensions(width: usize, height: usize) -> Result<(), ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::Corrupt("zero width or height"));
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ImageError::Unsupported("dimensions too large"));
+    }
+    Ok(())
+}
+
+/// Decodes a PNG, QOI or BMP file, picking the format from its signature
+pub fn decode(bytes: &[u8]) -> Result<PixelBuffer, ImageError> {
+    if bytes.starts_with(&png::SIGNATURE) {
+        png::decode(bytes)
+    } else if bytes.starts_with(&qoi::MAGIC) {
+        qoi::decode(bytes)
+    } else if bytes.starts_with(&bmp::MAGIC) {
+        bmp::decode(bytes)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+/// A bitmap, or a sprite cut out of one, drawn at any size
+pub struct Image {
+    bitmap: PixelBuffer,
+    options: BlitOptions,
+}
+impl Image {
+    pub fn new(bitmap: PixelBuffer) -> Self {
+        Self {
+            bitmap,
+            options: BlitOptions::default(),
+        }
+    }
+
+    /// Only draw part of the bitmap, e.g. one frame of a sprite sheet
+    pub fn with_source_rect(mut self, source_rect: Rect) -> Self {
+        self.options.source_rect = Some(source_rect);
+        self
+    }
+
+    pub fn with_size(mut self, size: Size) -> Self {
+        self.options.size = Some(size);
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.options.sampling = sampling;
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: u8) -> Self {
+        self.options.alpha = alpha;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.options.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn set_source_rect(&mut self, source_rect: Rect) {
+        self.options.source_rect = Some(source_rect);
+    }
+}
+impl Drawable for Image {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        buffer.blit(&self.bitmap, pos, &self.options);
+    }
+
+    fn get_render_size(&self) -> Size {
+        self.options.output_size(&self.bitmap)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::pixels::Color;
+
+    /// What the RGB fixtures contain; see tests/fixtures/generate.py
+    pub fn pattern(x: usize, y: usize) -> Color {
+        Color::new((x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8, (255 - x * y * 2) as u8)
+    }
+
+    pub fn assert_pattern(image: &PixelBuffer, expected: impl Fn(usize, usize) -> Color) {
+        assert_eq!((image.width, image.height), (13, 9));
+        for y in 0..image.height {
+            for x in 0..image.width {
+                assert_eq!(image.get((x, y)), Some(expected(x, y)), "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sniffs_format() {
+        assert!(decode(include_bytes!("../../tests/fixtures/rgba8.png")).is_ok());
+        assert!(decode(include_bytes!("../../tests/fixtures/pattern.qoi")).is_ok());
+        assert!(decode(include_bytes!("../../tests/fixtures/rgb24.bmp")).is_ok());
+        assert_eq!(decode(b"GIF89a").err(), Some(ImageError::UnknownFormat));
+    }
+
+    #[test]
+    fn test_image_size() {
+        let bitmap = decode(include_bytes!("../../tests/fixtures/rgba8.png")).unwrap();
+        assert_eq!(Image::new(bitmap).with_source_rect(((2, 2), (5, 3))).get_render_size(), (4, 2));
+    }
+}
@@ -0,0 +1,468 @@
+//! PNG decoding and encoding. Decoding handles every color type and bit
+//! depth in the spec, plus palettes, `tRNS` transparency and Adam7
+//! interlacing. Ancillary chunks other than `tRNS` are skipped, so there's
+//! no gamma or color management. Encoding always writes 8-bit RGBA.
+
+use alloc::{vec, vec::Vec};
+use super::{check_dimensions, deflate::zlib_compress, inflate::zlib_decompress, ImageError};
+use crate::pixels::{PixelBuffer, NUM_CHANNELS};
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// (x offset, y offset, x step, y step) of each Adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+const CRC_POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// CRC-32 as used by PNG chunks, computed bitwise to avoid a lookup table
+pub fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for chunk in chunks {
+        for &byte in *chunk {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ CRC_POLYNOMIAL } else { crc >> 1 };
+            }
+        }
+    }
+    !crc
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn channels(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: ColorType,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        if data.len() != 13 {
+            return Err(ImageError::Corrupt("IHDR length"));
+        }
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let bit_depth = data[8];
+        let color_type = match data[9] {
+            0 => ColorType::Grayscale,
+            2 => ColorType::Rgb,
+            3 => ColorType::Indexed,
+            4 => ColorType::GrayscaleAlpha,
+            6 => ColorType::Rgba,
+            _ => return Err(ImageError::Corrupt("invalid color type")),
+        };
+        let depth_ok = match color_type {
+            ColorType::Grayscale => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            ColorType::Indexed => matches!(bit_depth, 1 | 2 | 4 | 8),
+            _ => matches!(bit_depth, 8 | 16),
+        };
+        if !depth_ok {
+            return Err(ImageError::Corrupt("invalid bit depth for color type"));
+        }
+        if data[10] != 0 || data[11] != 0 {
+            return Err(ImageError::Unsupported("compression or filter method"));
+        }
+        let interlaced = match data[12] {
+            0 => false,
+            1 => true,
+            _ => return Err(ImageError::Corrupt("invalid interlace method")),
+        };
+        check_dimensions(width, height)?;
+
+        Ok(Self { width, height, bit_depth, color_type, interlaced })
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Bytes in one scanline of a `width` pixel wide image, minus the filter byte
+    fn stride(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses each scanline's filter. `data` is `rows` scanlines of
+/// `1 + stride` bytes; the result drops the filter type bytes, so it's
+/// `rows * stride` long.
+fn unfilter(data: &[u8], rows: usize, stride: usize, bytes_per_pixel: usize) -> Result<Vec<u8>, ImageError> {
+    let mut out = vec![0u8; rows * stride];
+    for row in 0..rows {
+        let line = &data[row * (stride + 1)..(row + 1) * (stride + 1)];
+        let (filter, line) = (line[0], &line[1..]);
+        let (previous, current) = out.split_at_mut(row * stride);
+        let previous = if row == 0 { None } else { Some(&previous[(row - 1) * stride..]) };
+        let current = &mut current[..stride];
+
+        for i in 0..stride {
+            let left = if i >= bytes_per_pixel { current[i - bytes_per_pixel] } else { 0 };
+            let up = previous.map_or(0, |p| p[i]);
+            let up_left = match previous {
+                Some(p) if i >= bytes_per_pixel => p[i - bytes_per_pixel],
+                _ => 0,
+            };
+            current[i] = match filter {
+                0 => line[i],
+                1 => line[i].wrapping_add(left),
+                2 => line[i].wrapping_add(up),
+                3 => line[i].wrapping_add(((left as u16 + up as u16) / 2) as u8),
+                4 => line[i].wrapping_add(paeth(left, up, up_left)),
+                _ => return Err(ImageError::Corrupt("invalid filter type")),
+            };
+        }
+    }
+    Ok(out)
+}
+
+/// Turns unfiltered scanlines into RGBA pixels
+struct Expander<'a> {
+    header: &'a Header,
+    palette: &'a [[u8; 4]],
+    /// Raw sample values that should be fully transparent, for gray and RGB
+    color_key: Option<[u16; 3]>,
+}
+
+impl Expander<'_> {
+    fn sample(&self, line: &[u8], index: usize) -> u16 {
+        match self.header.bit_depth {
+            16 => u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]),
+            8 => line[index] as u16,
+            depth => {
+                let depth = depth as usize;
+                let bit = index * depth;
+                let shift = 8 - depth - bit % 8;
+                ((line[bit / 8] >> shift) & ((1 << depth) - 1) as u8) as u16
+            }
+        }
+    }
+
+    fn to_8_bit(&self, value: u16) -> u8 {
+        match self.header.bit_depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            depth => (value * 255 / ((1 << depth) - 1)) as u8,
+        }
+    }
+
+    fn pixel(&self, line: &[u8], x: usize) -> Result<[u8; 4], ImageError> {
+        let channels = self.header.color_type.channels();
+        let sample = |c: usize| self.sample(line, x * channels + c);
+        let pixel = match self.header.color_type {
+            ColorType::Indexed => *self
+                .palette
+                .get(sample(0) as usize)
+                .ok_or(ImageError::Corrupt("palette index out of range"))?,
+            ColorType::Grayscale => {
+                let gray = sample(0);
+                let value = self.to_8_bit(gray);
+                let alpha = if self.color_key.is_some_and(|key| key[0] == gray) { 0 } else { 255 };
+                [value, value, value, alpha]
+            }
+            ColorType::GrayscaleAlpha => {
+                let value = self.to_8_bit(sample(0));
+                [value, value, value, self.to_8_bit(sample(1))]
+            }
+            ColorType::Rgb => {
+                let rgb = [sample(0), sample(1), sample(2)];
+                let alpha = if self.color_key == Some(rgb) { 0 } else { 255 };
+                [self.to_8_bit(rgb[0]), self.to_8_bit(rgb[1]), self.to_8_bit(rgb[2]), alpha]
+            }
+            ColorType::Rgba => [
+                self.to_8_bit(sample(0)),
+                self.to_8_bit(sample(1)),
+                self.to_8_bit(sample(2)),
+                self.to_8_bit(sample(3)),
+            ],
+        };
+        Ok(pixel)
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<PixelBuffer, ImageError> {
+    if !bytes.starts_with(&SIGNATURE) {
+        return Err(ImageError::UnknownFormat);
+    }
+
+    let mut header: Option<Header> = None;
+    let mut palette: Vec<[u8; 4]> = vec![];
+    let mut transparency: Option<&[u8]> = None;
+    let mut compressed: Vec<u8> = vec![];
+    let mut pos = SIGNATURE.len();
+    loop {
+        let length_bytes = bytes.get(pos..pos + 4).ok_or(ImageError::UnexpectedEof)?;
+        let length = u32::from_be_bytes([length_bytes[0], length_bytes[1], length_bytes[2], length_bytes[3]]) as usize;
+        let kind = bytes.get(pos + 4..pos + 8).ok_or(ImageError::UnexpectedEof)?;
+        // `length` comes from the file, and can overflow a 32-bit usize
+        let data_end = (pos + 8).checked_add(length).ok_or(ImageError::UnexpectedEof)?;
+        let chunk_end = data_end.checked_add(4).ok_or(ImageError::UnexpectedEof)?;
+        let data = bytes.get(pos + 8..data_end).ok_or(ImageError::UnexpectedEof)?;
+        let crc = bytes.get(data_end..chunk_end).ok_or(ImageError::UnexpectedEof)?;
+        if crc32(&[kind, data]) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(ImageError::Corrupt("chunk checksum mismatch"));
+        }
+        pos = chunk_end;
+
+        match kind {
+            b"IHDR" => header = Some(Header::parse(data)?),
+            _ if header.is_none() => return Err(ImageError::Corrupt("first chunk must be IHDR")),
+            b"PLTE" => {
+                if data.len() % 3 != 0 || data.len() > 256 * 3 {
+                    return Err(ImageError::Corrupt("PLTE length"));
+                }
+                palette = data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect();
+            }
+            b"tRNS" => transparency = Some(data),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // lowercase first letter means the chunk is safe to ignore
+            _ if kind[0] & 0x20 != 0 => {}
+            _ => return Err(ImageError::Unsupported("unknown critical chunk")),
+        }
+    }
+    let header = header.ok_or(ImageError::Corrupt("missing IHDR"))?;
+
+    let mut color_key = None;
+    if let Some(trns) = transparency {
+        let read_u16 = |i: usize| -> Result<u16, ImageError> {
+            let bytes = trns.get(i * 2..i * 2 + 2).ok_or(ImageError::Corrupt("tRNS length"))?;
+            Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+        match header.color_type {
+            ColorType::Indexed => {
+                for (entry, &alpha) in palette.iter_mut().zip(trns) {
+                    entry[3] = alpha;
+                }
+            }
+            ColorType::Grayscale => color_key = Some([read_u16(0)?, 0, 0]),
+            ColorType::Rgb => color_key = Some([read_u16(0)?, read_u16(1)?, read_u16(2)?]),
+            _ => return Err(ImageError::Corrupt("tRNS not allowed with alpha channel")),
+        }
+    }
+    if header.color_type == ColorType::Indexed && palette.is_empty() {
+        return Err(ImageError::Corrupt("missing PLTE"));
+    }
+
+    // each pass's origin, step, size and stride, with the empty ones left out
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced { &ADAM7 } else { &[(0, 0, 1, 1)] };
+    let passes: Vec<_> = passes
+        .iter()
+        .filter(|&&(x0, y0, _, _)| x0 < header.width && y0 < header.height)
+        .map(|&(x0, y0, step_x, step_y)| {
+            let pass_width = (header.width - x0).div_ceil(step_x);
+            let pass_height = (header.height - y0).div_ceil(step_y);
+            (x0, y0, step_x, step_y, pass_width, pass_height, header.stride(pass_width))
+        })
+        .collect();
+    // known before anything big is allocated, so a tiny file claiming to be
+    // a huge image, or a zlib bomb, fails without using the memory
+    let expected: usize = passes.iter().map(|&(.., pass_height, stride)| (stride + 1) * pass_height).sum();
+    let filtered = zlib_decompress(&compressed, expected)?;
+    if filtered.len() != expected {
+        return Err(ImageError::UnexpectedEof);
+    }
+
+    let expander = Expander { header: &header, palette: &palette, color_key };
+    let bytes_per_pixel = header.bits_per_pixel().div_ceil(8);
+    let mut rgba = vec![0u8; header.width * header.height * NUM_CHANNELS];
+    let mut offset = 0;
+    for (x0, y0, step_x, step_y, pass_width, pass_height, stride) in passes {
+        let length = (stride + 1) * pass_height;
+        let pass_data = &filtered[offset..offset + length];
+        offset += length;
+
+        let lines = unfilter(pass_data, pass_height, stride, bytes_per_pixel)?;
+        for (row, line) in lines.chunks_exact(stride).enumerate() {
+            let y = y0 + row * step_y;
+            for column in 0..pass_width {
+                let x = x0 + column * step_x;
+                let target = (y * header.width + x) * NUM_CHANNELS;
+                rgba[target..target + NUM_CHANNELS].copy_from_slice(&expander.pixel(line, column)?);
+            }
+        }
+    }
+
+    Ok(PixelBuffer::from_rgba(header.width, header.height, rgba))
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    out.extend(crc32(&[kind, data]).to_be_bytes());
+}
+
+/// Filters one scanline, trying every filter type and keeping the one with
+/// the smallest sum of absolute differences (the usual libpng heuristic).
+fn filter_line(line: &[u8], previous: Option<&[u8]>, out: &mut Vec<u8>) {
+    let bytes_per_pixel = NUM_CHANNELS;
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter in 0..=4u8 {
+        let filtered: Vec<u8> = (0..line.len())
+            .map(|i| {
+                let left = if i >= bytes_per_pixel { line[i - bytes_per_pixel] } else { 0 };
+                let up = previous.map_or(0, |p| p[i]);
+                let up_left = match previous {
+                    Some(p) if i >= bytes_per_pixel => p[i - bytes_per_pixel],
+                    _ => 0,
+                };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                line[i].wrapping_sub(predictor)
+            })
+            .collect();
+        let cost = filtered.iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+        if best.as_ref().is_none_or(|(best_cost, _, _)| cost < *best_cost) {
+            best = Some((cost, filter, filtered));
+        }
+    }
+    if let Some((_, filter, filtered)) = best {
+        out.push(filter);
+        out.extend(filtered);
+    }
+}
+
+/// Encodes `buffer` as a non-interlaced 8-bit RGBA PNG
+pub fn encode(buffer: &PixelBuffer) -> Vec<u8> {
+    let stride = buffer.width * NUM_CHANNELS;
+    let mut filtered = Vec::with_capacity((stride + 1) * buffer.height);
+    let mut previous = None;
+    for line in buffer.data_as_ref().chunks_exact(stride.max(1)).take(buffer.height) {
+        filter_line(line, previous, &mut filtered);
+        previous = Some(line);
+    }
+
+    let mut header = vec![];
+    header.extend((buffer.width as u32).to_be_bytes());
+    header.extend((buffer.height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, not interlaced
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&filtered));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::tests::{assert_pattern, pattern},
+        pixels::Color,
+    };
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(&[b"IEND"]), 0xae42_6082);
+        assert_eq!(crc32(&[b"IE", b"ND"]), 0xae42_6082);
+    }
+
+    #[test]
+    fn test_rgba() {
+        let image = decode(include_bytes!("../../tests/fixtures/rgba8.png")).unwrap();
+        assert_pattern(&image, pattern);
+    }
+
+    #[test]
+    fn test_rgb16_interlaced() {
+        let image = decode(include_bytes!("../../tests/fixtures/rgb16_interlaced.png")).unwrap();
+        assert_pattern(&image, |x, y| pattern(x, y).with_alpha(255));
+    }
+
+    #[test]
+    fn test_indexed_with_transparency() {
+        let image = decode(include_bytes!("../../tests/fixtures/indexed4.png")).unwrap();
+        assert_pattern(&image, |x, y| match (x + y) % 3 {
+            0 => Color::new(255, 0, 0, 255),
+            1 => Color::new(0, 255, 0, 128),
+            _ => Color::new(0, 0, 255, 0),
+        });
+    }
+
+    #[test]
+    fn test_gray1() {
+        let image = decode(include_bytes!("../../tests/fixtures/gray1.png")).unwrap();
+        assert_pattern(&image, |x, y| if (x ^ y) & 1 == 0 { Color::new(255, 255, 255, 255) } else { Color::new(0, 0, 0, 255) });
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let image = decode(include_bytes!("../../tests/fixtures/rgba8.png")).unwrap();
+        let encoded = encode(&image);
+        assert_pattern(&decode(&encoded).unwrap(), pattern);
+    }
+
+    #[test]
+    fn test_bad_crc() {
+        let mut bytes = include_bytes!("../../tests/fixtures/rgba8.png").to_vec();
+        bytes[20] ^= 1;
+        assert_eq!(decode(&bytes).err(), Some(ImageError::Corrupt("chunk checksum mismatch")));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = include_bytes!("../../tests/fixtures/rgba8.png");
+        assert_eq!(decode(&bytes[..40]).err(), Some(ImageError::UnexpectedEof));
+        // a chunk claiming to be bigger than any file could be
+        let mut huge = bytes.to_vec();
+        huge[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(decode(&huge).err(), Some(ImageError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_claims_to_be_huge() {
+        // a valid IHDR for a 16384x16384 image, then the fixture's small IDAT
+        let mut bytes = include_bytes!("../../tests/fixtures/rgba8.png").to_vec();
+        bytes[16..24].copy_from_slice(&[0, 0, 0x40, 0, 0, 0, 0x40, 0]);
+        let crc = crc32(&[&bytes[12..29]]);
+        bytes[29..33].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(decode(&bytes).err(), Some(ImageError::UnexpectedEof));
+    }
+}
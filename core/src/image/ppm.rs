@@ -0,0 +1,27 @@
+//! Binary PPM (P6) encoding. Drops alpha, but nearly every image tool can
+//! open it and it's trivial to eyeball in a hex dump.
+
+use alloc::{format, vec::Vec};
+use crate::pixels::{PixelBuffer, NUM_CHANNELS};
+
+pub fn encode(buffer: &PixelBuffer) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", buffer.width, buffer.height).into_bytes();
+    out.reserve(buffer.width * buffer.height * 3);
+    for pixel in buffer.data_as_ref().chunks_exact(NUM_CHANNELS) {
+        out.extend(&pixel[..3]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::Color;
+
+    #[test]
+    fn test_encode() {
+        let mut buffer = PixelBuffer::new(2, 1);
+        buffer.set((1, 0), Color::new(1, 2, 3, 4));
+        assert_eq!(encode(&buffer), b"P6\n2 1\n255\n\0\0\0\x01\x02\x03");
+    }
+}
@@ -0,0 +1,123 @@
+//! QOI ("Quite OK Image") decoding, per the 1.0 spec at qoiformat.org
+
+use alloc::vec;
+use super::{check_dimensions, ImageError};
+use crate::pixels::{PixelBuffer, NUM_CHANNELS};
+
+pub const MAGIC: [u8; 4] = *b"qoif";
+pub const HEADER_LEN: usize = 14;
+pub const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+pub const OP_INDEX: u8 = 0b0000_0000;
+pub const OP_DIFF: u8 = 0b0100_0000;
+pub const OP_LUMA: u8 = 0b1000_0000;
+pub const OP_RUN: u8 = 0b1100_0000;
+pub const OP_RGB: u8 = 0b1111_1110;
+pub const OP_RGBA: u8 = 0b1111_1111;
+const OP_MASK: u8 = 0b1100_0000;
+
+pub fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+pub fn decode(bytes: &[u8]) -> Result<PixelBuffer, ImageError> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err(ImageError::UnknownFormat);
+    }
+    if bytes.len() < HEADER_LEN + END_MARKER.len() {
+        return Err(ImageError::UnexpectedEof);
+    }
+    let width = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let height = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+    if !matches!(bytes[12], 3 | 4) || bytes[13] > 1 {
+        return Err(ImageError::Corrupt("invalid channels or colorspace"));
+    }
+    check_dimensions(width, height)?;
+
+    let mut rgba = vec![0u8; width * height * NUM_CHANNELS];
+    let mut index = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut pos = HEADER_LEN;
+    let mut run = 0;
+    let next = |pos: &mut usize| -> Result<u8, ImageError> {
+        let byte = *bytes.get(*pos).ok_or(ImageError::UnexpectedEof)?;
+        *pos += 1;
+        Ok(byte)
+    };
+
+    for out in rgba.chunks_exact_mut(NUM_CHANNELS) {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let op = next(&mut pos)?;
+            match op {
+                OP_RGB => {
+                    pixel[0] = next(&mut pos)?;
+                    pixel[1] = next(&mut pos)?;
+                    pixel[2] = next(&mut pos)?;
+                }
+                OP_RGBA => {
+                    for channel in pixel.iter_mut() {
+                        *channel = next(&mut pos)?;
+                    }
+                }
+                _ => match op & OP_MASK {
+                    OP_INDEX => pixel = index[op as usize],
+                    OP_DIFF => {
+                        pixel[0] = pixel[0].wrapping_add((op >> 4) & 0b11).wrapping_sub(2);
+                        pixel[1] = pixel[1].wrapping_add((op >> 2) & 0b11).wrapping_sub(2);
+                        pixel[2] = pixel[2].wrapping_add(op & 0b11).wrapping_sub(2);
+                    }
+                    OP_LUMA => {
+                        let second = next(&mut pos)?;
+                        let green = (op & 0b0011_1111).wrapping_sub(32);
+                        pixel[0] = pixel[0].wrapping_add(green).wrapping_add(second >> 4).wrapping_sub(8);
+                        pixel[1] = pixel[1].wrapping_add(green);
+                        pixel[2] = pixel[2].wrapping_add(green).wrapping_add(second & 0b1111).wrapping_sub(8);
+                    }
+                    _ => run = (op & 0b0011_1111) as usize,
+                },
+            }
+            index[hash(pixel)] = pixel;
+        }
+        out.copy_from_slice(&pixel);
+    }
+
+    Ok(PixelBuffer::from_rgba(width, height, rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::tests::{assert_pattern, pattern},
+        pixels::Color,
+    };
+
+    #[test]
+    fn test_decode() {
+        let image = decode(include_bytes!("../../tests/fixtures/pattern.qoi")).unwrap();
+        assert_pattern(&image, pattern);
+    }
+
+    #[test]
+    fn test_run_and_index() {
+        #[rustfmt::skip]
+        let bytes = [
+            b'q', b'o', b'i', b'f', 0, 0, 0, 4, 0, 0, 0, 1, 4, 0,
+            OP_RGBA, 10, 20, 30, 40,
+            OP_RUN | 1,
+            OP_INDEX | hash([0, 0, 0, 0]) as u8,
+            0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+        let image = decode(&bytes).unwrap();
+        assert_eq!(image.get((2, 0)), Some(Color::new(10, 20, 30, 40)));
+        assert_eq!(image.get((3, 0)), Some(Color::new(0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = include_bytes!("../../tests/fixtures/pattern.qoi");
+        assert_eq!(decode(&bytes[..30]).err(), Some(ImageError::UnexpectedEof));
+    }
+}
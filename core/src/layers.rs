@@ -0,0 +1,263 @@
+//! Named layers, each drawn into its own [PixelBuffer] and composited in
+//! z order, so what ends up on top is a choice rather than an accident of
+//! draw order.
+
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::{
+    dirty::DirtyRegions,
+    pixels::{blend, BlendMode, PixelBuffer, TRANSPARENT},
+    ui::Rect,
+};
+
+pub struct Layer {
+    name: String,
+    buffer: PixelBuffer,
+    opacity: u8,
+    visible: bool,
+    z_index: i32,
+    blend_mode: BlendMode,
+}
+
+impl Layer {
+    fn new(name: String, (width, height): (usize, usize), z_index: i32) -> Self {
+        Self {
+            name,
+            buffer: PixelBuffer::new(width, height),
+            opacity: 255,
+            visible: true,
+            z_index,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    /// Anything drawn here shows up at the next composite
+    fn invalidate(&mut self) {
+        let (width, height) = (self.buffer.width, self.buffer.height);
+        self.buffer.invalidate(((0, 0), (width.saturating_sub(1), height.saturating_sub(1))));
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn buffer(&self) -> &PixelBuffer {
+        &self.buffer
+    }
+
+    /// Starts out transparent. Only what changes gets recomposited.
+    pub fn buffer_mut(&mut self) -> &mut PixelBuffer {
+        &mut self.buffer
+    }
+
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Multiplied into every pixel's alpha when compositing
+    pub fn set_opacity(&mut self, opacity: u8) {
+        if self.opacity != opacity {
+            self.opacity = opacity;
+            self.invalidate();
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.invalidate();
+        }
+    }
+
+    pub fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    /// Higher is on top. Layers with the same z index stack in the order
+    /// they were added.
+    pub fn set_z_index(&mut self, z_index: i32) {
+        if self.z_index != z_index {
+            self.z_index = z_index;
+            self.invalidate();
+        }
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// How this layer combines with the ones below it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        if self.blend_mode != blend_mode {
+            self.blend_mode = blend_mode;
+            self.invalidate();
+        }
+    }
+}
+
+/// Same-sized layers, composited together into one frame
+pub struct LayerStack {
+    layers: Vec<Layer>,
+    width: usize,
+    height: usize,
+    /// Areas to recomposite that no layer's buffer knows about, e.g. where
+    /// a layer was removed
+    damage: DirtyRegions,
+}
+
+impl LayerStack {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            layers: vec![],
+            width,
+            height,
+            damage: DirtyRegions::new(width, height),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Adds an empty layer on top of any others with the same `z_index`.
+    /// Names must be unique.
+    pub fn add(&mut self, name: &str, z_index: i32) -> &mut Layer {
+        assert!(self.get(name).is_none(), "there's already a layer called {:?}", name);
+        self.layers.push(Layer::new(name.into(), (self.width, self.height), z_index));
+        self.layers.last_mut().unwrap()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Layer> {
+        let index = self.layers.iter().position(|layer| layer.name == name)?;
+        self.damage.mark_all();
+        Some(self.layers.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    /// Bottom to top
+    pub fn iter(&self) -> impl Iterator<Item = &Layer> {
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z_index);
+        layers.into_iter()
+    }
+
+    /// Recomposites everything next time, e.g. if the target was drawn over
+    pub fn invalidate(&mut self) {
+        self.damage.mark_all();
+    }
+
+    /// Replaces every layer's contents with a blank buffer of the new size
+    pub fn resize(&mut self, width: usize, height: usize) {
+        (self.width, self.height) = (width, height);
+        for layer in &mut self.layers {
+            layer.buffer = PixelBuffer::new(width, height);
+        }
+        self.damage = DirtyRegions::new(width, height);
+    }
+
+    /// Flattens the visible layers into `target`, which must be the
+    /// stack's size. Only areas where some layer changed since the last
+    /// call are redone, and returned.
+    pub fn composite(&mut self, target: &mut PixelBuffer) -> Vec<Rect> {
+        assert_eq!((target.width, target.height), (self.width, self.height), "target isn't the layers' size");
+        for layer in &mut self.layers {
+            // changes to hidden layers still count, in case they were just hidden
+            for rect in layer.buffer.take_dirty_rects() {
+                self.damage.mark_rect(rect);
+            }
+        }
+        let rects = self.damage.take_rects();
+        if rects.is_empty() {
+            return rects;
+        }
+
+        let mut visible: Vec<&Layer> = self.layers.iter().filter(|layer| layer.visible && layer.opacity > 0).collect();
+        visible.sort_by_key(|layer| layer.z_index);
+        for &((left, top), (right, bottom)) in &rects {
+            for y in top..=bottom {
+                for x in left..=right {
+                    let mut color = TRANSPARENT;
+                    for layer in &visible {
+                        let Some(pixel) = layer.buffer.get((x, y)) else {
+                            continue;
+                        };
+                        let alpha = ((pixel.a as u32 * layer.opacity as u32 + 127) / 255) as u8;
+                        color = blend(pixel.with_alpha(alpha), color, layer.blend_mode);
+                    }
+                    target.set((x, y), color);
+                }
+            }
+        }
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{Color, BLUE, RED, WHITE};
+
+    fn stack() -> LayerStack {
+        let mut layers = LayerStack::new(4, 4);
+        layers.add("top", 10).buffer_mut().set((1, 1), RED);
+        layers.add("bottom", 0).buffer_mut().set((1, 1), BLUE);
+        layers
+    }
+
+    #[test]
+    fn test_z_order() {
+        let mut layers = stack();
+        let mut target = PixelBuffer::new(4, 4);
+        layers.composite(&mut target);
+        assert_eq!(target.get((1, 1)), Some(RED));
+        assert_eq!(target.get((0, 0)), Some(TRANSPARENT));
+
+        layers.get_mut("top").unwrap().set_z_index(-1);
+        layers.composite(&mut target);
+        assert_eq!(target.get((1, 1)), Some(BLUE));
+        assert_eq!(layers.iter().map(Layer::name).collect::<Vec<_>>(), vec!["top", "bottom"]);
+    }
+
+    #[test]
+    fn test_opacity_and_visibility() {
+        let mut layers = stack();
+        let mut target = PixelBuffer::new(4, 4);
+        layers.get_mut("top").unwrap().set_opacity(128);
+        layers.composite(&mut target);
+        assert_eq!(target.get((1, 1)), Some(Color::new(128, 0, 127, 255)));
+
+        layers.get_mut("top").unwrap().set_visible(false);
+        layers.composite(&mut target);
+        assert_eq!(target.get((1, 1)), Some(BLUE));
+
+        layers.remove("bottom");
+        layers.composite(&mut target);
+        assert_eq!(target.get((1, 1)), Some(TRANSPARENT));
+    }
+
+    #[test]
+    fn test_only_changes_are_recomposited() {
+        let mut layers = LayerStack::new(100, 100);
+        layers.add("content", 0);
+        let mut target = PixelBuffer::new(100, 100);
+        layers.composite(&mut target);
+        target.take_dirty_rects();
+        assert_eq!(layers.composite(&mut target), vec![]);
+
+        layers.get_mut("content").unwrap().buffer_mut().set((70, 5), WHITE);
+        assert_eq!(layers.composite(&mut target), vec![((64, 0), (95, 31))]);
+        assert_eq!(target.take_dirty_rects(), vec![((64, 0), (95, 31))]);
+        assert_eq!(target.get((70, 5)), Some(WHITE));
+    }
+}
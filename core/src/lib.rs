@@ -0,0 +1,33 @@
+//! Everything that draws the site, with no ties to the browser: pixel
+//! buffers, shapes, text, images, post-processing, UI and the app itself.
+//! Only needs `alloc`, so it runs anywhere with a heap; the `std` feature
+//! adds conveniences like error traits and writing frames to disk.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod app;
+pub mod bitset;
+pub mod color;
+pub mod dirty;
+pub mod pixels;
+pub mod present;
+pub mod shapes;
+pub mod font;
+pub mod gradient;
+pub mod image;
+pub mod layers;
+pub mod palette;
+pub mod paragraph;
+pub mod postfx;
+pub mod rich;
+pub mod text;
+pub mod tiles;
+pub mod transform;
+pub mod ui;
+pub mod viewport;
+mod math;
+mod simd;
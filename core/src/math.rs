@@ -0,0 +1,25 @@
+//! Float functions `core` doesn't have, via libm
+
+pub(crate) fn sin(x: f32) -> f32 {
+    libm::sinf(x)
+}
+
+pub(crate) fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+pub(crate) fn ceil(x: f32) -> f32 {
+    libm::ceilf(x)
+}
+
+pub(crate) fn floor(x: f32) -> f32 {
+    libm::floorf(x)
+}
+
+pub(crate) fn sqrt(x: f32) -> f32 {
+    libm::sqrtf(x)
+}
+
+pub(crate) fn atan2(y: f32, x: f32) -> f32 {
+    libm::atan2f(y, x)
+}
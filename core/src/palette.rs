@@ -0,0 +1,394 @@
+//! Indexed color: a [Palette] of up to 256 colors, an [IndexedBuffer] of
+//! indices into it, and dithering for squeezing full color images and
+//! gradients into it.
+//!
+//! Colors are only looked up when the buffer is resolved to RGBA for
+//! upload, so swapping the palette recolors everything without redrawing.
+
+use alloc::{vec, vec::Vec};
+use crate::{
+    color::{Color, ParseColorError},
+    dirty::DirtyRegions,
+    pixels::{PixelBuffer, TRANSPARENT},
+    ui::{intersect_rects, Point, Rect},
+};
+
+/// The PICO-8 fantasy console's 16 colors
+pub const PICO_8: [Color; 16] = [
+    Color::rgb(0x00, 0x00, 0x00),
+    Color::rgb(0x1d, 0x2b, 0x53),
+    Color::rgb(0x7e, 0x25, 0x53),
+    Color::rgb(0x00, 0x87, 0x51),
+    Color::rgb(0xab, 0x52, 0x36),
+    Color::rgb(0x5f, 0x57, 0x4f),
+    Color::rgb(0xc2, 0xc3, 0xc7),
+    Color::rgb(0xff, 0xf1, 0xe8),
+    Color::rgb(0xff, 0x00, 0x4d),
+    Color::rgb(0xff, 0xa3, 0x00),
+    Color::rgb(0xff, 0xec, 0x27),
+    Color::rgb(0x00, 0xe4, 0x36),
+    Color::rgb(0x29, 0xad, 0xff),
+    Color::rgb(0x83, 0x76, 0x9c),
+    Color::rgb(0xff, 0x77, 0xa8),
+    Color::rgb(0xff, 0xcc, 0xaa),
+];
+
+/// 4x4 ordered dither thresholds, 0-15
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// How colors that aren't in the palette are approximated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Nearest palette color; flat, and bands on gradients
+    #[default]
+    None,
+    /// Bayer matrix pattern. Each pixel only depends on its own position,
+    /// so redrawing part of the screen can't disturb the rest.
+    Ordered,
+    /// Error diffusion; smoother than ordered, but every pixel depends on
+    /// the ones drawn before it in the same call
+    FloydSteinberg,
+}
+
+/// Weighted squared distance; green matters most to the eye, then red
+fn distance(a: Color, b: Color) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    2 * d(a.r, b.r) + 4 * d(a.g, b.g) + 3 * d(a.b, b.b) + 3 * d(a.a, b.a)
+}
+
+/// Adds `offset` to each of r, g and b, saturating
+fn offset(color: Color, [r, g, b]: [i32; 3]) -> Color {
+    let channel = |c: u8, o: i32| (c as i32 + o).clamp(0, 255) as u8;
+    Color::new(channel(color.r, r), channel(color.g, g), channel(color.b, b), color.a)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Color>,
+    /// How far apart neighbouring colors are per channel, on average. Ordered
+    /// dithering nudges colors by up to half of this.
+    spread: i32,
+}
+
+impl Palette {
+    /// Panics unless there are between 1 and 256 colors
+    pub fn new(colors: Vec<Color>) -> Self {
+        assert!(!colors.is_empty() && colors.len() <= 256, "palettes hold 1 to 256 colors");
+        // mean distance from each color to its closest neighbour, by its
+        // most different channel
+        let nearest_gaps = colors.iter().map(|&a| {
+            colors
+                .iter()
+                .filter(|&&b| b != a)
+                .map(|&b| (a.r.abs_diff(b.r)).max(a.g.abs_diff(b.g)).max(a.b.abs_diff(b.b)) as i32)
+                .min()
+                .unwrap_or(255)
+        });
+        let spread = nearest_gaps.sum::<i32>() / colors.len() as i32;
+        Self { colors, spread }
+    }
+
+    /// Parses hex colors separated by whitespace or commas, e.g.
+    /// `"#000 #fff"`. A list that's empty or too long is reported as
+    /// [ParseColorError::InvalidLength] with its number of colors.
+    pub fn from_hex(list: &str) -> Result<Self, ParseColorError> {
+        let colors = list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|hex| !hex.is_empty())
+            .map(Color::from_hex)
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.is_empty() || colors.len() > 256 {
+            return Err(ParseColorError::InvalidLength(colors.len()));
+        }
+        Ok(Self::new(colors))
+    }
+
+    /// `levels` evenly spaced grays from black to white
+    pub fn grayscale(levels: usize) -> Self {
+        let levels = levels.clamp(2, 256);
+        Self::new(
+            (0..levels)
+                .map(|i| {
+                    let gray = (i * 255 / (levels - 1)) as u8;
+                    Color::rgb(gray, gray, gray)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Always false, since palettes can't be empty
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Indices past the end are transparent
+    pub fn get(&self, index: u8) -> Color {
+        self.colors.get(index as usize).copied().unwrap_or(TRANSPARENT)
+    }
+
+    /// Index of the closest color, and how far off it is
+    fn nearest_with_distance(&self, color: Color) -> (u8, u32) {
+        let mut best = (0, u32::MAX);
+        for (i, &candidate) in self.colors.iter().enumerate() {
+            let d = distance(color, candidate);
+            if d < best.1 {
+                best = (i as u8, d);
+                if d == 0 {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Index of the closest color
+    pub fn nearest(&self, color: Color) -> u8 {
+        self.nearest_with_distance(color).0
+    }
+
+    /// Picks a color for `color` at `point` with an ordered dither. Colors
+    /// that are exactly in the palette are never dithered.
+    pub fn nearest_ordered(&self, color: Color, (x, y): Point) -> u8 {
+        let (index, distance) = self.nearest_with_distance(color);
+        if distance == 0 {
+            return index;
+        }
+        // centred on zero, from -spread/2 to +spread/2
+        let threshold = BAYER[y % 4][x % 4] as i32 * 2 + 1;
+        let bias = threshold * self.spread / 32 - self.spread / 2;
+        self.nearest(offset(color, [bias; 3]))
+    }
+}
+
+/// A bitmap of palette indices. Draw into it, then [IndexedBuffer::resolve]
+/// it to RGBA for display.
+pub struct IndexedBuffer {
+    indices: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    palette: Palette,
+    /// Changed since the last resolve
+    dirty: DirtyRegions,
+}
+
+impl IndexedBuffer {
+    /// Starts filled with index 0
+    pub fn new(width: usize, height: usize, palette: Palette) -> Self {
+        Self {
+            indices: vec![0; width * height],
+            width,
+            height,
+            palette,
+            dirty: DirtyRegions::new(width, height),
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Recolors the whole buffer on the next resolve
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.dirty.mark_all();
+    }
+
+    pub fn get(&self, (x, y): Point) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.indices[y * self.width + x])
+    }
+
+    pub fn set(&mut self, (x, y): Point, index: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = y * self.width + x;
+        if self.indices[i] != index {
+            self.indices[i] = index;
+            self.dirty.mark((x, y));
+        }
+    }
+
+    /// Sets the closest palette color to `color`, dithered by position if
+    /// `dither` is [Dither::Ordered]. Error diffusion needs a whole image,
+    /// so [Dither::FloydSteinberg] is treated as [Dither::None] here.
+    pub fn set_color(&mut self, point: Point, color: Color, dither: Dither) {
+        let index = match dither {
+            Dither::Ordered => self.palette.nearest_ordered(color, point),
+            Dither::None | Dither::FloydSteinberg => self.palette.nearest(color),
+        };
+        self.set(point, index);
+    }
+
+    /// Converts the inclusive `source_rect` of `source` to palette colors,
+    /// with its top left at `pos`.
+    pub fn quantize(&mut self, source: &PixelBuffer, source_rect: Rect, (pos_x, pos_y): Point, dither: Dither) {
+        let whole = ((0, 0), (source.width.saturating_sub(1), source.height.saturating_sub(1)));
+        let ((left, top), (right, bottom)) = intersect_rects(whole, source_rect);
+        if source.width == 0 || source.height == 0 || left > right || top > bottom {
+            return;
+        }
+
+        if dither != Dither::FloydSteinberg {
+            for y in top..=bottom {
+                for x in left..=right {
+                    let color = source.get((x, y)).unwrap_or(TRANSPARENT);
+                    self.set_color((pos_x + x - left, pos_y + y - top), color, dither);
+                }
+            }
+            return;
+        }
+
+        // error carried into this row and the next, in 1/16ths, with a
+        // column of padding either side
+        let width = right - left + 1;
+        let mut current = vec![[0i32; 3]; width + 2];
+        let mut next = vec![[0i32; 3]; width + 2];
+        for y in top..=bottom {
+            for x in left..=right {
+                let column = x - left + 1;
+                let error = current[column].map(|e| e / 16);
+                let wanted = offset(source.get((x, y)).unwrap_or(TRANSPARENT), error);
+                let index = self.palette.nearest(wanted);
+                let got = self.palette.get(index);
+                self.set((pos_x + x - left, pos_y + y - top), index);
+
+                let remainder = [
+                    wanted.r as i32 - got.r as i32,
+                    wanted.g as i32 - got.g as i32,
+                    wanted.b as i32 - got.b as i32,
+                ];
+                for channel in 0..3 {
+                    let e = remainder[channel];
+                    current[column + 1][channel] += e * 7;
+                    next[column - 1][channel] += e * 3;
+                    next[column][channel] += e * 5;
+                    next[column + 1][channel] += e;
+                }
+            }
+            current = core::mem::replace(&mut next, vec![[0; 3]; width + 2]);
+        }
+    }
+
+    /// Writes the colors of everything changed since the last resolve into
+    /// `target`, which must be the same size.
+    pub fn resolve(&mut self, target: &mut PixelBuffer) {
+        assert_eq!((target.width, target.height), (self.width, self.height), "resolve target size mismatch");
+        for ((left, top), (right, bottom)) in self.dirty.take_rects() {
+            for y in top..=bottom {
+                for x in left..=right {
+                    target.set((x, y), self.palette.get(self.indices[y * self.width + x]));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{BLACK, WHITE};
+
+    fn gray(value: u8) -> Color {
+        Color::rgb(value, value, value)
+    }
+
+    /// A `width`x1 buffer of one color
+    fn flat(width: usize, color: Color) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(width, 1);
+        for x in 0..width {
+            buffer.set((x, 0), color);
+        }
+        buffer
+    }
+
+    fn count(buffer: &IndexedBuffer, index: u8) -> usize {
+        buffer.indices.iter().filter(|&&i| i == index).count()
+    }
+
+    #[test]
+    fn test_nearest() {
+        let palette = Palette::new(PICO_8.to_vec());
+        assert_eq!(palette.nearest(BLACK), 0);
+        assert_eq!(palette.nearest(Color::rgb(250, 10, 70)), 8);
+        assert_eq!(palette.get(8), PICO_8[8]);
+        assert_eq!(palette.get(200), TRANSPARENT);
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let palette = Palette::from_hex("#000, #fff #ff0000").unwrap();
+        assert_eq!(palette.colors(), &[BLACK, WHITE, Color::rgb(255, 0, 0)]);
+        assert!(Palette::from_hex("#000 nope").is_err());
+        assert!(Palette::from_hex(" ").is_err());
+        assert_eq!(Palette::grayscale(3).colors(), &[BLACK, gray(127), WHITE]);
+    }
+
+    #[test]
+    fn test_ordered_dither_mixes_neighbours() {
+        let mut buffer = IndexedBuffer::new(16, 1, Palette::grayscale(2));
+        buffer.quantize(&flat(16, gray(128)), ((0, 0), (15, 0)), (0, 0), Dither::None);
+        assert_eq!(count(&buffer, 1), 16);
+
+        for y in 0..4 {
+            let mut row = IndexedBuffer::new(16, 4, Palette::grayscale(2));
+            row.quantize(&flat(16, gray(128)), ((0, 0), (15, 0)), (0, y), Dither::Ordered);
+            assert_eq!(count(&row, 1), 8, "row {}", y);
+        }
+    }
+
+    #[test]
+    fn test_palette_colors_are_not_dithered() {
+        let palette = Palette::grayscale(4);
+        for (x, &color) in palette.colors().iter().enumerate() {
+            for y in 0..4 {
+                assert_eq!(palette.nearest_ordered(color, (x, y)) as usize, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_floyd_steinberg_preserves_average() {
+        let mut source = PixelBuffer::new(64, 1);
+        for x in 0..64 {
+            source.set((x, 0), gray((x * 4) as u8));
+        }
+        let mut buffer = IndexedBuffer::new(64, 1, Palette::grayscale(2));
+        buffer.quantize(&source, ((0, 0), (63, 0)), (0, 0), Dither::FloydSteinberg);
+        // the source averages 126 of 255
+        let whites = count(&buffer, 1);
+        assert!((30..=33).contains(&whites), "{} white pixels", whites);
+        // and the dark end stays dark
+        assert_eq!(buffer.get((0, 0)), Some(0));
+        assert_eq!(buffer.get((63, 0)), Some(1));
+    }
+
+    #[test]
+    fn test_resolve_and_palette_swap() {
+        let mut buffer = IndexedBuffer::new(40, 40, Palette::grayscale(2));
+        let mut target = PixelBuffer::new(40, 40);
+        buffer.resolve(&mut target);
+        target.take_dirty_rects();
+        assert_eq!(target.get((39, 39)), Some(BLACK));
+
+        buffer.set((35, 35), 1);
+        buffer.resolve(&mut target);
+        assert_eq!(target.get((35, 35)), Some(WHITE));
+        assert_eq!(target.take_dirty_rects(), vec![((32, 32), (39, 39))]);
+
+        buffer.set_palette(Palette::new(vec![Color::rgb(0, 0, 255), Color::rgb(255, 255, 0)]));
+        buffer.resolve(&mut target);
+        assert_eq!(target.get((0, 0)), Some(Color::rgb(0, 0, 255)));
+        assert_eq!(target.get((35, 35)), Some(Color::rgb(255, 255, 0)));
+    }
+}
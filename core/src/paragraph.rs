@@ -0,0 +1,470 @@
+//! Paragraph layout. Text is broken into lines at `\n` and wherever it
+//! would run past a maximum width, then every glyph is placed according to
+//! its line's alignment. Words too long for a line of their own are
+//! hyphenated. Spans of rich text at different scales share a baseline.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    font::Font,
+    pixels::Color,
+    rich::{RichText, Style},
+    ui::Size,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Spaces stretch so lines reach both edges. A paragraph's last line,
+    /// and any line ending in `\n`, stays left-aligned.
+    Justify,
+}
+
+/// How text is broken into lines and lined up. Shorter lines are aligned
+/// within the longest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Paragraph {
+    max_width: Option<usize>,
+    line_spacing: usize,
+    align: Align,
+}
+
+/// A character and the index of its style
+type Item = (char, usize);
+
+struct Line {
+    items: Vec<Item>,
+    /// Broken to fit, so it should be justified
+    wrapped: bool,
+    /// Decides the height of the line if it's empty
+    style: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlacedGlyph {
+    pub c: char,
+    /// Where the glyph's cell's top left goes
+    pub pos: (isize, isize),
+    /// Index into [Layout::styles]
+    pub style: usize,
+}
+
+/// An underline or strikethrough
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decoration {
+    pub pos: (isize, isize),
+    pub size: Size,
+    pub style: usize,
+}
+
+/// Text broken into lines, with the place of each glyph and decoration
+/// worked out, relative to the paragraph's top left
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    glyphs: Vec<PlacedGlyph>,
+    decorations: Vec<Decoration>,
+    styles: Vec<Style>,
+    lines: usize,
+    size: Size,
+}
+
+impl Layout {
+    pub fn glyphs(&self) -> &[PlacedGlyph] {
+        &self.glyphs
+    }
+
+    pub fn decorations(&self) -> &[Decoration] {
+        &self.decorations
+    }
+
+    pub fn styles(&self) -> &[Style] {
+        &self.styles
+    }
+
+    /// Recolors every style without laying anything out again, since
+    /// color doesn't change where anything goes
+    pub fn set_color(&mut self, color: Color) {
+        for style in &mut self.styles {
+            style.color = color;
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// As wide as the longest line, from the top of the first line to the
+    /// bottom of the last, plus room for any shadows and outlines
+    pub fn size(&self) -> Size {
+        self.size
+    }
+}
+
+/// Measures characters in their own styles
+struct Measure<'a> {
+    font: &'a Font,
+    styles: &'a [Style],
+}
+
+impl Measure<'_> {
+    /// How far the pen moves past `items[i]`. Bold glyphs are a pixel
+    /// wider.
+    fn advance(&self, items: &[Item], i: usize) -> isize {
+        let (c, style) = items[i];
+        let style = &self.styles[style];
+        let next = items.get(i + 1).map(|&(next, _)| next);
+        self.font.pen_advance(c, next, style.scale) + style.bold as isize
+    }
+
+    fn width(&self, items: &[Item]) -> usize {
+        if items.is_empty() {
+            return 0;
+        }
+        let pen: isize = (0..items.len()).map(|i| self.advance(items, i)).sum();
+        (pen - self.font.spacing() as isize).max(0) as usize
+    }
+}
+
+impl Paragraph {
+    /// One line per `\n`, never wrapped, aligned left
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps lines between words so they're no wider than `max_width`
+    /// pixels
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Extra pixels between lines, on top of the font's line height
+    pub fn with_line_spacing(mut self, line_spacing: usize) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    fn break_lines(&self, measure: &Measure, items: &[Item]) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut hard_lines = items.split(|&(c, _)| c == '\n').peekable();
+        let mut start = 0;
+        while let Some(hard_line) = hard_lines.next() {
+            // the line break's style, or for the last line the style of
+            // the last character
+            let style = items.get(start + hard_line.len()).or(items.last()).map_or(0, |&(_, style)| style);
+            start += hard_line.len() + 1;
+            let hard_line = match hard_line.split_last() {
+                Some((('\r', _), rest)) if hard_lines.peek().is_some() => rest,
+                _ => hard_line,
+            };
+            let max_width = match self.max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(Line { items: hard_line.to_vec(), wrapped: false, style });
+                    continue;
+                }
+            };
+            let fits = |line: &[Item]| measure.width(line) <= max_width;
+            let hyphenated = |word: &[Item]| [word, &[('-', word[word.len() - 1].1)]].concat();
+
+            // where each word starts and ends, with a space before all but
+            // the first
+            let mut words = vec![];
+            let mut word_start = 0;
+            for (i, &(c, _)) in hard_line.iter().enumerate() {
+                if c == ' ' {
+                    words.push(word_start..i);
+                    word_start = i + 1;
+                }
+            }
+            words.push(word_start..hard_line.len());
+
+            // `None` until something's been put on the line, so indents
+            // survive but spaces at a wrap don't
+            let mut line: Option<Vec<Item>> = None;
+            for word in words {
+                let candidate = match &line {
+                    Some(line) => [line, &hard_line[word.start - 1..word.end]].concat(),
+                    None => hard_line[word.clone()].to_vec(),
+                };
+                if fits(&candidate) {
+                    line = Some(candidate);
+                    continue;
+                }
+                let mut full = line.take().unwrap_or_default();
+                while let Some(&(' ', _)) = full.last() {
+                    full.pop();
+                }
+                if !full.is_empty() {
+                    lines.push(Line { items: full, wrapped: true, style });
+                }
+                let mut rest = &hard_line[word];
+                while !fits(rest) && rest.len() > 1 {
+                    // the most of the word that fits with a hyphen, but
+                    // always at least a character
+                    let mut end = 1;
+                    for next_end in 2..rest.len() {
+                        if !fits(&hyphenated(&rest[..next_end])) {
+                            break;
+                        }
+                        end = next_end;
+                    }
+                    lines.push(Line { items: hyphenated(&rest[..end]), wrapped: true, style });
+                    rest = &rest[end..];
+                }
+                line = if rest.is_empty() { None } else { Some(rest.to_vec()) };
+            }
+            lines.push(Line { items: line.unwrap_or_default(), wrapped: false, style });
+        }
+        lines
+    }
+
+    pub fn layout(&self, font: &Font, text: &RichText) -> Layout {
+        let styles: Vec<Style> = text.spans().iter().map(|&(_, style)| style).collect();
+        if text.is_empty() {
+            return Layout { styles, ..Layout::default() };
+        }
+        let items: Vec<Item> = text.spans().iter().enumerate().flat_map(|(i, (span, _))| span.chars().map(move |c| (c, i))).collect();
+        let measure = Measure { font, styles: &styles };
+        let lines = self.break_lines(&measure, &items);
+        let widths: Vec<usize> = lines.iter().map(|line| measure.width(&line.items)).collect();
+        let width = widths.iter().copied().max().unwrap_or(0);
+        let (baseline, cell_height) = (font.baseline(), font.cell_size().1);
+        let descent = font.line_height().saturating_sub(baseline);
+
+        let mut glyphs = Vec::with_capacity(items.len());
+        let mut decorations = Vec::new();
+        let (mut top, mut bottom) = (0, 0);
+        for (line, line_width) in lines.iter().zip(widths) {
+            // every glyph sits on the same baseline, so the biggest decides
+            // how tall the line is
+            let line_scale = line.items.iter().map(|&(_, style)| styles[style].scale).max().unwrap_or(styles[line.style].scale);
+            let line_baseline = top + baseline * line_scale;
+            bottom = line_baseline + cell_height.saturating_sub(baseline) * line_scale;
+
+            let slack = width - line_width;
+            let left = match self.align {
+                Align::Left | Align::Justify => 0,
+                Align::Center => slack / 2,
+                Align::Right => slack,
+            };
+            let spaces = if self.align == Align::Justify && line.wrapped { line.items.iter().filter(|&&(c, _)| c == ' ').count() } else { 0 };
+            let mut spaces_before = 0;
+            let mut pen = 0;
+            // the decorated run so far: its style, and where it starts and
+            // ends
+            let mut run: Option<(usize, isize, isize)> = None;
+            for (i, &(c, style)) in line.items.iter().enumerate() {
+                let scale = styles[style].scale;
+                // shares the slack out between the spaces as evenly as
+                // whole pixels allow
+                let stretch = (slack * spaces_before).checked_div(spaces).unwrap_or(0);
+                let x = pen + (left + stretch) as isize;
+                let y = (line_baseline - baseline * scale) as isize;
+                glyphs.push(PlacedGlyph { c, pos: (x + font.cell_offset(c, scale), y), style });
+                let advance = measure.advance(&line.items, i);
+                pen += advance;
+                if c == ' ' {
+                    spaces_before += 1;
+                }
+
+                let end = x + advance - font.spacing() as isize;
+                run = match run {
+                    Some((run_style, run_start, _)) if run_style == style => Some((style, run_start, end)),
+                    _ => {
+                        decorate(&mut decorations, run, &styles, font, line_baseline);
+                        Some((style, x, end))
+                    }
+                };
+            }
+            decorate(&mut decorations, run, &styles, font, line_baseline);
+            top = line_baseline + descent * line_scale + self.line_spacing;
+        }
+
+        // outlines reach a pixel out all round, and shadows a glyph pixel
+        // down and right, so everything moves in to make room for them
+        let before = styles.iter().any(|style| style.outline.is_some()) as usize;
+        let after = styles
+            .iter()
+            .map(|style| style.shadow.map_or(0, |_| style.scale).max(style.outline.map_or(0, |_| 1)))
+            .max()
+            .unwrap_or(0);
+        let shift = before as isize;
+        for glyph in &mut glyphs {
+            glyph.pos = (glyph.pos.0 + shift, glyph.pos.1 + shift);
+        }
+        for decoration in &mut decorations {
+            decoration.pos = (decoration.pos.0 + shift, decoration.pos.1 + shift);
+        }
+        let size = (width + before + after, bottom + before + after);
+        Layout { glyphs, decorations, styles, lines: lines.len(), size }
+    }
+}
+
+/// Adds the underline and strikethrough, if its style has them, for a run
+/// of glyphs in one style
+fn decorate(decorations: &mut Vec<Decoration>, run: Option<(usize, isize, isize)>, styles: &[Style], font: &Font, line_baseline: usize) {
+    // scale 0 text draws nothing, so it has nothing to decorate either
+    let (style, start, end) = match run {
+        Some(run) if run.2 > run.1 && styles[run.0].scale > 0 => run,
+        _ => return,
+    };
+    let size = ((end - start) as usize, styles[style].scale);
+    let glyph_top = line_baseline - font.baseline() * size.1;
+    if styles[style].underline {
+        decorations.push(Decoration { pos: (start, line_baseline as isize), size, style });
+    }
+    if styles[style].strikethrough {
+        let y = glyph_top + font.baseline() / 2 * size.1;
+        decorations.push(Decoration { pos: (start, y as isize), size, style });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{RED, WHITE};
+    use alloc::string::String;
+
+    /// The builtin face monospaced, so every glyph at scale 1 is 6 pixels
+    /// from the next
+    fn font() -> Font {
+        Font::builtin().with_monospace(true)
+    }
+
+    fn plain(text: &str, scale: usize) -> RichText {
+        RichText::plain(String::from(text), Style::new(WHITE, scale))
+    }
+
+    fn lines(paragraph: Paragraph, text: &str) -> Vec<String> {
+        let (font, styles) = (font(), [Style::new(WHITE, 1)]);
+        let items: Vec<Item> = text.chars().map(|c| (c, 0)).collect();
+        let lines = paragraph.break_lines(&Measure { font: &font, styles: &styles }, &items);
+        lines.into_iter().map(|line| line.items.iter().map(|&(c, _)| c).collect()).collect()
+    }
+
+    fn placed(layout: &Layout) -> Vec<(char, (isize, isize))> {
+        layout.glyphs().iter().map(|glyph| (glyph.c, glyph.pos)).collect()
+    }
+
+    #[test]
+    fn test_breaks() {
+        // "ab cd" is 29 pixels wide
+        let wrapped = Paragraph::new().with_max_width(29);
+        assert_eq!(lines(wrapped, "ab cd ef"), ["ab cd", "ef"]);
+        assert_eq!(lines(wrapped, "ab\nc  d\r\n\n  gh"), ["ab", "c  d", "", "  gh"]);
+        // spaces at a wrap are dropped
+        assert_eq!(lines(Paragraph::new().with_max_width(11), "ab   cd"), ["ab", "cd"]);
+        assert_eq!(lines(Paragraph::new(), "ab cd ef gh\nij"), ["ab cd ef gh", "ij"]);
+    }
+
+    #[test]
+    fn test_hyphenates_long_words() {
+        // four characters and a hyphen
+        let wrapped = Paragraph::new().with_max_width(29);
+        assert_eq!(lines(wrapped, "a abcdefghij b"), ["a", "abcd-", "efgh-", "ij b"]);
+        // narrower than a single glyph, which still has to go somewhere
+        assert_eq!(lines(Paragraph::new().with_max_width(3), "ab"), ["a-", "b"]);
+    }
+
+    #[test]
+    fn test_layout() {
+        let font = font();
+        let layout = Paragraph::new().with_line_spacing(2).layout(&font, &plain("ab\nc", 2));
+        // lines are 6 * 2 + 2 apart
+        assert_eq!(placed(&layout), [('a', (0, 0)), ('b', (11, 0)), ('c', (0, 14))]);
+        assert_eq!((layout.lines(), layout.size()), (2, (21, 24)));
+        // a single line measures the same as the font does
+        assert_eq!(Paragraph::new().layout(&font, &plain("abc", 3)).size(), font.measure("abc", 3));
+        assert_eq!(Paragraph::new().layout(&font, &plain("", 3)).size(), (0, 0));
+    }
+
+    #[test]
+    fn test_align() {
+        let font = font();
+        let starts = |align: Align| -> Vec<isize> {
+            let layout = Paragraph::new().with_align(align).layout(&font, &plain("abcde\nab", 1));
+            vec![layout.glyphs()[0].pos.0, layout.glyphs()[5].pos.0]
+        };
+        // the second line is 18 pixels narrower
+        assert_eq!(starts(Align::Left), [0, 0]);
+        assert_eq!(starts(Align::Center), [0, 9]);
+        assert_eq!(starts(Align::Right), [0, 18]);
+        // a hard break isn't justified
+        assert_eq!(starts(Align::Justify), [0, 0]);
+    }
+
+    #[test]
+    fn test_justify() {
+        let text = "a b c d e ffffffffff g";
+        let layout = Paragraph::new().with_max_width(59).with_align(Align::Justify).layout(&font(), &plain(text, 1));
+        // "a b c d e" is 6 pixels short of the long word, shared out
+        // between its four spaces
+        let xs: Vec<isize> = layout.glyphs().iter().map(|glyph| glyph.pos.0).collect();
+        assert_eq!(xs[..9], [0, 6, 13, 19, 27, 33, 40, 46, 54]);
+        // the last line isn't stretched
+        assert_eq!(placed(&layout).last(), Some(&('g', (0, 12))));
+    }
+
+    #[test]
+    fn test_mixed_scales_share_a_baseline() {
+        let font = font();
+        let text = RichText::new().with_span("a", Style::new(WHITE, 1)).with_span("B\nc", Style::new(RED, 3));
+        let layout = Paragraph::new().layout(&font, &text);
+        // a's bottom lines up with B's, 15 pixels down; the next line is
+        // 6 * 3 below the first
+        assert_eq!(placed(&layout), [('a', (0, 10)), ('B', (6, 0)), ('c', (0, 18))]);
+        assert_eq!(layout.glyphs()[1].style, 1);
+        assert_eq!(layout.size(), (21, 33));
+    }
+
+    #[test]
+    fn test_decorations() {
+        let font = font();
+        let base = Style::new(WHITE, 2);
+        let text = RichText::parse("a [u]bc[/u] [s]d[/s]", base).unwrap();
+        let layout = Paragraph::new().layout(&font, &text);
+        // the underline runs along the baseline, from b's left edge to c's
+        // right, and the strikethrough through the middle of d
+        assert_eq!(
+            layout.decorations(),
+            [
+                Decoration { pos: (22, 10), size: (21, 2), style: 1 },
+                Decoration { pos: (55, 4), size: (10, 2), style: 3 },
+            ]
+        );
+        // bold still makes scale 0 text a pixel wide, but no taller
+        let invisible = Style { bold: true, underline: true, ..Style::new(WHITE, 0) };
+        let layout = Paragraph::new().layout(&font, &RichText::plain(String::from("hi"), invisible));
+        assert!(layout.decorations().is_empty());
+    }
+
+    #[test]
+    fn test_effects_make_room() {
+        let font = font();
+        let shadowed = Style { shadow: Some(RED), ..Style::new(WHITE, 4) };
+        let layout = Paragraph::new().layout(&font, &RichText::plain(String::from("A"), shadowed));
+        assert_eq!((placed(&layout), layout.size()), (vec![('A', (0, 0))], (24, 24)));
+        // an outline reaches up and left too, so the text moves in
+        let outlined = Style { outline: Some(RED), ..Style::new(WHITE, 4) };
+        let layout = Paragraph::new().layout(&font, &RichText::plain(String::from("A"), outlined));
+        assert_eq!((placed(&layout), layout.size()), (vec![('A', (1, 1))], (22, 22)));
+    }
+
+    #[test]
+    fn test_bold_is_a_pixel_wider() {
+        let font = font();
+        let bold = Style { bold: true, ..Style::new(WHITE, 1) };
+        let layout = Paragraph::new().layout(&font, &RichText::plain(String::from("ab"), bold));
+        assert_eq!(placed(&layout), [('a', (0, 0)), ('b', (7, 0))]);
+        assert_eq!(layout.size(), (13, 5));
+    }
+}
@@ -0,0 +1,1035 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{convert::TryFrom, mem};
+use crate::{
+    color::mul_255,
+    dirty::{DirtyRegions, DIRTY_TILE_SIZE},
+    font::Font,
+    gradient::Paint,
+    paragraph::Layout,
+    simd,
+    text::TextRasterizer,
+    tiles::{Command, Op},
+    transform::{pixel_area, pixel_span, Area, Transform},
+    ui::{intersect_rects, is_point_in_rect, Point, Rect, Size},
+};
+
+pub use crate::color::{Color, PremultipliedColor};
+
+pub const WHITE: Color = Color::rgb(255, 255, 255);
+pub const BLACK: Color = Color::rgb(0, 0, 0);
+pub const RED: Color = Color::rgb(255, 0, 0);
+pub const BLUE: Color = Color::rgb(0, 0, 255);
+pub const GREEN: Color = Color::rgb(0, 255, 0);
+pub const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
+
+/// How a source color is combined with the pixel already in the buffer.
+///
+/// Every mode except [BlendMode::Replace] respects the source alpha, so a
+/// half-transparent color only covers half of what's underneath.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite all four channels, alpha included
+    Replace,
+    /// Porter-Duff source-over
+    #[default]
+    Over,
+    /// Darkens; white is the identity
+    Multiply,
+    /// Lightens; black is the identity
+    Screen,
+    /// Adds channels together, saturating at white
+    Additive,
+}
+
+fn mix_channel(mode: BlendMode, src: u32, dst: u32) -> u32 {
+    match mode {
+        BlendMode::Replace | BlendMode::Over => src,
+        BlendMode::Multiply => mul_255(src, dst),
+        BlendMode::Screen => src + dst - mul_255(src, dst),
+        BlendMode::Additive => (src + dst).min(255),
+    }
+}
+
+/// Composites `src` on top of `dst` using `mode`.
+///
+/// General formula from the W3C compositing spec: the mode's mixed
+/// color is weighted by how opaque the destination is, then the result is
+/// laid over the destination with source-over.
+pub fn blend(src: Color, dst: Color, mode: BlendMode) -> Color {
+    let (sr, sg, sb, sa) = (src.r as u32, src.g as u32, src.b as u32, src.a as u32);
+    let (dr, dg, db, da) = (dst.r as u32, dst.g as u32, dst.b as u32, dst.a as u32);
+
+    if mode == BlendMode::Replace || sa == 255 && mode == BlendMode::Over {
+        return src;
+    }
+    if sa == 0 {
+        return dst;
+    }
+
+    let out_a = sa + mul_255(da, 255 - sa);
+    let channel = |s: u32, d: u32| -> u8 {
+        // source color after the blend function, weighted by dest coverage
+        let mixed = mul_255(255 - da, s) + mul_255(da, mix_channel(mode, s, d));
+        // premultiplied source-over (scaled by 255), then un-premultiply
+        let premul = sa * mixed + mul_255(da, d) * (255 - sa);
+        ((premul + out_a / 2) / out_a).min(255) as u8
+    };
+
+    Color::new(channel(sr, dr), channel(sg, dg), channel(sb, db), out_a as u8)
+}
+
+/// How a blit picks source pixels when drawing at a different size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampling {
+    /// Blocky; the right choice for pixel art and integer scales
+    #[default]
+    Nearest,
+    /// Smooth; blends the four closest source pixels
+    Bilinear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlitOptions {
+    /// Part of the source to copy (inclusive), or all of it if `None`
+    pub source_rect: Option<Rect>,
+    /// Size to draw at, or the source rect's size if `None`
+    pub size: Option<Size>,
+    pub sampling: Sampling,
+    /// Multiplied into every source pixel's alpha
+    pub alpha: u8,
+    pub blend_mode: BlendMode,
+}
+
+impl Default for BlitOptions {
+    fn default() -> Self {
+        Self {
+            source_rect: None,
+            size: None,
+            sampling: Sampling::default(),
+            alpha: 255,
+            blend_mode: BlendMode::default(),
+        }
+    }
+}
+
+impl BlitOptions {
+    /// The source rect, clamped to `source`. May be empty.
+    pub fn clamped_source_rect(&self, source: &PixelBuffer) -> Rect {
+        let whole = ((0, 0), (source.width.saturating_sub(1), source.height.saturating_sub(1)));
+        match self.source_rect {
+            Some(rect) => intersect_rects(whole, rect),
+            None => whole,
+        }
+    }
+
+    pub fn output_size(&self, source: &PixelBuffer) -> Size {
+        if let Some(size) = self.size {
+            return size;
+        }
+        let ((left, top), (right, bottom)) = self.clamped_source_rect(source);
+        if source.width == 0 || source.height == 0 || left > right || top > bottom {
+            return (0, 0);
+        }
+        (right - left + 1, bottom - top + 1)
+    }
+}
+
+/// Interpolates in premultiplied space so transparent neighbours don't
+/// bleed their (meaningless) color into the edges.
+fn sample_bilinear(source: &PixelBuffer, ((left, top), (right, bottom)): Rect, x: f32, y: f32) -> Color {
+    let x = x.max(0.0).min((right - left) as f32);
+    let y = y.max(0.0).min((bottom - top) as f32);
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x0 + 1).min(right - left), (y0 + 1).min(bottom - top));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let mut sum = [0.0f32; 4];
+    for (sx, sy, weight) in [
+        (x0, y0, (1.0 - tx) * (1.0 - ty)),
+        (x1, y0, tx * (1.0 - ty)),
+        (x0, y1, (1.0 - tx) * ty),
+        (x1, y1, tx * ty),
+    ] {
+        let Color { r, g, b, a } = source.get((left + sx, top + sy)).unwrap_or(TRANSPARENT);
+        let alpha = a as f32 * weight;
+        sum[0] += r as f32 * alpha;
+        sum[1] += g as f32 * alpha;
+        sum[2] += b as f32 * alpha;
+        sum[3] += alpha;
+    }
+    if sum[3] <= 0.0 {
+        return TRANSPARENT;
+    }
+    Color::new(
+        (sum[0] / sum[3] + 0.5) as u8,
+        (sum[1] / sum[3] + 0.5) as u8,
+        (sum[2] / sum[3] + 0.5) as u8,
+        (sum[3] + 0.5) as u8,
+    )
+}
+
+/// Nearest neighbour looks up whole pixels, so `u` and `v` (local
+/// coordinates within the blit) are floored first
+fn sample_blit(source: &PixelBuffer, source_rect: Rect, (width, height): Size, sampling: Sampling, u: f32, v: f32) -> Color {
+    let ((source_left, source_top), (source_right, source_bottom)) = source_rect;
+    let source_width = source_right - source_left + 1;
+    let source_height = source_bottom - source_top + 1;
+    match sampling {
+        Sampling::Nearest => {
+            let (x, y) = (u as usize, v as usize);
+            source
+                .get((source_left + x * source_width / width, source_top + y * source_height / height))
+                .unwrap_or(TRANSPARENT)
+        }
+        Sampling::Bilinear => sample_bilinear(
+            source,
+            source_rect,
+            u * source_width as f32 / width as f32 - 0.5,
+            v * source_height as f32 / height as f32 - 0.5,
+        ),
+    }
+}
+
+/// What [PixelBuffer::clip_rect] is when nothing can be drawn
+const EMPTY_RECT: Rect = ((1, 1), (0, 0));
+
+pub struct PixelBuffer {
+    data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    /// Where the stored pixels start. Usually the whole buffer is stored,
+    /// but a tile only stores its own part of the picture.
+    origin: Point,
+    /// Width and height of the stored pixels
+    stored: Size,
+    /// Each entry is already intersected with the ones below it
+    clip_stack: Vec<Rect>,
+    /// Each entry is already combined with the ones below it
+    transform_stack: Vec<Transform>,
+    dirty: DirtyRegions,
+    /// When set, drawing is recorded here instead of touching pixels
+    recording: Option<Vec<Command>>,
+    /// Keeps expanded glyphs around between frames
+    text: TextRasterizer,
+}
+
+pub const NUM_CHANNELS: usize = 4;
+
+impl PixelBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::from_rgba(width, height, vec![0; width * height * NUM_CHANNELS])
+    }
+
+    /// A buffer that records drawing for [crate::tiles] instead of doing
+    /// it, starting with the same clip and transform as `self`. Borrows
+    /// the text rasterizer until [PixelBuffer::end_recording].
+    pub(crate) fn recorder(&mut self) -> Self {
+        Self {
+            data: vec![],
+            width: self.width,
+            height: self.height,
+            origin: (0, 0),
+            stored: (self.width, self.height),
+            clip_stack: self.clip_stack.clone(),
+            transform_stack: self.transform_stack.clone(),
+            dirty: DirtyRegions::new(0, 0),
+            recording: Some(vec![]),
+            text: mem::take(&mut self.text),
+        }
+    }
+
+    /// What `recorder`, made by [PixelBuffer::recorder], recorded
+    pub(crate) fn end_recording(&mut self, mut recorder: Self) -> Vec<Command> {
+        self.text = recorder.text;
+        recorder.recording.take().unwrap_or_default()
+    }
+
+    /// A copy of just the pixels in `rect`, which must be inside this
+    /// buffer. Drawing into it works in this buffer's coordinates.
+    pub(crate) fn copy_window(&self, ((left, top), (right, bottom)): Rect) -> Self {
+        let stored = (right - left + 1, bottom - top + 1);
+        let mut data = Vec::with_capacity(stored.0 * stored.1 * NUM_CHANNELS);
+        for y in top..=bottom {
+            let offset = self.offset((left, y));
+            data.extend_from_slice(&self.data[offset..offset + stored.0 * NUM_CHANNELS]);
+        }
+        Self {
+            data,
+            width: self.width,
+            height: self.height,
+            origin: (left, top),
+            stored,
+            clip_stack: vec![],
+            transform_stack: vec![],
+            dirty: DirtyRegions::new(0, 0),
+            recording: None,
+            text: TextRasterizer::new(),
+        }
+    }
+
+    /// Copies a buffer made by [PixelBuffer::copy_window] back where it
+    /// came from
+    pub(crate) fn paste_window(&mut self, window: &PixelBuffer) {
+        let (left, top) = window.origin;
+        let row_bytes = window.stored.0 * NUM_CHANNELS;
+        if row_bytes == 0 {
+            return;
+        }
+        for (i, row) in window.data.chunks_exact(row_bytes).enumerate() {
+            self.write_span(top + i, left, row);
+        }
+    }
+
+    /// The stored pixels, and where writes go when nothing is clipped
+    fn stored_rect(&self) -> Rect {
+        let ((left, top), (width, height)) = (self.origin, self.stored);
+        if width == 0 || height == 0 {
+            return EMPTY_RECT;
+        }
+        ((left, top), (left + width - 1, top + height - 1))
+    }
+
+    /// Index in `data` of a stored pixel
+    fn offset(&self, (x, y): Point) -> usize {
+        ((y - self.origin.1) * self.stored.0 + x - self.origin.0) * NUM_CHANNELS
+    }
+
+    /// Records `op` if recording, returning whether it did. Ops that can't
+    /// touch anything on screen (`bounds` is `None`) are dropped.
+    fn record(&mut self, bounds: Option<Rect>, op: impl FnOnce() -> Op) -> bool {
+        if self.recording.is_none() {
+            return false;
+        }
+        if let Some(bounds) = bounds {
+            let command = Command { clip: self.clip_rect(), transform: self.transform(), bounds, op: op() };
+            if let Some(recording) = &mut self.recording {
+                recording.push(command);
+            }
+        }
+        true
+    }
+
+    /// Performs a recorded command under the clip and transform it was
+    /// recorded with
+    pub(crate) fn replay(&mut self, command: &Command) {
+        self.clip_stack.push(intersect_rects(self.clip_rect(), command.clip));
+        self.transform_stack.push(command.transform);
+        match &command.op {
+            Op::Set(point, color) => self.set(*point, *color),
+            Op::Blend(point, color, mode) => self.blend(*point, *color, *mode),
+            Op::Span { y, x0, x1, color, mode } => self.fill_span(*y, *x0, *x1, *color, *mode),
+            Op::Fill { area, color, gradient, mode } => {
+                let paint = match gradient {
+                    Some(gradient) => Paint::Gradient(gradient),
+                    None => Paint::Solid(*color),
+                };
+                self.fill_area(*area, paint, *mode);
+            }
+            Op::Blit { source, pos, options } => self.blit(source, *pos, options),
+        }
+        self.transform_stack.pop();
+        self.clip_stack.pop();
+    }
+
+    /// Marks `rect` as needing upload even if none of its pixels change.
+    /// Writes that actually change a pixel are tracked automatically.
+    pub fn invalidate(&mut self, rect: Rect) {
+        self.dirty.mark_rect(rect);
+    }
+
+    /// The regions changed since the last call, as inclusive rects. A new
+    /// buffer is entirely dirty.
+    pub fn take_dirty_rects(&mut self) -> Vec<Rect> {
+        self.dirty.take_rects()
+    }
+
+    /// Wraps existing RGBA bytes, e.g. a decoded image
+    pub fn from_rgba(width: usize, height: usize, data: Vec<u8>) -> Self {
+        assert_eq!(data.len(), width * height * NUM_CHANNELS, "RGBA data doesn't match dimensions");
+        Self {
+            data,
+            width,
+            height,
+            origin: (0, 0),
+            stored: (width, height),
+            clip_stack: vec![],
+            transform_stack: vec![],
+            dirty: DirtyRegions::new(width, height),
+            recording: None,
+            text: TextRasterizer::new(),
+        }
+    }
+
+    /// Restricts every following write to `rect` (inclusive), on top of any
+    /// clip already in effect. Must be paired with [PixelBuffer::pop_clip].
+    ///
+    /// `rect` is transformed like anything else drawn; if that rotates it,
+    /// the clip is its bounding box.
+    pub fn push_clip(&mut self, (topleft, bottomright): Rect) {
+        let clip = if topleft.0 > bottomright.0 || topleft.1 > bottomright.1 {
+            EMPTY_RECT
+        } else {
+            self.device_bounds(pixel_area(topleft, bottomright)).unwrap_or(EMPTY_RECT)
+        };
+        self.clip_stack.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    /// The region writes are currently allowed to touch. May be empty, in
+    /// which case topleft is past bottomright.
+    pub fn clip_rect(&self) -> Rect {
+        match self.clip_stack.last() {
+            Some(clip) => *clip,
+            None => self.stored_rect(),
+        }
+    }
+
+    /// Applies `transform` to everything drawn after, on top of any
+    /// transform already in effect. Must be paired with
+    /// [PixelBuffer::pop_transform].
+    ///
+    /// Fills, text, shapes, blits and clips all take coordinates in this
+    /// local space; per-pixel access like [PixelBuffer::set] always
+    /// addresses device pixels.
+    pub fn push_transform(&mut self, transform: Transform) {
+        let combined = self.transform() * transform;
+        self.transform_stack.push(combined);
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.transform_stack.pop();
+    }
+
+    /// Maps local coordinates to device pixels
+    pub fn transform(&self) -> Transform {
+        self.transform_stack.last().copied().unwrap_or_default()
+    }
+
+    /// The device pixels, within the clip, whose centers land in the
+    /// bounding box of local `area`
+    fn device_bounds(&self, area: Area) -> Option<Rect> {
+        let (left, top, right, bottom) = self.transform().bounds(area);
+        let (x0, x1) = pixel_span(left, right)?;
+        let (y0, y1) = pixel_span(top, bottom)?;
+        let clipped = intersect_rects(self.clip_rect(), ((x0, y0), (x1, y1)));
+        let ((left, top), (right, bottom)) = clipped;
+        if left > right || top > bottom {
+            return None;
+        }
+        Some(clipped)
+    }
+
+    /// Blends `paint` over every device pixel whose center lands in local
+    /// `area`. Neighbouring areas never overlap or leave gaps, whatever the
+    /// transform. Gradients are sampled at each pixel's local center.
+    pub(crate) fn fill_area(&mut self, area: Area, paint: Paint, mode: BlendMode) {
+        let bounds = self.device_bounds(area);
+        let recorded = self.record(bounds, || match paint {
+            Paint::Solid(color) => Op::Fill { area, color, gradient: None, mode },
+            Paint::Gradient(gradient) => Op::Fill { area, color: TRANSPARENT, gradient: Some(gradient.clone()), mode },
+        });
+        let (Some(((left, top), (right, bottom))), false) = (bounds, recorded) else {
+            return;
+        };
+        let transform = self.transform();
+        if let (Paint::Solid(color), true) = (paint, transform.is_axis_aligned()) {
+            // the bounding box is exactly the area
+            for y in top..=bottom {
+                self.fill_span(y, left, right, color, mode);
+            }
+            return;
+        }
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        if transform.is_axis_aligned() && mode == BlendMode::Replace {
+            // work out a row of gradient at a time, then write it in bulk
+            let mut row = vec![0u8; (right - left + 1) * NUM_CHANNELS];
+            for y in top..=bottom {
+                for (x, pixel) in (left..=right).zip(row.chunks_exact_mut(NUM_CHANNELS)) {
+                    let local = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+                    pixel.copy_from_slice(&<[u8; 4]>::from(paint.color_at(local)));
+                }
+                self.write_span(y, left, &row);
+            }
+            return;
+        }
+        let (area_left, area_top, area_right, area_bottom) = area;
+        for y in top..=bottom {
+            for x in left..=right {
+                let (local_x, local_y) = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+                if local_x >= area_left && local_x < area_right && local_y >= area_top && local_y < area_bottom {
+                    self.blend((x, y), paint.color_at((local_x, local_y)), mode);
+                }
+            }
+        }
+    }
+
+    /// Fills the inclusive `rect`, through the transform, with a color or
+    /// a gradient
+    pub fn fill_rect<'a>(&mut self, (topleft, bottomright): Rect, paint: impl Into<Paint<'a>>, mode: BlendMode) {
+        if topleft.0 > bottomright.0 || topleft.1 > bottomright.1 {
+            return;
+        }
+        self.fill_area(pixel_area(topleft, bottomright), paint.into(), mode);
+    }
+
+    /// Blends `color` over device pixels `x0..=x1` of row `y`, ignoring the
+    /// transform but not the clip. Opaque and [BlendMode::Replace] spans are
+    /// written in bulk.
+    pub fn fill_span(&mut self, y: usize, x0: usize, x1: usize, color: Color, mode: BlendMode) {
+        let ((clip_left, clip_top), (clip_right, clip_bottom)) = self.clip_rect();
+        let (x0, x1) = (x0.max(clip_left), x1.min(clip_right));
+        if y < clip_top || y > clip_bottom || x0 > x1 {
+            return;
+        }
+        if self.record(Some(((x0, y), (x1, y))), || Op::Span { y, x0, x1, color, mode }) {
+            return;
+        }
+        let bulk = mode == BlendMode::Replace || mode == BlendMode::Over && color.a == 255;
+        if !bulk {
+            if mode == BlendMode::Over && color.a == 0 {
+                return;
+            }
+            for x in x0..=x1 {
+                self.blend((x, y), color, mode);
+            }
+            return;
+        }
+        let pixel = <[u8; 4]>::from(color);
+        // a dirty tile at a time, so only tiles that really change get marked
+        let mut x = x0;
+        while x <= x1 {
+            let end = ((x / DIRTY_TILE_SIZE + 1) * DIRTY_TILE_SIZE - 1).min(x1);
+            let (offset, end_offset) = (self.offset((x, y)), self.offset((end, y)) + NUM_CHANNELS);
+            if simd::fill(&mut self.data[offset..end_offset], pixel) {
+                self.dirty.mark((x, y));
+            }
+            x = end + 1;
+        }
+    }
+
+    /// Copies RGBA bytes into row `y` starting at device pixel `x`, within
+    /// the clip
+    fn write_span(&mut self, y: usize, x: usize, row: &[u8]) {
+        let ((clip_left, clip_top), (clip_right, clip_bottom)) = self.clip_rect();
+        let width = row.len() / NUM_CHANNELS;
+        if width == 0 || y < clip_top || y > clip_bottom {
+            return;
+        }
+        let (x0, x1) = (x.max(clip_left), (x + width - 1).min(clip_right));
+        let mut start = x0;
+        while start <= x1 {
+            let end = ((start / DIRTY_TILE_SIZE + 1) * DIRTY_TILE_SIZE - 1).min(x1);
+            let source = &row[(start - x) * NUM_CHANNELS..(end - x + 1) * NUM_CHANNELS];
+            let offset = self.offset((start, y));
+            if simd::copy(&mut self.data[offset..offset + source.len()], source) {
+                self.dirty.mark((start, y));
+            }
+            start = end + 1;
+        }
+    }
+
+    /// Overwrites every pixel in the clip with `color`, alpha included
+    pub fn clear(&mut self, color: Color) {
+        let ((left, top), (right, bottom)) = self.clip_rect();
+        if left > right || top > bottom {
+            return;
+        }
+        for y in top..=bottom {
+            self.fill_span(y, left, right, color, BlendMode::Replace);
+        }
+    }
+
+    /// `None` outside the buffer, and always while recording
+    pub fn get(&self, (x, y): Point) -> Option<Color> {
+        if self.recording.is_some() || !is_point_in_rect((x, y), self.stored_rect()) {
+            return None;
+        }
+
+        let offset = self.offset((x, y));
+        Some(Color::new(
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ))
+    }
+
+    pub fn set(&mut self, (x, y): Point, Color { r, g, b, a }: Color) {
+        if !is_point_in_rect((x, y), self.clip_rect()) {
+            return;
+        }
+        if self.record(Some(((x, y), (x, y))), || Op::Set((x, y), Color { r, g, b, a })) {
+            return;
+        }
+
+        let offset = self.offset((x, y));
+        let pixel = &mut self.data[offset..offset + NUM_CHANNELS];
+        // repainting a pixel with what it already had shouldn't cost an upload
+        if pixel == [r, g, b, a] {
+            return;
+        }
+        pixel.copy_from_slice(&[r, g, b, a]);
+        self.dirty.mark((x, y));
+    }
+
+    /// Like [PixelBuffer::set], but composites `color` over the existing
+    /// pixel instead of overwriting it.
+    pub fn blend(&mut self, point: Point, color: Color, mode: BlendMode) {
+        if !is_point_in_rect(point, self.clip_rect()) {
+            return;
+        }
+        if self.record(Some((point, point)), || Op::Blend(point, color, mode)) {
+            return;
+        }
+        if let Some(existing) = self.get(point) {
+            self.set(point, blend(color, existing, mode));
+        }
+    }
+
+    /// Draws `source` (or part of it) with its top left at `pos`, scaling
+    /// and blending as `options` say. Any transform applies too, rotation
+    /// included.
+    pub fn blit(&mut self, source: &PixelBuffer, pos: Point, options: &BlitOptions) {
+        let source_rect = options.clamped_source_rect(source);
+        let ((source_left, source_top), (source_right, source_bottom)) = source_rect;
+        let (width, height) = options.output_size(source);
+        if width == 0 || height == 0 || source_left > source_right || source_top > source_bottom {
+            return;
+        }
+        let bounds = self.device_bounds(pixel_area(pos, (pos.0 + width - 1, pos.1 + height - 1)));
+        // only the part being drawn is kept, in the same coordinates
+        if self.record(bounds, || Op::Blit { source: Box::new(source.copy_window(source_rect)), pos, options: *options }) {
+            return;
+        }
+        let draw = |buffer: &mut PixelBuffer, device: Point, u: f32, v: f32| {
+            let color = sample_blit(source, source_rect, (width, height), options.sampling, u, v);
+            let alpha = ((color.a as u32 * options.alpha as u32 + 127) / 255) as u8;
+            buffer.blend(device, color.with_alpha(alpha), options.blend_mode);
+        };
+
+        let transform = self.transform();
+        let offset = transform.integer_translation().and_then(|(dx, dy)| {
+            let x = usize::try_from(pos.0 as isize + dx).ok()?;
+            let y = usize::try_from(pos.1 as isize + dy).ok()?;
+            Some((x, y))
+        });
+        if let Some((pos_x, pos_y)) = offset {
+            // only visit destination pixels that can pass the clip
+            let ((clip_left, clip_top), (clip_right, clip_bottom)) = self.clip_rect();
+            let (x_start, y_start) = (clip_left.saturating_sub(pos_x), clip_top.saturating_sub(pos_y));
+            let x_end = width.min((clip_right + 1).saturating_sub(pos_x));
+            let y_end = height.min((clip_bottom + 1).saturating_sub(pos_y));
+            // at 1:1 each row is a straight copy, as long as blending
+            // wouldn't change anything
+            let one_to_one = (width, height) == (source_right - source_left + 1, source_bottom - source_top + 1)
+                && options.alpha == 255
+                && matches!(options.blend_mode, BlendMode::Replace | BlendMode::Over);
+            if one_to_one && x_start < x_end {
+                for y in y_start..y_end {
+                    let offset = source.offset((source_left + x_start, source_top + y));
+                    let row = &source.data[offset..offset + (x_end - x_start) * NUM_CHANNELS];
+                    let copyable = options.blend_mode == BlendMode::Replace && options.sampling == Sampling::Nearest
+                        || simd::is_opaque(row);
+                    if copyable {
+                        self.write_span(pos_y + y, pos_x + x_start, row);
+                    } else {
+                        for x in x_start..x_end {
+                            draw(self, (pos_x + x, pos_y + y), x as f32 + 0.5, y as f32 + 0.5);
+                        }
+                    }
+                }
+                return;
+            }
+            for y in y_start..y_end {
+                for x in x_start..x_end {
+                    draw(self, (pos_x + x, pos_y + y), x as f32 + 0.5, y as f32 + 0.5);
+                }
+            }
+            return;
+        }
+
+        // map each device pixel back into the blit to see what lands there
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        let Some(((left, top), (right, bottom))) = bounds else {
+            return;
+        };
+        for y in top..=bottom {
+            for x in left..=right {
+                let (local_x, local_y) = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+                let (u, v) = (local_x - pos.0 as f32, local_y - pos.1 as f32);
+                if u >= 0.0 && v >= 0.0 && u < width as f32 && v < height as f32 {
+                    draw(self, (x, y), u, v);
+                }
+            }
+        }
+    }
+
+    /// Copies the inclusive `rect` into `target` blown up `scale` times with
+    /// nearest neighbour, ignoring clipping and blending. Much cheaper than
+    /// [PixelBuffer::blit] for presenting a low resolution screen.
+    pub fn upscale_into(&self, target: &mut PixelBuffer, ((left, top), (right, bottom)): Rect, scale: usize) {
+        let right = right.min(self.width.saturating_sub(1));
+        let bottom = bottom.min(self.height.saturating_sub(1));
+        if self.width == 0 || self.height == 0 || scale == 0 || left > right || top > bottom {
+            return;
+        }
+        // exclusive bounds in the target, which may cut the edge pixels short
+        let (target_left, target_top) = (left * scale, top * scale);
+        let target_right = ((right + 1) * scale).min(target.width);
+        let target_bottom = ((bottom + 1) * scale).min(target.height);
+        if target_left >= target_right || target_top >= target_bottom {
+            return;
+        }
+
+        let mut row = vec![0u8; (target_right - target_left) * NUM_CHANNELS];
+        for y in top..=bottom {
+            for (i, pixel) in row.chunks_exact_mut(NUM_CHANNELS).enumerate() {
+                let offset = (y * self.width + left + i / scale) * NUM_CHANNELS;
+                pixel.copy_from_slice(&self.data[offset..offset + NUM_CHANNELS]);
+            }
+            for target_y in (y * scale)..((y + 1) * scale).min(target_bottom) {
+                let offset = (target_y * target.width + target_left) * NUM_CHANNELS;
+                target.data[offset..offset + row.len()].copy_from_slice(&row);
+            }
+        }
+        target.invalidate(((target_left, target_top), (target_right - 1, target_bottom - 1)));
+    }
+
+    pub fn render_text(&mut self, text: &str, pos: Point, font: &Font, color: Color, scale: usize) {
+        self.draw_text(text, pos, font, color, scale, BlendMode::Over);
+    }
+
+    /// Draws with this buffer's [TextRasterizer], so text follows any
+    /// transform already in effect
+    pub(crate) fn draw_text(&mut self, text: &str, pos: Point, font: &Font, color: Color, scale: usize, mode: BlendMode) {
+        let mut rasterizer = mem::take(&mut self.text);
+        rasterizer.draw(self, font, text, pos, color, scale, mode);
+        self.text = rasterizer;
+    }
+
+    /// [PixelBuffer::draw_text] for a laid out paragraph
+    pub(crate) fn draw_layout(&mut self, layout: &Layout, pos: Point, font: &Font, mode: BlendMode) {
+        let mut rasterizer = mem::take(&mut self.text);
+        rasterizer.draw_layout(self, font, layout, pos, mode);
+        self.text = rasterizer;
+    }
+
+    pub fn data_as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// Raw RGBA access for whole-buffer effects. Ignores the clip, and
+    /// marks everything dirty since any byte might change.
+    pub fn data_as_mut(&mut self) -> &mut [u8] {
+        self.dirty.mark_all();
+        self.data.as_mut()
+    }
+
+    /// Makes this buffer a copy of the same-sized `source`, only marking
+    /// pixels that actually change as dirty
+    pub fn copy_from(&mut self, source: &PixelBuffer) {
+        assert_eq!((source.width, source.height), (self.width, self.height), "copy between different sizes");
+        for (i, (pixel, source_pixel)) in
+            self.data.chunks_exact_mut(NUM_CHANNELS).zip(source.data.chunks_exact(NUM_CHANNELS)).enumerate()
+        {
+            if pixel != source_pixel {
+                pixel.copy_from_slice(source_pixel);
+                self.dirty.mark((i % self.width, i / self.width));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Every point that isn't transparent, row by row
+    pub fn lit(buffer: &PixelBuffer) -> Vec<Point> {
+        let mut points = vec![];
+        for y in 0..buffer.height {
+            for x in 0..buffer.width {
+                if buffer.get((x, y)) != Some(TRANSPARENT) {
+                    points.push((x, y));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_over_opaque_and_transparent() {
+        let dst = Color::new(10, 20, 30, 255);
+        assert_eq!(blend(RED, dst, BlendMode::Over), RED);
+        assert_eq!(blend(Color::new(255, 0, 0, 0), dst, BlendMode::Over), dst);
+    }
+
+    #[test]
+    fn test_over_half_alpha() {
+        let Color { r, g, b, a } = blend(Color::new(255, 255, 255, 128), Color::new(0, 0, 0, 255), BlendMode::Over);
+        assert_eq!((r, g, b, a), (128, 128, 128, 255));
+    }
+
+    #[test]
+    fn test_over_onto_transparent() {
+        assert_eq!(blend(Color::new(200, 100, 50, 128), Color::new(0, 0, 0, 0), BlendMode::Over), Color::new(200, 100, 50, 128));
+    }
+
+    #[test]
+    fn test_modes() {
+        let dst = Color::new(100, 200, 255, 255);
+        assert_eq!(blend(WHITE, dst, BlendMode::Multiply), dst);
+        assert_eq!(blend(Color::new(0, 0, 0, 255), dst, BlendMode::Screen), dst);
+        assert_eq!(blend(Color::new(200, 100, 0, 255), dst, BlendMode::Additive), Color::new(255, 255, 255, 255));
+        assert_eq!(blend(Color::new(0, 0, 0, 0), dst, BlendMode::Replace), Color::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_buffer_blend() {
+        let mut buffer = PixelBuffer::new(2, 2);
+        buffer.set((0, 0), BLUE);
+        buffer.blend((0, 0), Color::new(255, 0, 0, 128), BlendMode::Over);
+        assert_eq!(buffer.get((0, 0)), Some(Color::new(128, 0, 127, 255)));
+        // out of bounds is ignored
+        buffer.blend((5, 5), RED, BlendMode::Over);
+        assert_eq!(buffer.get((5, 5)), None);
+    }
+
+    #[test]
+    fn test_clip_stack() {
+        let mut buffer = PixelBuffer::new(10, 10);
+        buffer.push_clip(((2, 2), (6, 6)));
+        buffer.push_clip(((4, 0), (9, 5)));
+        assert_eq!(buffer.clip_rect(), ((4, 2), (6, 5)));
+        buffer.render_text("W", (0, 0), &Font::builtin(), WHITE, 2);
+        buffer.set((3, 3), WHITE);
+        buffer.pop_clip();
+        buffer.set((3, 3), RED);
+        buffer.pop_clip();
+        buffer.set((0, 0), BLUE);
+
+        assert_eq!(buffer.get((3, 3)), Some(RED));
+        assert_eq!(buffer.get((0, 0)), Some(BLUE));
+        for y in 0..10 {
+            for x in 0..10 {
+                let written = buffer.get((x, y)) == Some(WHITE);
+                if written {
+                    assert!(is_point_in_rect((x, y), ((4, 2), (6, 5))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_changes_are_dirty() {
+        let mut buffer = PixelBuffer::new(100, 100);
+        assert_eq!(buffer.take_dirty_rects(), vec![((0, 0), (99, 99))]);
+        buffer.set((10, 10), Color::new(0, 0, 0, 0));
+        assert_eq!(buffer.take_dirty_rects(), vec![]);
+        buffer.set((70, 40), WHITE);
+        assert_eq!(buffer.take_dirty_rects(), vec![((64, 32), (95, 63))]);
+        buffer.invalidate(((0, 96), (3, 99)));
+        assert_eq!(buffer.take_dirty_rects(), vec![((0, 96), (31, 99))]);
+    }
+
+    #[test]
+    fn test_blit_nearest_scaled() {
+        let mut source = PixelBuffer::new(2, 2);
+        source.set((0, 0), RED);
+        source.set((1, 1), BLUE);
+        let mut buffer = PixelBuffer::new(6, 6);
+        let options = BlitOptions { size: Some((4, 4)), ..Default::default() };
+        buffer.blit(&source, (1, 1), &options);
+        assert_eq!(buffer.get((1, 1)), Some(RED));
+        assert_eq!(buffer.get((2, 2)), Some(RED));
+        assert_eq!(buffer.get((4, 4)), Some(BLUE));
+        assert_eq!(buffer.get((3, 1)), Some(Color::new(0, 0, 0, 0)));
+        assert_eq!(buffer.get((5, 5)), Some(Color::new(0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_blit_source_rect_and_alpha() {
+        let mut source = PixelBuffer::new(4, 1);
+        source.set((2, 0), WHITE);
+        source.set((3, 0), GREEN);
+        let mut buffer = PixelBuffer::new(2, 1);
+        buffer.set((0, 0), Color::new(0, 0, 0, 255));
+        buffer.set((1, 0), Color::new(0, 0, 0, 255));
+        let options = BlitOptions { source_rect: Some(((2, 0), (3, 0))), alpha: 128, ..Default::default() };
+        buffer.blit(&source, (0, 0), &options);
+        assert_eq!(buffer.get((0, 0)), Some(Color::new(128, 128, 128, 255)));
+        assert_eq!(buffer.get((1, 0)), Some(Color::new(0, 128, 0, 255)));
+    }
+
+    #[test]
+    fn test_blit_bilinear_ignores_transparent_color() {
+        let mut source = PixelBuffer::new(2, 1);
+        source.set((0, 0), WHITE);
+        source.set((1, 0), Color::new(255, 0, 0, 0));
+        let mut buffer = PixelBuffer::new(4, 1);
+        let options = BlitOptions { size: Some((4, 1)), sampling: Sampling::Bilinear, blend_mode: BlendMode::Replace, ..Default::default() };
+        buffer.blit(&source, (0, 0), &options);
+        let Color { r, g, b, a } = buffer.get((1, 0)).unwrap();
+        assert_eq!((r, g, b), (255, 255, 255));
+        assert!(a > 0 && a < 255);
+        assert_eq!(buffer.get((0, 0)), Some(WHITE));
+    }
+
+    #[test]
+    fn test_disjoint_clip_is_empty() {
+        let mut buffer = PixelBuffer::new(10, 10);
+        buffer.push_clip(((0, 0), (2, 2)));
+        buffer.push_clip(((5, 5), (9, 9)));
+        for y in 0..10 {
+            for x in 0..10 {
+                buffer.set((x, y), WHITE);
+            }
+        }
+        assert!(buffer.data_as_ref().iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_upscale_into() {
+        let mut source = PixelBuffer::new(3, 2);
+        source.set((1, 0), RED);
+        source.set((2, 1), BLUE);
+        // the target is a pixel short of 3x on the right
+        let mut target = PixelBuffer::new(8, 6);
+        target.take_dirty_rects();
+        source.upscale_into(&mut target, ((1, 0), (2, 1)), 3);
+        for y in 0..6 {
+            for x in 0..8 {
+                let expected = match (x / 3, y / 3) {
+                    (1, 0) => RED,
+                    (2, 1) => BLUE,
+                    _ => TRANSPARENT,
+                };
+                assert_eq!(target.get((x, y)), Some(expected), "pixel ({}, {})", x, y);
+            }
+        }
+        assert_eq!(target.take_dirty_rects(), vec![((0, 0), (7, 5))]);
+    }
+
+    #[test]
+    fn test_transform_stack() {
+        let mut buffer = PixelBuffer::new(10, 10);
+        buffer.push_transform(Transform::translate(2.0, 1.0));
+        buffer.push_transform(Transform::scale(2.0, 2.0));
+        buffer.fill_rect(((0, 0), (0, 0)), WHITE, BlendMode::Over);
+        buffer.push_clip(((1, 1), (2, 2)));
+        assert_eq!(buffer.clip_rect(), ((4, 3), (7, 6)));
+        buffer.pop_clip();
+        buffer.pop_transform();
+        buffer.pop_transform();
+        assert!(buffer.transform().is_identity());
+        assert_eq!(lit(&buffer), vec![(2, 1), (3, 1), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn test_scaled_text_matches_transform() {
+        let font = Font::builtin();
+        let mut by_scale = PixelBuffer::new(12, 12);
+        by_scale.render_text("A", (1, 1), &font, WHITE, 2);
+        let mut by_transform = PixelBuffer::new(12, 12);
+        by_transform.push_transform(Transform::translate(1.0, 1.0).scaled(2.0, 2.0));
+        by_transform.render_text("A", (0, 0), &font, WHITE, 1);
+        assert!(!lit(&by_scale).is_empty());
+        assert_eq!(by_scale.data_as_ref(), by_transform.data_as_ref());
+    }
+
+    #[test]
+    fn test_blit_quarter_turn() {
+        let mut source = PixelBuffer::new(2, 1);
+        source.set((0, 0), RED);
+        source.set((1, 0), BLUE);
+        let mut buffer = PixelBuffer::new(3, 3);
+        // a clockwise turn about the blit's top left, moved back on screen
+        buffer.push_transform(Transform::translate(1.0, 0.0) * Transform::quarter_turns(1));
+        buffer.blit(&source, (0, 0), &BlitOptions::default());
+        assert_eq!(lit(&buffer), vec![(0, 0), (0, 1)]);
+        assert_eq!((buffer.get((0, 0)), buffer.get((0, 1))), (Some(RED), Some(BLUE)));
+    }
+
+    #[test]
+    fn test_rotated_fills_tile_exactly() {
+        let rotation = Transform::rotate(0.5).around((10.0, 10.0));
+        let mut halves = PixelBuffer::new(20, 20);
+        halves.push_transform(rotation);
+        halves.fill_rect(((4, 4), (9, 15)), Color::rgb(100, 0, 0), BlendMode::Additive);
+        halves.fill_rect(((10, 4), (15, 15)), Color::rgb(100, 0, 0), BlendMode::Additive);
+        let mut whole = PixelBuffer::new(20, 20);
+        whole.push_transform(rotation);
+        whole.fill_rect(((4, 4), (15, 15)), Color::rgb(100, 0, 0), BlendMode::Additive);
+        // no gaps, and nothing drawn twice
+        assert_eq!(halves.data_as_ref(), whole.data_as_ref());
+    }
+
+    /// The same picture drawn a pixel at a time
+    fn per_pixel(width: usize, height: usize, draw: impl Fn(Point) -> Option<Color>) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(width, height);
+        buffer.take_dirty_rects();
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(color) = draw((x, y)) {
+                    buffer.set((x, y), color);
+                }
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_bulk_fills_match_per_pixel() {
+        let mut bulk = PixelBuffer::new(100, 40);
+        bulk.take_dirty_rects();
+        bulk.push_clip(((3, 0), (90, 39)));
+        bulk.clear(BLUE);
+        bulk.fill_span(5, 0, 99, RED, BlendMode::Over);
+        // already red, so nothing new gets dirty
+        bulk.fill_span(5, 40, 60, RED, BlendMode::Replace);
+        bulk.pop_clip();
+        let expected = per_pixel(100, 40, |(x, y)| match (x, y) {
+            (3..=90, 5) => Some(RED),
+            (3..=90, _) => Some(BLUE),
+            _ => None,
+        });
+        assert_eq!(bulk.data_as_ref(), expected.data_as_ref());
+        let mut expected = expected;
+        assert_eq!(bulk.take_dirty_rects(), expected.take_dirty_rects());
+
+        // only the changed tile is marked
+        bulk.fill_span(5, 40, 60, WHITE, BlendMode::Replace);
+        bulk.fill_span(5, 64, 70, RED, BlendMode::Replace);
+        assert_eq!(bulk.take_dirty_rects(), vec![((32, 0), (63, 31))]);
+    }
+
+    #[test]
+    fn test_bulk_blit_matches_per_pixel() {
+        let mut source = PixelBuffer::new(50, 3);
+        for x in 0..50 {
+            source.set((x, 0), Color::rgb(x as u8, 0, 0));
+            source.set((x, 1), Color::new(0, x as u8, 0, 100));
+        }
+        let options = BlitOptions { source_rect: Some(((2, 0), (45, 2))), ..Default::default() };
+        let mut bulk = PixelBuffer::new(60, 10);
+        bulk.clear(WHITE);
+        bulk.push_clip(((10, 0), (40, 9)));
+        bulk.blit(&source, (5, 2), &options);
+        bulk.pop_clip();
+        let expected = per_pixel(60, 10, |(x, y)| {
+            let under = WHITE;
+            if !(10..=40).contains(&x) || !(2..=4).contains(&y) {
+                return Some(under);
+            }
+            Some(blend(source.get((x - 5 + 2, y - 2)).unwrap(), under, BlendMode::Over))
+        });
+        assert_eq!(bulk.data_as_ref(), expected.data_as_ref());
+    }
+}
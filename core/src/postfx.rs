@@ -0,0 +1,492 @@
+//! Frame-wide post-processing: an ordered [FilterChain] of effects run over
+//! the finished frame before upload, for a CRT look.
+//!
+//! Filters are addressed by name so their parameters can be tweaked at
+//! runtime, e.g. from JavaScript, without knowing their concrete types.
+
+use alloc::{boxed::Box, string::String, string::ToString, vec, vec::Vec};
+use crate::pixels::{PixelBuffer, NUM_CHANNELS};
+
+/// A whole-frame effect
+pub trait Filter {
+    /// Applies the effect to all of `buffer` in place
+    fn apply(&mut self, buffer: &mut PixelBuffer);
+
+    /// Sets the parameter called `name`, returning false if there isn't one
+    fn set_param(&mut self, name: &str, value: f32) -> bool;
+}
+
+/// Perceived brightness, 0-255
+fn luma(pixel: &[u8]) -> u32 {
+    (pixel[0] as u32 * 54 + pixel[1] as u32 * 183 + pixel[2] as u32 * 19) >> 8
+}
+
+/// Scales the r, g and b of one RGBA `pixel` by `factor`
+fn scale_rgb(pixel: &mut [u8], factor: f32) {
+    for channel in &mut pixel[..3] {
+        *channel = (*channel as f32 * factor + 0.5).min(255.0) as u8;
+    }
+}
+
+/// Blurs r, g and b in place with a `radius` box filter along rows, then
+/// columns. Edges repeat their outermost pixel.
+fn box_blur(data: &mut [u8], width: usize, height: usize, radius: usize) {
+    if radius == 0 || width == 0 || height == 0 {
+        return;
+    }
+    let window = (radius * 2 + 1) as u32;
+    // (number of lines, line length, step between lines, step along a line)
+    let passes = [
+        (height, width, width * NUM_CHANNELS, NUM_CHANNELS),
+        (width, height, NUM_CHANNELS, width * NUM_CHANNELS),
+    ];
+    for (lines, length, line_step, step) in passes {
+        let mut line = vec![[0u8; 3]; length];
+        for l in 0..lines {
+            let start = l * line_step;
+            for (i, pixel) in line.iter_mut().enumerate() {
+                pixel.copy_from_slice(&data[start + i * step..start + i * step + 3]);
+            }
+            let at = |i: isize| line[i.clamp(0, length as isize - 1) as usize];
+            let mut sum = [0u32; 3];
+            for i in -(radius as isize)..=radius as isize {
+                for (c, total) in sum.iter_mut().enumerate() {
+                    *total += at(i)[c] as u32;
+                }
+            }
+            for i in 0..length {
+                let offset = start + i * step;
+                for c in 0..3 {
+                    data[offset + c] = ((sum[c] + window / 2) / window) as u8;
+                }
+                let (entering, leaving) = (at((i + radius + 1) as isize), at(i as isize - radius as isize));
+                for c in 0..3 {
+                    sum[c] = sum[c] + entering[c] as u32 - leaving[c] as u32;
+                }
+            }
+        }
+    }
+}
+
+/// Darkens every `period`th row, like the gaps between a CRT's lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scanlines {
+    /// How much the dark rows lose, 0.0 to 1.0
+    pub intensity: f32,
+    /// Rows per scanline, at least 2
+    pub period: usize,
+}
+
+impl Default for Scanlines {
+    fn default() -> Self {
+        Self { intensity: 0.35, period: 2 }
+    }
+}
+
+impl Filter for Scanlines {
+    fn apply(&mut self, buffer: &mut PixelBuffer) {
+        let (width, period) = (buffer.width, self.period.max(2));
+        if width == 0 {
+            return;
+        }
+        let factor = 1.0 - self.intensity.clamp(0.0, 1.0);
+        let data = buffer.data_as_mut();
+        for (y, row) in data.chunks_exact_mut(width * NUM_CHANNELS).enumerate() {
+            if y % period == period - 1 {
+                row.chunks_exact_mut(NUM_CHANNELS).for_each(|pixel| scale_rgb(pixel, factor));
+            }
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "intensity" => self.intensity = value,
+            "period" => self.period = value as usize,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Makes bright areas glow: everything over `threshold` is blurred and
+/// added back on top
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    /// Brightness (0-255) a pixel needs to glow
+    pub threshold: u8,
+    /// Blur radius in pixels
+    pub radius: usize,
+    /// How much of the glow is added, usually 0.0 to 1.0
+    pub strength: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self { threshold: 160, radius: 3, strength: 0.6 }
+    }
+}
+
+impl Filter for Bloom {
+    fn apply(&mut self, buffer: &mut PixelBuffer) {
+        let (width, height) = (buffer.width, buffer.height);
+        // any wider just blurs everything to the same average
+        let radius = self.radius.min(width.max(height));
+        let data = buffer.data_as_mut();
+        let mut glow: Vec<u8> = data
+            .chunks_exact(NUM_CHANNELS)
+            .flat_map(|pixel| if luma(pixel) >= self.threshold as u32 { [pixel[0], pixel[1], pixel[2], 0] } else { [0; 4] })
+            .collect();
+        // three box blurs are close enough to a gaussian
+        for _ in 0..3 {
+            box_blur(&mut glow, width, height, radius);
+        }
+        for (pixel, glow) in data.chunks_exact_mut(NUM_CHANNELS).zip(glow.chunks_exact(NUM_CHANNELS)) {
+            for c in 0..3 {
+                pixel[c] = (pixel[c] as f32 + glow[c] as f32 * self.strength).clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold" => self.threshold = value.clamp(0.0, 255.0) as u8,
+            "radius" => self.radius = value as usize,
+            "strength" => self.strength = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Darkens towards the corners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    /// How dark the very corners get, 0.0 to 1.0
+    pub strength: f32,
+    /// Fraction of the way to the corners where darkening starts
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self { strength: 0.5, radius: 0.5 }
+    }
+}
+
+impl Filter for Vignette {
+    fn apply(&mut self, buffer: &mut PixelBuffer) {
+        let (width, height) = (buffer.width, buffer.height);
+        let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+        let radius = self.radius.clamp(0.0, 0.99);
+        let strength = self.strength.clamp(0.0, 1.0);
+        let data = buffer.data_as_mut();
+        for (i, pixel) in data.chunks_exact_mut(NUM_CHANNELS).enumerate() {
+            // squared distance, normalised so the corners are at 1.0
+            let dx = ((i % width) as f32 + 0.5 - center_x) / center_x;
+            let dy = ((i / width) as f32 + 0.5 - center_y) / center_y;
+            let distance = (dx * dx + dy * dy) / 2.0;
+            let falloff = ((distance - radius * radius) / (1.0 - radius * radius)).clamp(0.0, 1.0);
+            scale_rgb(pixel, 1.0 - strength * falloff);
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            "radius" => self.radius = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Pulls red and blue apart horizontally, like a badly converged tube
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChromaticOffset {
+    /// Pixels red moves left and blue moves right
+    pub offset: isize,
+}
+
+impl Filter for ChromaticOffset {
+    fn apply(&mut self, buffer: &mut PixelBuffer) {
+        let width = buffer.width as isize;
+        if self.offset == 0 || width == 0 {
+            return;
+        }
+        // parameters set from outside can be huge, and overflow `x + offset`
+        let offset = self.offset.clamp(-width, width);
+        let data = buffer.data_as_mut();
+        for row in data.chunks_exact_mut(width as usize * NUM_CHANNELS) {
+            let original = row.to_vec();
+            let sample = |x: isize, c: usize| original[x.clamp(0, width - 1) as usize * NUM_CHANNELS + c];
+            for x in 0..width {
+                let pixel = x as usize * NUM_CHANNELS;
+                row[pixel] = sample(x + offset, 0);
+                row[pixel + 2] = sample(x - offset, 2);
+            }
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "offset" => self.offset = value as isize,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Averages `size`x`size` blocks into single flat colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pixelate {
+    pub size: usize,
+}
+
+impl Default for Pixelate {
+    fn default() -> Self {
+        Self { size: 4 }
+    }
+}
+
+impl Filter for Pixelate {
+    fn apply(&mut self, buffer: &mut PixelBuffer) {
+        let (width, height) = (buffer.width, buffer.height);
+        let size = self.size.min(width.max(height));
+        if size <= 1 {
+            return;
+        }
+        let data = buffer.data_as_mut();
+        for top in (0..height).step_by(size) {
+            for left in (0..width).step_by(size) {
+                let (right, bottom) = ((left + size).min(width), (top + size).min(height));
+                let offsets = || (top..bottom).flat_map(move |y| (left..right).map(move |x| (y * width + x) * NUM_CHANNELS));
+                let mut sum = [0u32; NUM_CHANNELS];
+                for offset in offsets() {
+                    for c in 0..NUM_CHANNELS {
+                        sum[c] += data[offset + c] as u32;
+                    }
+                }
+                let count = ((right - left) * (bottom - top)) as u32;
+                let average = sum.map(|total| ((total + count / 2) / count) as u8);
+                for offset in offsets() {
+                    data[offset..offset + NUM_CHANNELS].copy_from_slice(&average);
+                }
+            }
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "size" => self.size = value as usize,
+            _ => return false,
+        }
+        true
+    }
+}
+
+struct Entry {
+    name: String,
+    enabled: bool,
+    filter: Box<dyn Filter>,
+}
+
+/// Named filters, applied in the order they were added
+#[derive(Default)]
+pub struct FilterChain {
+    entries: Vec<Entry>,
+    /// Where the filters work, so the source frame is left untouched
+    scratch: Option<PixelBuffer>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scanlines, bloom, chromatic offset and vignette, plus a pixelate
+    /// that starts disabled
+    pub fn crt() -> Self {
+        Self::new()
+            .with("pixelate", Box::new(Pixelate::default()))
+            .with("bloom", Box::new(Bloom::default()))
+            .with("chromatic", Box::new(ChromaticOffset { offset: 1 }))
+            .with("scanlines", Box::new(Scanlines::default()))
+            .with("vignette", Box::new(Vignette::default()))
+            .disabled("pixelate")
+    }
+
+    /// Adds `filter` to the end of the chain, enabled
+    pub fn with(mut self, name: &str, filter: Box<dyn Filter>) -> Self {
+        self.push(name, filter);
+        self
+    }
+
+    fn disabled(mut self, name: &str) -> Self {
+        self.set_enabled(name, false);
+        self
+    }
+
+    pub fn push(&mut self, name: &str, filter: Box<dyn Filter>) {
+        self.entries.push(Entry { name: name.to_string(), enabled: true, filter });
+    }
+
+    /// Returns false if there's no filter called `name`
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => {
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns false if there's no such filter, or it has no such parameter
+    pub fn set_param(&mut self, filter: &str, param: &str, value: f32) -> bool {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.name == filter)
+            .is_some_and(|entry| entry.filter.set_param(param, value))
+    }
+
+    /// Whether any filter would run
+    pub fn is_active(&self) -> bool {
+        self.entries.iter().any(|entry| entry.enabled)
+    }
+
+    /// Writes `source` with every enabled filter applied into `target`.
+    /// Both must be the same size; only pixels that end up different are
+    /// marked dirty in `target`.
+    pub fn process(&mut self, source: &PixelBuffer, target: &mut PixelBuffer) {
+        let scratch = match &mut self.scratch {
+            Some(scratch) if (scratch.width, scratch.height) == (source.width, source.height) => scratch,
+            _ => self.scratch.insert(PixelBuffer::new(source.width, source.height)),
+        };
+        scratch.data_as_mut().copy_from_slice(source.data_as_ref());
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            entry.filter.apply(scratch);
+        }
+        target.copy_from(scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{Color, BLACK, WHITE};
+
+    fn filled(width: usize, height: usize, color: Color) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                buffer.set((x, y), color);
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_box_blur_keeps_flat_color() {
+        let mut buffer = filled(7, 5, Color::rgb(10, 100, 200));
+        let before = buffer.data_as_ref().to_vec();
+        box_blur(buffer.data_as_mut(), 7, 5, 2);
+        assert_eq!(buffer.data_as_ref(), &before[..]);
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let mut buffer = filled(2, 4, WHITE);
+        Scanlines { intensity: 0.5, period: 2 }.apply(&mut buffer);
+        assert_eq!(buffer.get((0, 0)), Some(WHITE));
+        assert_eq!(buffer.get((1, 1)), Some(Color::rgb(128, 128, 128)));
+        assert_eq!(buffer.get((0, 3)), Some(Color::rgb(128, 128, 128)));
+    }
+
+    #[test]
+    fn test_bloom_spreads_bright_pixels_only() {
+        let mut buffer = filled(9, 9, BLACK);
+        buffer.set((4, 4), WHITE);
+        buffer.set((0, 0), Color::rgb(100, 100, 100));
+        Bloom { threshold: 200, radius: 1, strength: 1.0 }.apply(&mut buffer);
+        assert!(buffer.get((5, 4)).unwrap().r > 0);
+        assert_eq!(buffer.get((1, 0)), Some(BLACK));
+        assert_eq!(buffer.get((0, 0)), Some(Color::rgb(100, 100, 100)));
+    }
+
+    #[test]
+    fn test_vignette_darkens_corners() {
+        let mut buffer = filled(20, 20, WHITE);
+        Vignette { strength: 1.0, radius: 0.5 }.apply(&mut buffer);
+        assert_eq!(buffer.get((10, 10)), Some(WHITE));
+        let (corner, edge) = (buffer.get((0, 0)).unwrap().r, buffer.get((10, 0)).unwrap().r);
+        assert!(corner < 64 && corner < edge && edge < 255, "corner {}, edge {}", corner, edge);
+    }
+
+    #[test]
+    fn test_chromatic_offset() {
+        let mut buffer = filled(5, 1, BLACK);
+        buffer.set((2, 0), WHITE);
+        ChromaticOffset { offset: 1 }.apply(&mut buffer);
+        assert_eq!(buffer.get((1, 0)), Some(Color::rgb(255, 0, 0)));
+        assert_eq!(buffer.get((2, 0)), Some(Color::rgb(0, 255, 0)));
+        assert_eq!(buffer.get((3, 0)), Some(Color::rgb(0, 0, 255)));
+    }
+
+    #[test]
+    fn test_pixelate_averages_blocks() {
+        let mut buffer = filled(3, 2, BLACK);
+        buffer.set((0, 0), WHITE);
+        buffer.set((1, 1), WHITE);
+        Pixelate { size: 2 }.apply(&mut buffer);
+        assert_eq!(buffer.get((0, 1)), Some(Color::rgb(128, 128, 128)));
+        // the leftover column is its own, narrower block
+        assert_eq!(buffer.get((2, 0)), Some(BLACK));
+    }
+
+    #[test]
+    fn test_extreme_params() {
+        let mut empty = PixelBuffer::new(0, 3);
+        Scanlines::default().apply(&mut empty);
+
+        let mut buffer = filled(3, 2, BLACK);
+        buffer.set((0, 0), WHITE);
+        let mut chromatic = ChromaticOffset::default();
+        assert!(chromatic.set_param("offset", 1e30));
+        chromatic.apply(&mut buffer);
+        assert_eq!(buffer.get((0, 0)), Some(Color::rgb(0, 255, 255)));
+        assert_eq!(buffer.get((2, 0)), Some(Color::rgb(0, 0, 255)));
+
+        let mut bloom = Bloom::default();
+        assert!(bloom.set_param("radius", 1e30));
+        bloom.apply(&mut buffer);
+        let mut pixelate = Pixelate::default();
+        assert!(pixelate.set_param("size", 1e30));
+        pixelate.apply(&mut buffer);
+        let first = buffer.get((0, 0));
+        assert!((0..2).all(|y| (0..3).all(|x| buffer.get((x, y)) == first)));
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut chain = FilterChain::new().with("scanlines", Box::new(Scanlines::default()));
+        assert!(chain.set_param("scanlines", "intensity", 1.0));
+        assert!(!chain.set_param("scanlines", "nope", 1.0));
+        assert!(!chain.set_param("nope", "intensity", 1.0));
+
+        let source = filled(2, 2, WHITE);
+        let mut target = PixelBuffer::new(2, 2);
+        chain.process(&source, &mut target);
+        assert_eq!(target.get((0, 1)), Some(BLACK));
+        assert_eq!(source.get((0, 1)), Some(WHITE));
+
+        // unchanged output isn't dirty
+        target.take_dirty_rects();
+        chain.process(&source, &mut target);
+        assert!(target.take_dirty_rects().is_empty());
+
+        assert!(chain.set_enabled("scanlines", false));
+        assert!(!chain.is_active());
+        chain.process(&source, &mut target);
+        assert_eq!(target.get((0, 1)), Some(WHITE));
+    }
+}
@@ -0,0 +1,196 @@
+//! Where finished frames go. The app only ever hands a [Presenter] a
+//! [PixelBuffer] and the parts of it that changed, so the same code can
+//! draw to a browser canvas, to memory for tests, or to files on disk.
+
+use alloc::{vec, vec::Vec};
+use core::convert::Infallible;
+
+use crate::{
+    pixels::PixelBuffer,
+    ui::{Rect, Size},
+};
+
+pub trait Presenter {
+    type Error;
+
+    /// Called before the first frame, and whenever the frame size changes
+    fn resize(&mut self, size: Size) -> Result<(), Self::Error>;
+
+    /// Shows `frame`. Only the inclusive `dirty` rects changed since the
+    /// last call, so only they need copying out.
+    fn present(&mut self, frame: &PixelBuffer, dirty: &[Rect]) -> Result<(), Self::Error>;
+}
+
+/// Keeps the last presented frame in memory, built up only from the dirty
+/// rects it was given, exactly like a canvas would be. Useful for tests and
+/// tools.
+pub struct HeadlessPresenter {
+    frame: PixelBuffer,
+    /// How many frames have been presented
+    pub frames: usize,
+    /// The dirty rects of the most recent frame
+    pub last_dirty: Vec<Rect>,
+}
+
+impl HeadlessPresenter {
+    pub fn new() -> Self {
+        Self {
+            frame: PixelBuffer::new(0, 0),
+            frames: 0,
+            last_dirty: vec![],
+        }
+    }
+
+    /// What's on "screen"
+    pub fn frame(&self) -> &PixelBuffer {
+        &self.frame
+    }
+}
+
+impl Default for HeadlessPresenter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Presenter for HeadlessPresenter {
+    type Error = Infallible;
+
+    fn resize(&mut self, (width, height): Size) -> Result<(), Self::Error> {
+        self.frame = PixelBuffer::new(width, height);
+        Ok(())
+    }
+
+    fn present(&mut self, frame: &PixelBuffer, dirty: &[Rect]) -> Result<(), Self::Error> {
+        for &rect in dirty {
+            frame.upscale_into(&mut self.frame, rect, 1);
+        }
+        self.frames += 1;
+        self.last_dirty = dirty.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use files::{FileSequencePresenter, FrameFormat};
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod files {
+    use std::{format, fs, io, path::PathBuf};
+
+    use super::{HeadlessPresenter, Presenter};
+    use crate::{
+        image::{png, ppm},
+        pixels::PixelBuffer,
+        ui::{Rect, Size},
+    };
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum FrameFormat {
+        #[default]
+        Png,
+        /// Bigger, but trivially fast to write
+        Ppm,
+    }
+
+    impl FrameFormat {
+        fn extension(self) -> &'static str {
+            match self {
+                FrameFormat::Png => "png",
+                FrameFormat::Ppm => "ppm",
+            }
+        }
+    }
+
+    /// Writes every presented frame to `frame_00000.png`, `frame_00001.png`,
+    /// and so on in a directory
+    pub struct FileSequencePresenter {
+        dir: PathBuf,
+        format: FrameFormat,
+        screen: HeadlessPresenter,
+    }
+
+    impl FileSequencePresenter {
+        /// Creates `dir` if it doesn't exist
+        pub fn new(dir: impl Into<PathBuf>, format: FrameFormat) -> io::Result<Self> {
+            let dir = dir.into();
+            fs::create_dir_all(&dir)?;
+            Ok(Self {
+                dir,
+                format,
+                screen: HeadlessPresenter::new(),
+            })
+        }
+
+        /// Where the next frame will be written
+        pub fn next_path(&self) -> PathBuf {
+            self.dir.join(format!("frame_{:05}.{}", self.screen.frames, self.format.extension()))
+        }
+
+        /// How many frames have been written
+        pub fn frames(&self) -> usize {
+            self.screen.frames
+        }
+
+        /// The last frame written
+        pub fn frame(&self) -> &PixelBuffer {
+            self.screen.frame()
+        }
+    }
+
+    impl Presenter for FileSequencePresenter {
+        type Error = io::Error;
+
+        fn resize(&mut self, size: Size) -> io::Result<()> {
+            let Ok(()) = self.screen.resize(size);
+            Ok(())
+        }
+
+        fn present(&mut self, frame: &PixelBuffer, dirty: &[Rect]) -> io::Result<()> {
+            let path = self.next_path();
+            let Ok(()) = self.screen.present(frame, dirty);
+            let bytes = match self.format {
+                FrameFormat::Png => png::encode(self.screen.frame()),
+                FrameFormat::Ppm => ppm::encode(self.screen.frame()),
+            };
+            fs::write(path, bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{RED, TRANSPARENT, WHITE};
+
+    #[test]
+    fn test_headless_only_copies_dirty_rects() {
+        let mut frame = PixelBuffer::new(4, 4);
+        frame.set((0, 0), WHITE);
+        frame.set((3, 3), RED);
+
+        let mut presenter = HeadlessPresenter::new();
+        let Ok(()) = presenter.resize((4, 4));
+        let Ok(()) = presenter.present(&frame, &[((2, 2), (3, 3))]);
+        assert_eq!(presenter.frame().get((3, 3)), Some(RED));
+        assert_eq!(presenter.frame().get((0, 0)), Some(TRANSPARENT));
+        assert_eq!((presenter.frames, presenter.last_dirty.clone()), (1, vec![((2, 2), (3, 3))]));
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_file_sequence() {
+        let dir = std::env::temp_dir().join(std::format!("kpt_frames_{}", std::process::id()));
+        let mut presenter = FileSequencePresenter::new(&dir, FrameFormat::Png).unwrap();
+        presenter.resize((3, 2)).unwrap();
+        let mut frame = PixelBuffer::new(3, 2);
+        frame.set((1, 1), WHITE);
+        presenter.present(&frame, &[((0, 0), (2, 1))]).unwrap();
+        presenter.present(&frame, &[]).unwrap();
+
+        let second = crate::image::decode(&std::fs::read(dir.join("frame_00001.png")).unwrap()).unwrap();
+        assert_eq!(second.get((1, 1)), Some(WHITE));
+        assert_eq!(presenter.frames(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
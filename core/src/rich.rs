@@ -0,0 +1,226 @@
+//! Rich text: runs of text that each have their own [Style], built up in
+//! code or parsed from a small BBCode-like markup.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::{
+    color::ParseColorError,
+    pixels::{Color, BLACK},
+};
+
+/// How a run of text looks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub color: Color,
+    pub scale: usize,
+    /// A line along the baseline
+    pub underline: bool,
+    /// A line through the middle of capitals
+    pub strikethrough: bool,
+    /// Faux bold: every glyph drawn twice, a pixel apart
+    pub bold: bool,
+    /// Drawn behind the text, a glyph pixel down and to the right
+    pub shadow: Option<Color>,
+    /// Drawn a pixel out all the way around the text
+    pub outline: Option<Color>,
+}
+
+impl Style {
+    /// Plain text, without decorations or effects
+    pub const fn new(color: Color, scale: usize) -> Self {
+        Self { color, scale, underline: false, strikethrough: false, bold: false, shadow: None, outline: None }
+    }
+}
+
+/// Positions are byte offsets of the `[` starting the tag at fault
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkupError {
+    /// A `[` with no `]` to end it
+    UnclosedTag(usize),
+    UnknownTag(usize),
+    /// A value missing from a tag that needs one, given to a tag that
+    /// doesn't take one, or that doesn't make sense
+    InvalidValue(usize),
+    InvalidColor(usize, ParseColorError),
+    /// A closing tag for anything but the innermost open tag
+    MismatchedClose(usize),
+}
+
+impl fmt::Display for MarkupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkupError::UnclosedTag(at) => write!(f, "tag at {} is never closed with ']'", at),
+            MarkupError::UnknownTag(at) => write!(f, "unknown tag at {}", at),
+            MarkupError::InvalidValue(at) => write!(f, "invalid value for tag at {}", at),
+            MarkupError::InvalidColor(at, error) => write!(f, "tag at {}: {}", at, error),
+            MarkupError::MismatchedClose(at) => write!(f, "closing tag at {} doesn't match the open one", at),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MarkupError {}
+
+/// What's inside a tag `name`, given the style outside it
+fn apply(name: &str, value: Option<&str>, mut style: Style, at: usize) -> Result<Style, MarkupError> {
+    let color = |value: Option<&str>, default: Option<Color>| match value {
+        Some(hex) => Color::from_hex(hex).map_err(|error| MarkupError::InvalidColor(at, error)),
+        None => default.ok_or(MarkupError::InvalidValue(at)),
+    };
+    match name {
+        "color" => style.color = color(value, None)?,
+        "scale" => {
+            let scale = value.and_then(|scale| scale.parse().ok()).filter(|&scale| scale > 0);
+            style.scale = scale.ok_or(MarkupError::InvalidValue(at))?;
+        }
+        "shadow" => style.shadow = Some(color(value, Some(BLACK))?),
+        "outline" => style.outline = Some(color(value, Some(BLACK))?),
+        "u" | "s" | "b" if value.is_some() => return Err(MarkupError::InvalidValue(at)),
+        "u" => style.underline = true,
+        "s" => style.strikethrough = true,
+        "b" => style.bold = true,
+        _ => return Err(MarkupError::UnknownTag(at)),
+    }
+    Ok(style)
+}
+
+/// Text made of spans, each in its own style
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RichText {
+    spans: Vec<(String, Style)>,
+}
+
+impl RichText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All of `text` in one style
+    pub fn plain(text: String, style: Style) -> Self {
+        Self { spans: alloc::vec![(text, style)] }
+    }
+
+    /// Adds `text` on the end, in `style`
+    pub fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some((last, last_style)) if *last_style == style => last.push_str(text),
+            _ => self.spans.push((String::from(text), style)),
+        }
+    }
+
+    pub fn with_span(mut self, text: &str, style: Style) -> Self {
+        self.push(text, style);
+        self
+    }
+
+    /// In order, with neighbouring spans of the same style merged
+    pub fn spans(&self) -> &[(String, Style)] {
+        &self.spans
+    }
+
+    /// Recolors every span, keeping the rest of their styles
+    pub fn set_color(&mut self, color: Color) {
+        for (_, style) in &mut self.spans {
+            style.color = color;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.iter().all(|(text, _)| text.is_empty())
+    }
+
+    /// Parses markup like `PLAIN [color=#f80]ORANGE [b]AND BOLD[/b][/color]`
+    /// on top of `base`. Tags nest, each closing with `[/name]`:
+    ///
+    /// - `[color=#f80]`, in any form [Color::from_hex] takes
+    /// - `[scale=3]`
+    /// - `[u]` underline, `[s]` strikethrough and `[b]` bold
+    /// - `[shadow]` and `[outline]`, black unless given a color like
+    ///   `[shadow=#0008]`
+    ///
+    /// `[[` is a literal `[`. Tags still open at the end run to the end.
+    pub fn parse(markup: &str, base: Style) -> Result<Self, MarkupError> {
+        let mut text = Self::new();
+        // each open tag with the style outside it
+        let mut open: Vec<(&str, Style)> = Vec::new();
+        let mut style = base;
+        let mut rest = 0;
+        while let Some(found) = markup[rest..].find('[') {
+            let at = rest + found;
+            text.push(&markup[rest..at], style);
+            if markup[at + 1..].starts_with('[') {
+                text.push("[", style);
+                rest = at + 2;
+                continue;
+            }
+            let end = at + markup[at..].find(']').ok_or(MarkupError::UnclosedTag(at))?;
+            let tag = &markup[at + 1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                match open.pop() {
+                    Some((open_name, outside)) if open_name == name => style = outside,
+                    _ => return Err(MarkupError::MismatchedClose(at)),
+                }
+            } else {
+                let (name, value) = match tag.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (tag, None),
+                };
+                open.push((name, style));
+                style = apply(name, value, style, at)?;
+            }
+            rest = end + 1;
+        }
+        text.push(&markup[rest..], style);
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::{RED, WHITE};
+
+    #[test]
+    fn test_parse() {
+        let base = Style::new(WHITE, 2);
+        let text = RichText::parse("A [color=#f00]B [b][scale=3]C[/scale][/b][/color] [[D] [shadow]E", base).unwrap();
+        let red = Style { color: RED, ..base };
+        assert_eq!(
+            text.spans(),
+            [
+                (String::from("A "), base),
+                (String::from("B "), red),
+                (String::from("C"), Style { bold: true, scale: 3, ..red }),
+                (String::from(" [D] "), base),
+                (String::from("E"), Style { shadow: Some(BLACK), ..base }),
+            ]
+        );
+        let decorated = RichText::parse("[u][s][outline=#fff]X", base).unwrap();
+        assert_eq!(decorated.spans()[0].1, Style { underline: true, strikethrough: true, outline: Some(WHITE), ..base });
+        assert!(RichText::parse("", base).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let base = Style::new(WHITE, 1);
+        assert_eq!(RichText::parse("AB [b", base), Err(MarkupError::UnclosedTag(3)));
+        assert_eq!(RichText::parse("[i]", base), Err(MarkupError::UnknownTag(0)));
+        assert_eq!(RichText::parse("[scale=0]", base), Err(MarkupError::InvalidValue(0)));
+        assert_eq!(RichText::parse("[color]", base), Err(MarkupError::InvalidValue(0)));
+        assert_eq!(RichText::parse("[b=1]", base), Err(MarkupError::InvalidValue(0)));
+        assert_eq!(RichText::parse("[color=f00]", base), Err(MarkupError::InvalidColor(0, ParseColorError::MissingHash)));
+        assert_eq!(RichText::parse("[b][u]X[/b]", base), Err(MarkupError::MismatchedClose(7)));
+        assert_eq!(RichText::parse("X[/b]", base), Err(MarkupError::MismatchedClose(1)));
+    }
+
+    #[test]
+    fn test_push_merges_styles() {
+        let style = Style::new(WHITE, 1);
+        let text = RichText::new().with_span("A", style).with_span("", Style::new(RED, 1)).with_span("B", style);
+        assert_eq!(text.spans(), [(String::from("AB"), style)]);
+    }
+}
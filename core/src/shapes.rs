@@ -0,0 +1,677 @@
+use alloc::vec::Vec;
+use crate::{
+    gradient::{Gradient, Paint},
+    pixels::{BlendMode, Color, PixelBuffer},
+    transform::Transform,
+    ui::{Drawable, Point, Size},
+};
+
+/// Primitives are rasterized in signed space so a shape can hang off the
+/// top or left edge of the buffer.
+type SignedPoint = (isize, isize);
+
+fn signed((x, y): Point) -> SignedPoint {
+    (x as isize, y as isize)
+}
+
+fn offset((x, y): Point, (dx, dy): Point) -> Point {
+    (x + dx, y + dy)
+}
+
+/// Runs `fill` with the origin moved to `pos`, so a gradient is measured
+/// from the shape's top left rather than the buffer's
+fn fill_at(buffer: &mut PixelBuffer, (x, y): Point, fill: impl FnOnce(&mut PixelBuffer)) {
+    buffer.push_transform(Transform::translate(x as f32, y as f32));
+    fill(buffer);
+    buffer.pop_transform();
+}
+
+/// The gradient if there is one, otherwise `color`
+fn paint_of(color: Color, gradient: &Option<Gradient>) -> Paint<'_> {
+    match gradient {
+        Some(gradient) => Paint::Gradient(gradient),
+        None => Paint::Solid(color),
+    }
+}
+
+/// `color` with its alpha scaled by `coverage` (0.0-1.0), for anti-aliasing
+fn with_coverage(color: Color, coverage: f32) -> Color {
+    color.with_alpha((color.a as f32 * coverage + 0.5) as u8)
+}
+
+/// Rounds towards positive infinity without needing `std`
+fn ceil(value: f32) -> isize {
+    let truncated = value as isize;
+    if (truncated as f32) < value {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// Calls `plot` for every point on the Bresenham line between `from` and
+/// `to`, endpoints included.
+fn line_points(from: SignedPoint, to: SignedPoint, mut plot: impl FnMut(SignedPoint)) {
+    let (mut x, mut y) = from;
+    let (x1, y1) = to;
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let step_x = if x < x1 { 1 } else { -1 };
+    let step_y = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    loop {
+        plot((x, y));
+        if x == x1 && y == y1 {
+            break;
+        }
+        let err2 = 2 * err;
+        if err2 >= dy {
+            err += dy;
+            x += step_x;
+        }
+        if err2 <= dx {
+            err += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Calls `plot` for every first-quadrant point (x, y >= 0) of an ellipse
+/// outline, using the midpoint algorithm. Each point is visited once.
+fn ellipse_quadrant(rx: isize, ry: isize, mut plot: impl FnMut(isize, isize)) {
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let (mut x, mut y) = (0, ry);
+    let (mut px, mut py) = (0, 2 * rx2 * y);
+
+    // region 1: slope shallower than -1, step x
+    let mut p = ry2 - rx2 * ry + rx2 / 4;
+    while px < py {
+        plot(x, y);
+        x += 1;
+        px += 2 * ry2;
+        if p < 0 {
+            p += ry2 + px;
+        } else {
+            y -= 1;
+            py -= 2 * rx2;
+            p += ry2 + px - py;
+        }
+    }
+
+    // region 2: slope steeper than -1, step y
+    p = ry2 * (x * x + x) + ry2 / 4 + rx2 * (y - 1) * (y - 1) - rx2 * ry2;
+    while y >= 0 {
+        plot(x, y);
+        y -= 1;
+        py -= 2 * rx2;
+        if p > 0 {
+            p += rx2 - py;
+        } else {
+            x += 1;
+            px += 2 * ry2;
+            p += rx2 - py + px;
+        }
+    }
+}
+
+/// Half the width of a filled ellipse's scanline `dy` rows from its center
+fn ellipse_half_width(rx: isize, ry: isize, dy: isize) -> isize {
+    // radii are padded by half a pixel so edges aren't flattened
+    let rx2 = (rx * rx + rx) as usize;
+    let ry2 = (ry * ry + ry) as usize;
+    let dy2 = (dy * dy) as usize;
+    if dy2 > ry2 {
+        return -1;
+    }
+    (rx2 * (ry2 - dy2) / ry2).isqrt() as isize
+}
+
+impl PixelBuffer {
+    /// Shapes are rasterized in local pixels, and each one is drawn as a
+    /// (transformed) square
+    fn plot(&mut self, (x, y): SignedPoint, color: Color, mode: BlendMode) {
+        if self.transform().is_identity() {
+            if x >= 0 && y >= 0 {
+                self.blend((x as usize, y as usize), color, mode);
+            }
+            return;
+        }
+        self.fill_area((x as f32, y as f32, x as f32 + 1.0, y as f32 + 1.0), color.into(), mode);
+    }
+
+    /// Inclusive horizontal run from `x0` to `x1`
+    fn hspan(&mut self, y: isize, x0: isize, x1: isize, paint: Paint, mode: BlendMode) {
+        if x1 < x0 {
+            return;
+        }
+        self.fill_area((x0 as f32, y as f32, x1 as f32 + 1.0, y as f32 + 1.0), paint, mode);
+    }
+
+    /// Aliased line using Bresenham's algorithm. Both endpoints are drawn.
+    pub fn draw_line(&mut self, from: Point, to: Point, color: Color, mode: BlendMode) {
+        line_points(signed(from), signed(to), |point| self.plot(point, color, mode));
+    }
+
+    /// Anti-aliased line using Xiaolin Wu's algorithm. Coverage is applied
+    /// through the color's alpha, so `mode` should respect alpha.
+    pub fn draw_line_aa(&mut self, from: Point, to: Point, color: Color, mode: BlendMode) {
+        let (mut x0, mut y0) = signed(from);
+        let (mut x1, mut y1) = signed(to);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            (x0, y0, x1, y1) = (y0, x0, y1, x1);
+        }
+        if x0 > x1 {
+            (x0, y0, x1, y1) = (x1, y1, x0, y0);
+        }
+
+        let dx = (x1 - x0) as f32;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) as f32 / dx };
+        let mut intersect_y = y0 as f32;
+        for x in x0..=x1 {
+            // truncation is only a floor for positive values
+            let y = if intersect_y < 0.0 { intersect_y as isize - 1 } else { intersect_y as isize };
+            let fraction = intersect_y - y as f32;
+            let (near, far) = if steep { ((y, x), (y + 1, x)) } else { ((x, y), (x, y + 1)) };
+            self.plot(near, with_coverage(color, 1.0 - fraction), mode);
+            if fraction > 0.0 {
+                self.plot(far, with_coverage(color, fraction), mode);
+            }
+            intersect_y += gradient;
+        }
+    }
+
+    pub fn draw_ellipse(&mut self, center: Point, (rx, ry): Size, color: Color, mode: BlendMode) {
+        let (cx, cy) = signed(center);
+        let (rx, ry) = (rx as isize, ry as isize);
+        if rx == 0 || ry == 0 {
+            let from = ((cx - rx).max(0) as usize, (cy - ry).max(0) as usize);
+            let to = ((cx + rx) as usize, (cy + ry) as usize);
+            self.draw_line(from, to, color, mode);
+            return;
+        }
+
+        ellipse_quadrant(rx, ry, |x, y| {
+            // mirror into the other quadrants without plotting an axis point twice
+            self.plot((cx + x, cy + y), color, mode);
+            if x != 0 {
+                self.plot((cx - x, cy + y), color, mode);
+            }
+            if y != 0 {
+                self.plot((cx + x, cy - y), color, mode);
+            }
+            if x != 0 && y != 0 {
+                self.plot((cx - x, cy - y), color, mode);
+            }
+        });
+    }
+
+    pub fn fill_ellipse<'a>(&mut self, center: Point, (rx, ry): Size, paint: impl Into<Paint<'a>>, mode: BlendMode) {
+        let paint = paint.into();
+        let (cx, cy) = signed(center);
+        let (rx, ry) = (rx as isize, ry as isize);
+        for dy in -ry..=ry {
+            let half = ellipse_half_width(rx, ry, dy);
+            if half >= 0 {
+                self.hspan(cy + dy, cx - half, cx + half, paint, mode);
+            }
+        }
+    }
+
+    pub fn draw_circle(&mut self, center: Point, radius: usize, color: Color, mode: BlendMode) {
+        self.draw_ellipse(center, (radius, radius), color, mode);
+    }
+
+    pub fn fill_circle<'a>(&mut self, center: Point, radius: usize, paint: impl Into<Paint<'a>>, mode: BlendMode) {
+        self.fill_ellipse(center, (radius, radius), paint, mode);
+    }
+
+    /// Closed outline through `points`
+    pub fn draw_polygon(&mut self, points: &[Point], color: Color, mode: BlendMode) {
+        if let [point] = points {
+            self.plot(signed(*point), color, mode);
+            return;
+        }
+        for (i, &from) in points.iter().enumerate() {
+            let to = signed(points[(i + 1) % points.len()]);
+            // each vertex is left to the edge that starts there, so
+            // translucent outlines don't get darker corners
+            line_points(signed(from), to, |point| {
+                if point != to {
+                    self.plot(point, color, mode);
+                }
+            });
+        }
+    }
+
+    /// Even-odd scanline fill. Pixels are inside when their center is.
+    pub fn fill_polygon<'a>(&mut self, points: &[Point], paint: impl Into<Paint<'a>>, mode: BlendMode) {
+        let paint = paint.into();
+        if points.len() < 3 {
+            return;
+        }
+        let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+        let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+
+        let mut crossings: Vec<f32> = Vec::with_capacity(points.len());
+        for y in min_y..max_y {
+            let scan_y = y as f32 + 0.5;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                let (x0, y0, x1, y1) = (x0 as f32, y0 as f32, x1 as f32, y1 as f32);
+                if (y0 <= scan_y && scan_y < y1) || (y1 <= scan_y && scan_y < y0) {
+                    crossings.push(x0 + (scan_y - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+            for pair in crossings.chunks_exact(2) {
+                let start = ceil(pair[0] - 0.5);
+                let end = ceil(pair[1] - 0.5) - 1;
+                if start <= end {
+                    self.hspan(y as isize, start, end, paint, mode);
+                }
+            }
+        }
+    }
+
+    pub fn draw_rounded_rect(&mut self, pos: Point, (width, height): Size, radius: usize, color: Color, mode: BlendMode) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let radius = radius.min((width - 1) / 2).min((height - 1) / 2) as isize;
+        let (left, top) = signed(pos);
+        let (right, bottom) = (left + width as isize - 1, top + height as isize - 1);
+        let (inner_left, inner_right) = (left + radius, right - radius);
+        let (inner_top, inner_bottom) = (top + radius, bottom - radius);
+
+        if radius == 0 {
+            self.hspan(top, left, right, color.into(), mode);
+            if bottom != top {
+                self.hspan(bottom, left, right, color.into(), mode);
+            }
+            for y in (top + 1)..bottom {
+                self.plot((left, y), color, mode);
+                if right != left {
+                    self.plot((right, y), color, mode);
+                }
+            }
+            return;
+        }
+
+        // straight edges, not including the points the corner arcs draw
+        self.hspan(top, inner_left + 1, inner_right - 1, color.into(), mode);
+        self.hspan(bottom, inner_left + 1, inner_right - 1, color.into(), mode);
+        for y in (inner_top + 1)..inner_bottom {
+            self.plot((left, y), color, mode);
+            self.plot((right, y), color, mode);
+        }
+
+        // at the biggest radius an odd side has no straight part, and the
+        // mirrored arcs meet on the same pixels, which mustn't blend twice
+        let (meet_x, meet_y) = (inner_left == inner_right, inner_top == inner_bottom);
+        ellipse_quadrant(radius, radius, |x, y| {
+            let (mirror_x, mirror_y) = (!(meet_x && x == 0), !(meet_y && y == 0));
+            self.plot((inner_right + x, inner_top - y), color, mode);
+            if mirror_x {
+                self.plot((inner_left - x, inner_top - y), color, mode);
+            }
+            if mirror_y {
+                self.plot((inner_right + x, inner_bottom + y), color, mode);
+                if mirror_x {
+                    self.plot((inner_left - x, inner_bottom + y), color, mode);
+                }
+            }
+        });
+    }
+
+    pub fn fill_rounded_rect<'a>(&mut self, pos: Point, (width, height): Size, radius: usize, paint: impl Into<Paint<'a>>, mode: BlendMode) {
+        let paint = paint.into();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let radius = radius.min((width - 1) / 2).min((height - 1) / 2) as isize;
+        let (left, top) = signed(pos);
+        let (width, height) = (width as isize, height as isize);
+
+        for row in 0..height {
+            let dy = if row < radius {
+                radius - row
+            } else if row > height - 1 - radius {
+                row - (height - 1 - radius)
+            } else {
+                0
+            };
+            let inset = if dy > 0 { radius - ellipse_half_width(radius, radius, dy) } else { 0 };
+            self.hspan(top + row, left + inset, left + width - 1 - inset, paint, mode);
+        }
+    }
+}
+
+pub struct Line {
+    from: Point,
+    to: Point,
+    color: Color,
+    anti_aliased: bool,
+    blend_mode: BlendMode,
+}
+impl Line {
+    /// `from` and `to` are relative to wherever the line is drawn
+    pub fn new(from: Point, to: Point, color: Color) -> Self {
+        Self {
+            from,
+            to,
+            color,
+            anti_aliased: false,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    pub fn anti_aliased(mut self) -> Self {
+        self.anti_aliased = true;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for Line {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        let (from, to) = (offset(pos, self.from), offset(pos, self.to));
+        if self.anti_aliased {
+            buffer.draw_line_aa(from, to, self.color, self.blend_mode);
+        } else {
+            buffer.draw_line(from, to, self.color, self.blend_mode);
+        }
+    }
+
+    fn get_render_size(&self) -> Size {
+        (
+            self.from.0.max(self.to.0) + 1,
+            self.from.1.max(self.to.1) + 1,
+        )
+    }
+}
+
+/// Drawn with its bounding box's top left at the draw position
+pub struct Ellipse {
+    radii: Size,
+    color: Color,
+    gradient: Option<Gradient>,
+    filled: bool,
+    blend_mode: BlendMode,
+}
+impl Ellipse {
+    pub fn new(radii: Size, color: Color) -> Self {
+        Self {
+            radii,
+            color,
+            gradient: None,
+            filled: false,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    pub fn filled(radii: Size, color: Color) -> Self {
+        Self { filled: true, ..Self::new(radii, color) }
+    }
+
+    /// Fills with `gradient` instead of the color, measured from the
+    /// shape's top left. Outlines ignore it.
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for Ellipse {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        let center = offset(pos, self.radii);
+        if self.filled {
+            let radii = self.radii;
+            let paint = paint_of(self.color, &self.gradient);
+            fill_at(buffer, pos, |buffer| buffer.fill_ellipse(radii, radii, paint, self.blend_mode));
+        } else {
+            buffer.draw_ellipse(center, self.radii, self.color, self.blend_mode);
+        }
+    }
+
+    fn get_render_size(&self) -> Size {
+        (self.radii.0 * 2 + 1, self.radii.1 * 2 + 1)
+    }
+}
+
+pub struct Circle {
+    ellipse: Ellipse,
+}
+impl Circle {
+    pub fn new(radius: usize, color: Color) -> Self {
+        Self { ellipse: Ellipse::new((radius, radius), color) }
+    }
+
+    pub fn filled(radius: usize, color: Color) -> Self {
+        Self { ellipse: Ellipse::filled((radius, radius), color) }
+    }
+
+    /// Fills with `gradient` instead of the color, measured from the
+    /// shape's top left. Outlines ignore it.
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.ellipse.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.ellipse.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for Circle {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        self.ellipse.draw(buffer, pos);
+    }
+
+    fn get_render_size(&self) -> Size {
+        self.ellipse.get_render_size()
+    }
+}
+
+pub struct Polygon {
+    points: Vec<Point>,
+    color: Color,
+    gradient: Option<Gradient>,
+    filled: bool,
+    blend_mode: BlendMode,
+}
+impl Polygon {
+    /// `points` are relative to wherever the polygon is drawn
+    pub fn new(points: Vec<Point>, color: Color) -> Self {
+        Self {
+            points,
+            color,
+            gradient: None,
+            filled: false,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    pub fn filled(points: Vec<Point>, color: Color) -> Self {
+        Self { filled: true, ..Self::new(points, color) }
+    }
+
+    /// Fills with `gradient` instead of the color, measured from the
+    /// shape's top left. Outlines ignore it.
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for Polygon {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        if self.filled {
+            let paint = paint_of(self.color, &self.gradient);
+            fill_at(buffer, pos, |buffer| buffer.fill_polygon(&self.points, paint, self.blend_mode));
+        } else {
+            let points: Vec<Point> = self.points.iter().map(|&p| offset(pos, p)).collect();
+            buffer.draw_polygon(&points, self.color, self.blend_mode);
+        }
+    }
+
+    fn get_render_size(&self) -> Size {
+        let width = self.points.iter().map(|p| p.0 + 1).max().unwrap_or(0);
+        let height = self.points.iter().map(|p| p.1 + 1).max().unwrap_or(0);
+        (width, height)
+    }
+}
+
+pub struct RoundedRectangle {
+    size: Size,
+    radius: usize,
+    color: Color,
+    gradient: Option<Gradient>,
+    filled: bool,
+    blend_mode: BlendMode,
+}
+impl RoundedRectangle {
+    pub fn new(size: Size, radius: usize, color: Color) -> Self {
+        Self {
+            size,
+            radius,
+            color,
+            gradient: None,
+            filled: false,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    pub fn filled(size: Size, radius: usize, color: Color) -> Self {
+        Self { filled: true, ..Self::new(size, radius, color) }
+    }
+
+    /// Fills with `gradient` instead of the color, measured from the
+    /// shape's top left. Outlines ignore it.
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for RoundedRectangle {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        if self.filled {
+            let paint = paint_of(self.color, &self.gradient);
+            fill_at(buffer, pos, |buffer| buffer.fill_rounded_rect((0, 0), self.size, self.radius, paint, self.blend_mode));
+        } else {
+            buffer.draw_rounded_rect(pos, self.size, self.radius, self.color, self.blend_mode);
+        }
+    }
+
+    fn get_render_size(&self) -> Size {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::pixels::{tests::lit, TRANSPARENT, WHITE};
+
+    #[test]
+    fn test_line_endpoints() {
+        let mut buffer = PixelBuffer::new(8, 8);
+        buffer.draw_line((6, 1), (1, 4), WHITE, BlendMode::Replace);
+        assert_eq!(buffer.get((6, 1)), Some(WHITE));
+        assert_eq!(buffer.get((1, 4)), Some(WHITE));
+        assert_eq!(lit(&buffer).len(), 6);
+    }
+
+    #[test]
+    fn test_aa_line_coverage_sums() {
+        let mut buffer = PixelBuffer::new(8, 8);
+        buffer.draw_line_aa((0, 0), (6, 3), WHITE, BlendMode::Replace);
+        for x in 0..=6 {
+            let column: u32 = (0..8).map(|y| buffer.get((x, y)).unwrap().a as u32).sum();
+            assert!((254..=256).contains(&column), "column {} had {}", x, column);
+        }
+    }
+
+    #[test]
+    fn test_circle_is_symmetric() {
+        let mut buffer = PixelBuffer::new(11, 11);
+        buffer.draw_circle((5, 5), 4, WHITE, BlendMode::Replace);
+        for (x, y) in lit(&buffer) {
+            assert_eq!(buffer.get((10 - x, y)), Some(WHITE));
+            assert_eq!(buffer.get((y, x)), Some(WHITE));
+        }
+        assert_eq!(buffer.get((5, 1)), Some(WHITE));
+        assert_eq!(buffer.get((5, 5)), Some(TRANSPARENT));
+    }
+
+    #[test]
+    fn test_translucent_outline_has_no_overlaps() {
+        let mut ellipse = PixelBuffer::new(12, 12);
+        ellipse.draw_ellipse((6, 6), (5, 3), Color::new(255, 255, 255, 100), BlendMode::Over);
+        let mut triangle = PixelBuffer::new(12, 12);
+        triangle.draw_polygon(&[(0, 0), (4, 0), (4, 4)], Color::new(255, 255, 255, 100), BlendMode::Over);
+        for buffer in [ellipse, triangle] {
+            for point in lit(&buffer) {
+                assert_eq!(buffer.get(point).unwrap().a, 100);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_polygon_square() {
+        let mut buffer = PixelBuffer::new(8, 8);
+        buffer.fill_polygon(&[(1, 1), (5, 1), (5, 4), (1, 4)], WHITE, BlendMode::Replace);
+        assert_eq!(lit(&buffer).len(), 4 * 3);
+        assert_eq!(buffer.get((1, 1)), Some(WHITE));
+        assert_eq!(buffer.get((4, 3)), Some(WHITE));
+        assert_eq!(buffer.get((5, 1)), Some(TRANSPARENT));
+    }
+
+    #[test]
+    fn test_rounded_rect_cuts_corners() {
+        let mut buffer = PixelBuffer::new(10, 10);
+        buffer.fill_rounded_rect((0, 0), (10, 8), 3, WHITE, BlendMode::Replace);
+        assert_eq!(buffer.get((0, 0)), Some(TRANSPARENT));
+        assert_eq!(buffer.get((9, 7)), Some(TRANSPARENT));
+        assert_eq!(buffer.get((0, 4)), Some(WHITE));
+        assert_eq!(buffer.get((5, 0)), Some(WHITE));
+        assert_eq!(buffer.get((5, 8)), Some(TRANSPARENT));
+    }
+
+    #[test]
+    fn test_translucent_pill_outline_is_even() {
+        // odd sides at the biggest radius, where the arcs meet
+        let translucent = Color::new(255, 255, 255, 128);
+        let mut buffer = PixelBuffer::new(12, 8);
+        buffer.draw_rounded_rect((0, 0), (11, 7), 10, translucent, BlendMode::Over);
+        let points = lit(&buffer);
+        assert!(points.contains(&(5, 0)) && points.contains(&(0, 3)));
+        assert!(points.iter().all(|&point| buffer.get(point) == Some(translucent)));
+    }
+
+    #[test]
+    fn test_drawables_report_size() {
+        assert_eq!(Circle::filled(3, WHITE).get_render_size(), (7, 7));
+        assert_eq!(Line::new((4, 0), (0, 2), WHITE).get_render_size(), (5, 3));
+        assert_eq!(Polygon::filled(vec![(0, 0), (6, 1), (2, 9)], WHITE).get_render_size(), (7, 10));
+    }
+}
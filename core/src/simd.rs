@@ -0,0 +1,147 @@
+//! Bulk operations on runs of RGBA bytes, for the hot paths in
+//! [PixelBuffer](crate::pixels::PixelBuffer). Uses wasm `simd128` when the
+//! build enables it (`-C target-feature=+simd128`), otherwise plain loops,
+//! which native compilers vectorize well enough on their own.
+//!
+//! Every write reports whether it changed anything, so callers can keep
+//! dirty tracking exact without a second pass.
+
+pub(crate) use imp::{copy, fill, is_opaque};
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod imp {
+    use core::arch::wasm32::*;
+
+    const LANE_BYTES: usize = 16;
+
+    /// Sets every pixel in `row` to `pixel`
+    pub(crate) fn fill(row: &mut [u8], pixel: [u8; 4]) -> bool {
+        let splat = u32x4_splat(u32::from_ne_bytes(pixel));
+        let mut changed = u32x4_splat(0);
+        let mut chunks = row.chunks_exact_mut(LANE_BYTES);
+        for chunk in &mut chunks {
+            let ptr = chunk.as_mut_ptr() as *mut v128;
+            // SAFETY: the chunk is exactly 16 bytes, and wasm loads and
+            // stores don't need alignment
+            unsafe {
+                changed = v128_or(changed, v128_xor(v128_load(ptr), splat));
+                v128_store(ptr, splat);
+            }
+        }
+        let mut any = v128_any_true(changed);
+        for existing in chunks.into_remainder().chunks_exact_mut(4) {
+            any |= existing != pixel;
+            existing.copy_from_slice(&pixel);
+        }
+        any
+    }
+
+    /// Overwrites `row` with the same-length `source`
+    pub(crate) fn copy(row: &mut [u8], source: &[u8]) -> bool {
+        debug_assert_eq!(row.len(), source.len());
+        let mut changed = u32x4_splat(0);
+        let mut chunks = row.chunks_exact_mut(LANE_BYTES);
+        let mut source_chunks = source.chunks_exact(LANE_BYTES);
+        for (chunk, source_chunk) in (&mut chunks).zip(&mut source_chunks) {
+            let (ptr, source_ptr) = (chunk.as_mut_ptr() as *mut v128, source_chunk.as_ptr() as *const v128);
+            // SAFETY: both chunks are exactly 16 bytes, and wasm loads and
+            // stores don't need alignment
+            unsafe {
+                let value = v128_load(source_ptr);
+                changed = v128_or(changed, v128_xor(v128_load(ptr), value));
+                v128_store(ptr, value);
+            }
+        }
+        let (rest, source_rest) = (chunks.into_remainder(), source_chunks.remainder());
+        let any = v128_any_true(changed) || rest != source_rest;
+        rest.copy_from_slice(source_rest);
+        any
+    }
+
+    /// Whether every pixel in `row` has full alpha
+    pub(crate) fn is_opaque(row: &[u8]) -> bool {
+        let alpha = u32x4_splat(u32::from_ne_bytes([0, 0, 0, 255]));
+        let mut chunks = row.chunks_exact(LANE_BYTES);
+        for chunk in &mut chunks {
+            // SAFETY: the chunk is exactly 16 bytes, and wasm loads don't
+            // need alignment
+            let value = unsafe { v128_load(chunk.as_ptr() as *const v128) };
+            if !u32x4_all_true(u32x4_eq(v128_and(value, alpha), alpha)) {
+                return false;
+            }
+        }
+        chunks.remainder().chunks_exact(4).all(|pixel| pixel[3] == 255)
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+mod imp {
+    /// Pixels compared and written at once by [fill]
+    const BLOCK_PIXELS: usize = 32;
+
+    /// Sets every pixel in `row` to `pixel`
+    pub(crate) fn fill(row: &mut [u8], pixel: [u8; 4]) -> bool {
+        // whole blocks go through memcmp and memcpy, which are fast however
+        // the crate is optimized
+        let block = [pixel; BLOCK_PIXELS];
+        let block = block.as_flattened();
+        let mut chunks = row.chunks_exact_mut(block.len());
+        let mut changed = false;
+        for chunk in &mut chunks {
+            changed |= copy(chunk, block);
+        }
+        let rest = chunks.into_remainder();
+        changed | copy(rest, &block[..rest.len()])
+    }
+
+    /// Overwrites `row` with the same-length `source`
+    pub(crate) fn copy(row: &mut [u8], source: &[u8]) -> bool {
+        if row == source {
+            return false;
+        }
+        row.copy_from_slice(source);
+        true
+    }
+
+    /// Whether every pixel in `row` has full alpha
+    pub(crate) fn is_opaque(row: &[u8]) -> bool {
+        row.chunks_exact(4).all(|pixel| pixel[3] == 255)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_fill() {
+        // long enough for whole lanes and a remainder
+        let mut row = vec![0u8; 4 * 7];
+        assert!(fill(&mut row, [1, 2, 3, 4]));
+        assert!(row.chunks_exact(4).all(|pixel| pixel == [1, 2, 3, 4]));
+        assert!(!fill(&mut row, [1, 2, 3, 4]));
+        row[25] = 0;
+        assert!(fill(&mut row, [1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_copy() {
+        let source: vec::Vec<u8> = (0..4 * 7).collect();
+        let mut row = vec![0u8; 4 * 7];
+        assert!(copy(&mut row, &source));
+        assert_eq!(row, source);
+        assert!(!copy(&mut row, &source));
+    }
+
+    #[test]
+    fn test_is_opaque() {
+        let mut row = vec![255u8; 4 * 7];
+        assert!(is_opaque(&row));
+        row[4 * 6 + 3] = 254;
+        assert!(!is_opaque(&row));
+        row[4 * 6 + 3] = 255;
+        row[3] = 0;
+        assert!(!is_opaque(&row));
+    }
+}
@@ -0,0 +1,285 @@
+//! Text rasterization. Glyphs are expanded once per font and scale into
+//! runs of set pixels, cached, and drawn a span at a time, along with any
+//! decorations and effects rich text has.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    bitset::Bitset,
+    font::Font,
+    paragraph::Layout,
+    pixels::{BlendMode, Color, PixelBuffer},
+    rich::Style,
+    transform::Transform,
+    ui::{Point, Rect, Size},
+};
+
+/// Turns a glyph's bitmap into one rect per horizontal run of set pixels,
+/// each `scale` pixels tall
+fn expand(bitmap: &Bitset, (width, height): Size, scale: usize) -> Vec<Rect> {
+    let mut runs = Vec::new();
+    for glyph_y in 0..height {
+        let mut glyph_x = 0;
+        while glyph_x < width {
+            if !bitmap.get(glyph_y * width + glyph_x) {
+                glyph_x += 1;
+                continue;
+            }
+            let start = glyph_x;
+            while glyph_x < width && bitmap.get(glyph_y * width + glyph_x) {
+                glyph_x += 1;
+            }
+            runs.push((
+                (start * scale, glyph_y * scale),
+                (glyph_x * scale - 1, (glyph_y + 1) * scale - 1),
+            ));
+        }
+    }
+    runs
+}
+
+/// Draws text, remembering every glyph it has expanded so each (font,
+/// glyph, scale) is only worked out once
+#[derive(Default)]
+pub struct TextRasterizer {
+    glyphs: BTreeMap<(usize, char, usize), Vec<Rect>>,
+}
+
+impl TextRasterizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many expanded glyphs are cached
+    pub fn cached(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    /// Draws `text` in `font` with its top left at `pos`, under the
+    /// buffer's clip and transform
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&mut self, buffer: &mut PixelBuffer, font: &Font, text: &str, (x, y): Point, color: Color, scale: usize, mode: BlendMode) {
+        if scale == 0 {
+            return;
+        }
+        for (c, glyph_x) in font.layout(text, scale) {
+            self.draw_glyph(buffer, font, c, (x as isize + glyph_x, y as isize), scale, color, mode);
+        }
+    }
+
+    /// Draws a laid out paragraph in `font` with its top left at `pos`.
+    /// Every shadow goes down first, then every outline, then the text, so
+    /// no glyph's effects cover its neighbours.
+    pub fn draw_layout(&mut self, buffer: &mut PixelBuffer, font: &Font, layout: &Layout, (x, y): Point, mode: BlendMode) {
+        let (x, y) = (x as isize, y as isize);
+        for layer in [Layer::Shadow, Layer::Outline, Layer::Fill] {
+            for glyph in layout.glyphs() {
+                let style = &layout.styles()[glyph.style];
+                let pos = (x + glyph.pos.0, y + glyph.pos.1);
+                layer.paint(style, |(dx, dy), color| {
+                    self.draw_glyph(buffer, font, glyph.c, (pos.0 + dx, pos.1 + dy), style.scale, color, mode);
+                });
+            }
+            for decoration in layout.decorations() {
+                let style = &layout.styles()[decoration.style];
+                let (width, height) = decoration.size;
+                let line = [((0, 0), (width - 1, height - 1))];
+                let pos = (x + decoration.pos.0, y + decoration.pos.1);
+                layer.paint(style, |(dx, dy), color| draw_runs(buffer, &line, (pos.0 + dx, pos.1 + dy), color, mode));
+            }
+        }
+    }
+
+    /// Draws `c`'s glyph with its cell's top left at `pos`
+    #[allow(clippy::too_many_arguments)]
+    fn draw_glyph(&mut self, buffer: &mut PixelBuffer, font: &Font, c: char, pos: (isize, isize), scale: usize, color: Color, mode: BlendMode) {
+        if scale == 0 {
+            return;
+        }
+        let runs = self.glyphs.entry((font.id(), c, scale)).or_insert_with(|| expand(font.glyph(c), font.cell_size(), scale));
+        draw_runs(buffer, runs, pos, color, mode);
+    }
+}
+
+/// Draws rects of pixels relative to `(left, top)`, under the buffer's
+/// clip and transform
+fn draw_runs(buffer: &mut PixelBuffer, runs: &[Rect], (left, top): (isize, isize), color: Color, mode: BlendMode) {
+    // whole-pixel offsets are the common case, and can skip the transform
+    // entirely
+    match buffer.transform().integer_translation() {
+        Some((dx, dy)) => {
+            let (left, top) = (left + dx, top + dy);
+            for &((x0, y0), (x1, y1)) in runs {
+                let (x0, x1) = (left + x0 as isize, left + x1 as isize);
+                if x1 < 0 {
+                    continue;
+                }
+                for row in (top + y0 as isize).max(0)..=top + y1 as isize {
+                    buffer.fill_span(row as usize, x0.max(0) as usize, x1 as usize, color, mode);
+                }
+            }
+        }
+        None => {
+            buffer.push_transform(Transform::translate(left as f32, top as f32));
+            for &run in runs {
+                buffer.fill_rect(run, color, mode);
+            }
+            buffer.pop_transform();
+        }
+    }
+}
+
+/// A pixel out in every direction
+const OUTLINE: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Rich text is drawn in layers, back to front
+#[derive(Clone, Copy)]
+enum Layer {
+    Shadow,
+    Outline,
+    Fill,
+}
+
+impl Layer {
+    /// Calls `draw` with each offset from the text that this layer draws
+    /// `style` at, and the color to draw it in. Bold text is drawn twice,
+    /// a pixel apart, in every layer.
+    fn paint(self, style: &Style, mut draw: impl FnMut((isize, isize), Color)) {
+        let (color, offsets, distance): (_, &[(isize, isize)], _) = match self {
+            Layer::Shadow => (style.shadow, &[(1, 1)], style.scale as isize),
+            Layer::Outline => (style.outline, &OUTLINE, 1),
+            Layer::Fill => (Some(style.color), &[(0, 0)], 0),
+        };
+        let color = match color {
+            Some(color) => color,
+            None => return,
+        };
+        for &(dx, dy) in offsets {
+            for bold in 0..=style.bold as isize {
+                draw((dx * distance + bold, dy * distance), color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        paragraph::Paragraph,
+        pixels::{BLUE, RED, TRANSPARENT, WHITE},
+        rich::RichText,
+    };
+    use alloc::{string::String, vec};
+
+    /// The glyph's bitmap drawn a pixel at a time, the way text used to be
+    fn draw_per_pixel(buffer: &mut PixelBuffer, font: &Font, c: char, pos: Point, scale: usize) {
+        let (width, height) = font.cell_size();
+        buffer.push_transform(Transform::translate(pos.0 as f32, pos.1 as f32).scaled(scale as f32, scale as f32));
+        for glyph_y in 0..height {
+            for glyph_x in 0..width {
+                if font.glyph(c).get(glyph_y * width + glyph_x) {
+                    buffer.fill_rect(((glyph_x, glyph_y), (glyph_x, glyph_y)), WHITE, BlendMode::Over);
+                }
+            }
+        }
+        buffer.pop_transform();
+    }
+
+    #[test]
+    fn test_matches_per_pixel() {
+        let font = Font::builtin().with_monospace(true);
+        for transform in [Transform::IDENTITY, Transform::translate(-3.0, 2.0), Transform::rotate(0.3).translated(10.0, 0.0)] {
+            let mut expected = PixelBuffer::new(60, 40);
+            let mut actual = PixelBuffer::new(60, 40);
+            expected.push_transform(transform);
+            actual.push_transform(transform);
+            for (i, c) in "WK0".chars().enumerate() {
+                draw_per_pixel(&mut expected, &font, c, (1 + i * font.advance(3), 4), 3);
+            }
+            TextRasterizer::new().draw(&mut actual, &font, "WK0", (1, 4), WHITE, 3, BlendMode::Over);
+            assert!(expected.data_as_ref() == actual.data_as_ref(), "differs under {:?}", transform);
+        }
+    }
+
+    #[test]
+    fn test_glyphs_are_cached_per_font_and_scale() {
+        let mut buffer = PixelBuffer::new(100, 20);
+        let (font, other) = (Font::builtin(), Font::builtin());
+        let mut text = TextRasterizer::new();
+        text.draw(&mut buffer, &font, "AAB", (0, 0), WHITE, 2, BlendMode::Over);
+        assert_eq!(text.cached(), 2);
+        text.draw(&mut buffer, &font, "BA", (0, 0), WHITE, 2, BlendMode::Over);
+        assert_eq!(text.cached(), 2);
+        text.draw(&mut buffer, &font, "A", (0, 0), WHITE, 3, BlendMode::Over);
+        assert_eq!(text.cached(), 3);
+        text.draw(&mut buffer, &other, "A", (0, 0), WHITE, 3, BlendMode::Over);
+        assert_eq!(text.cached(), 4);
+    }
+
+    #[test]
+    fn test_other_cell_sizes() {
+        // a 3x2 face whose only glyph is a ring
+        let mut ring = Bitset::new(6);
+        for i in [0, 1, 2, 3, 5] {
+            ring.set(i, true);
+        }
+        let font = Font::new((3, 2)).with_glyph('o', ring).with_spacing(2);
+        let mut buffer = PixelBuffer::new(8, 2);
+        TextRasterizer::new().draw(&mut buffer, &font, "oo", (0, 0), WHITE, 1, BlendMode::Over);
+        let lit = |y| (0..8).map(|x| if buffer.get((x, y)) == Some(WHITE) { 'X' } else { '.' }).collect::<alloc::string::String>();
+        assert_eq!((lit(0).as_str(), lit(1).as_str()), ("XXX..XXX", "X.X..X.X"));
+    }
+
+    #[test]
+    fn test_proportional() {
+        // ! is a single column of ink, and space is 3 wide
+        let font = Font::builtin();
+        let mut buffer = PixelBuffer::new(10, 1);
+        TextRasterizer::new().draw(&mut buffer, &font, "! !", (1, 0), WHITE, 1, BlendMode::Over);
+        let lit = (0..10).map(|x| if buffer.get((x, 0)) == Some(WHITE) { 'X' } else { '.' }).collect::<alloc::string::String>();
+        assert_eq!(lit, ".X.....X..");
+    }
+
+    #[test]
+    fn test_effects() {
+        let mut dot = Bitset::new(1);
+        dot.set(0, true);
+        let font = Font::new((1, 1)).with_glyph('x', dot);
+        let draw = |style: Style, (width, height): Size| -> Vec<String> {
+            let layout = Paragraph::new().layout(&font, &RichText::plain(String::from("x"), style));
+            let mut buffer = PixelBuffer::new(width, height);
+            TextRasterizer::new().draw_layout(&mut buffer, &font, &layout, (1, 1), BlendMode::Over);
+            let lit = |x, y| match buffer.get((x, y)) {
+                Some(WHITE) => 'W',
+                Some(RED) => 'R',
+                Some(BLUE) => 'B',
+                _ => '.',
+            };
+            (0..height).map(|y| (0..width).map(|x| lit(x, y)).collect()).collect()
+        };
+        // a glyph pixel down and to the right, under the text
+        let shadowed = Style { shadow: Some(RED), ..Style::new(WHITE, 2) };
+        assert_eq!(draw(shadowed, (5, 5)), [".....", ".WW..", ".WW..", "...RR", "...RR"]);
+        // bold doubles the outline up too, and the layout leaves room for
+        // it, so nothing is drawn above or left of the layout's position
+        let outlined = Style { outline: Some(BLUE), bold: true, ..Style::new(WHITE, 1) };
+        assert_eq!(draw(outlined, (5, 4)), [".....", ".BBBB", ".BWWB", ".BBBB"]);
+        // the underline sits just below the glyph
+        let underlined = Style { underline: true, ..Style::new(WHITE, 1) };
+        assert_eq!(draw(underlined, (3, 3)), ["...", ".W.", ".W."]);
+    }
+
+    #[test]
+    fn test_runs() {
+        // the top row of W is |X| | | |X|, the bottom | |X| |X| |
+        let font = Font::builtin();
+        let runs = expand(font.glyph('W'), font.cell_size(), 2);
+        assert_eq!(runs[..2], [((0, 0), (1, 1)), ((8, 0), (9, 1))]);
+        assert_eq!(runs[runs.len() - 2..], [((2, 8), (3, 9)), ((6, 8), (7, 9))]);
+        assert_eq!(expand(font.glyph(' '), font.cell_size(), 2), vec![]);
+        let mut buffer = PixelBuffer::new(4, 4);
+        TextRasterizer::new().draw(&mut buffer, &font, " ", (0, 0), WHITE, 1, BlendMode::Over);
+        assert!(buffer.data_as_ref().chunks_exact(4).all(|pixel| pixel == <[u8; 4]>::from(TRANSPARENT)));
+    }
+}
@@ -0,0 +1,240 @@
+//! Tile-based rasterization. Drawing is recorded first, each command is
+//! binned into the [TILE_SIZE] tiles it touches, then every tile replays
+//! its own commands into a copy of just its pixels. Tiles share nothing,
+//! so a [TileExecutor] can hand them to as many threads as there are.
+//!
+//! Every drawing primitive decides each pixel from that pixel alone, and
+//! a tile replays its commands in the order they were drawn, so the result
+//! is identical to drawing straight into the buffer.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    gradient::Gradient,
+    pixels::{BlendMode, BlitOptions, Color, PixelBuffer},
+    transform::{Area, Transform},
+    ui::{Point, Rect},
+};
+
+/// Big enough that binning stays cheap, small enough that a frame splits
+/// into plenty of tiles to share out
+pub const TILE_SIZE: usize = 64;
+
+/// A drawing call, with what it needs to be replayed anywhere
+pub(crate) struct Command {
+    /// The clip and transform in effect when it was recorded
+    pub(crate) clip: Rect,
+    pub(crate) transform: Transform,
+    /// Every device pixel it might touch
+    pub(crate) bounds: Rect,
+    pub(crate) op: Op,
+}
+
+pub(crate) enum Op {
+    Set(Point, Color),
+    Blend(Point, Color, BlendMode),
+    Span { y: usize, x0: usize, x1: usize, color: Color, mode: BlendMode },
+    /// Filled with `gradient` if there is one, otherwise `color`
+    Fill { area: Area, color: Color, gradient: Option<Gradient>, mode: BlendMode },
+    /// `source` only holds the part being drawn
+    Blit { source: Box<PixelBuffer>, pos: Point, options: BlitOptions },
+}
+
+/// A piece of the picture and the commands that land on it
+pub struct Tile {
+    rect: Rect,
+    buffer: PixelBuffer,
+    /// Indices into the recording, in drawing order
+    commands: Vec<usize>,
+}
+
+impl Tile {
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+}
+
+/// Decides where tiles get rasterized. Implement this to bring your own
+/// threads, e.g. a wasm thread pool over a `SharedArrayBuffer`.
+pub trait TileExecutor {
+    /// Calls `rasterize` on every tile exactly once, in any order, from any
+    /// thread
+    fn execute(&self, tiles: &mut [Tile], rasterize: &(dyn Fn(&mut Tile) + Sync));
+}
+
+/// One tile after another on the calling thread. Works everywhere.
+pub struct Sequential;
+
+impl TileExecutor for Sequential {
+    fn execute(&self, tiles: &mut [Tile], rasterize: &(dyn Fn(&mut Tile) + Sync)) {
+        tiles.iter_mut().for_each(rasterize);
+    }
+}
+
+/// Shares tiles out between OS threads, round robin so that busy parts of
+/// the screen are spread across all of them
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub struct Threads {
+    count: usize,
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Threads {
+    pub fn new(count: usize) -> Self {
+        Self { count: count.max(1) }
+    }
+
+    /// One thread per core
+    pub fn available() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |count| count.get()))
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl TileExecutor for Threads {
+    fn execute(&self, tiles: &mut [Tile], rasterize: &(dyn Fn(&mut Tile) + Sync)) {
+        if self.count == 1 || tiles.len() < 2 {
+            return Sequential.execute(tiles, rasterize);
+        }
+        let mut shares: Vec<Vec<&mut Tile>> = (0..self.count).map(|_| vec![]).collect();
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            shares[i % self.count].push(tile);
+        }
+        std::thread::scope(|scope| {
+            for share in shares {
+                scope.spawn(move || share.into_iter().for_each(rasterize));
+            }
+        });
+    }
+}
+
+/// Rasterizes whatever `draw` draws into `target` a tile at a time, under
+/// `target`'s clip and transform, with the same result as drawing into it
+/// directly. `draw` gets a stand-in buffer that only records, so reading
+/// pixels back from it gets nothing.
+pub fn render_tiled(target: &mut PixelBuffer, executor: &dyn TileExecutor, draw: impl FnOnce(&mut PixelBuffer)) {
+    let mut recorder = target.recorder();
+    draw(&mut recorder);
+    let commands = target.end_recording(recorder);
+    if commands.is_empty() {
+        return;
+    }
+
+    let columns = target.width.div_ceil(TILE_SIZE);
+    let rows = target.height.div_ceil(TILE_SIZE);
+    let mut bins: Vec<Vec<usize>> = (0..columns * rows).map(|_| vec![]).collect();
+    for (i, command) in commands.iter().enumerate() {
+        let ((left, top), (right, bottom)) = command.bounds;
+        for row in (top / TILE_SIZE)..=(bottom / TILE_SIZE).min(rows - 1) {
+            for column in (left / TILE_SIZE)..=(right / TILE_SIZE).min(columns - 1) {
+                bins[row * columns + column].push(i);
+            }
+        }
+    }
+
+    let mut tiles: Vec<Tile> = vec![];
+    for (i, bin) in bins.into_iter().enumerate() {
+        if bin.is_empty() {
+            continue;
+        }
+        let (left, top) = ((i % columns) * TILE_SIZE, (i / columns) * TILE_SIZE);
+        let right = (left + TILE_SIZE).min(target.width) - 1;
+        let bottom = (top + TILE_SIZE).min(target.height) - 1;
+        let rect = ((left, top), (right, bottom));
+        tiles.push(Tile { rect, buffer: target.copy_window(rect), commands: bin });
+    }
+
+    executor.execute(&mut tiles, &|tile: &mut Tile| {
+        for &i in &tile.commands {
+            tile.buffer.replay(&commands[i]);
+        }
+    });
+    for tile in &tiles {
+        target.paste_window(&tile.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gradient::Gradient,
+        pixels::{Sampling, BLUE, GREEN, RED, WHITE},
+        shapes::{Circle, Line, Polygon},
+        ui::{Drawable, Text},
+    };
+    use crate::font::Font;
+    use alloc::{rc::Rc, string::ToString};
+
+    /// A bit of everything: solid and gradient fills, text, shapes, a
+    /// translucent rotated blit, clips and transforms, spanning many tiles
+    fn scene(buffer: &mut PixelBuffer) {
+        let mut image = PixelBuffer::new(40, 30);
+        for y in 0..30 {
+            for x in 0..40 {
+                image.set((x, y), Color::new(x as u8 * 6, y as u8 * 8, 100, 128 + x as u8));
+            }
+        }
+        let gradient = Gradient::radial((150.0, 100.0), 120.0).with_stop(0.0, WHITE).with_stop(1.0, BLUE);
+
+        buffer.clear(Color::rgb(10, 20, 30));
+        buffer.fill_rect(((20, 20), (280, 180)), &gradient, BlendMode::Replace);
+        buffer.fill_rect(((5, 60), (295, 70)), Color::new(255, 0, 0, 90), BlendMode::Screen);
+        buffer.push_clip(((30, 0), (200, 199)));
+        Text::new("TILES".to_string(), &Rc::new(Font::builtin()), 5, GREEN).draw(buffer, (10, 5));
+        buffer.pop_clip();
+        Circle::filled(40, Color::new(200, 100, 0, 200)).draw(buffer, (100, 90));
+        Line::new((0, 0), (250, 170), WHITE).anti_aliased().draw(buffer, (20, 10));
+        Polygon::filled(vec![(0, 40), (30, 0), (60, 40)], RED).draw(buffer, (220, 140));
+        buffer.push_transform(Transform::rotate(0.6).around((150.0, 100.0)));
+        buffer.blit(&image, (130, 85), &BlitOptions { sampling: Sampling::Bilinear, size: Some((80, 60)), ..Default::default() });
+        buffer.pop_transform();
+        buffer.set((299, 199), RED);
+        buffer.blend((0, 0), Color::new(0, 255, 0, 100), BlendMode::Over);
+    }
+
+    fn direct() -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(300, 200);
+        buffer.take_dirty_rects();
+        scene(&mut buffer);
+        buffer
+    }
+
+    fn tiled(executor: &dyn TileExecutor) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(300, 200);
+        buffer.take_dirty_rects();
+        render_tiled(&mut buffer, executor, scene);
+        buffer
+    }
+
+    #[test]
+    fn test_tiled_matches_direct() {
+        let (mut expected, mut actual) = (direct(), tiled(&Sequential));
+        assert_ne!(expected.get((150, 100)), expected.get((290, 10)), "nothing was drawn");
+        assert!(expected.data_as_ref() == actual.data_as_ref(), "tiled rendering differs");
+        assert_eq!(actual.take_dirty_rects(), expected.take_dirty_rects());
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_threads_match_direct() {
+        let expected = direct();
+        for count in [2, 3, 8] {
+            assert!(tiled(&Threads::new(count)).data_as_ref() == expected.data_as_ref(), "{} threads differ", count);
+        }
+    }
+
+    #[test]
+    fn test_respects_target_clip_and_transform() {
+        let draw = |buffer: &mut PixelBuffer| buffer.fill_rect(((0, 0), (99, 99)), WHITE, BlendMode::Over);
+        let mut expected = PixelBuffer::new(150, 150);
+        let mut actual = PixelBuffer::new(150, 150);
+        for buffer in [&mut expected, &mut actual] {
+            buffer.push_clip(((10, 10), (120, 70)));
+            buffer.push_transform(Transform::translate(30.0, 5.0));
+        }
+        draw(&mut expected);
+        render_tiled(&mut actual, &Sequential, draw);
+        assert!(expected.data_as_ref() == actual.data_as_ref());
+    }
+}
@@ -0,0 +1,219 @@
+//! 2D affine transforms for drawing. A [PixelBuffer](crate::pixels::PixelBuffer)
+//! keeps a stack of them, like its clip stack, so containers can move,
+//! zoom or spin everything their children draw without the children
+//! knowing.
+
+use core::ops::Mul;
+
+use crate::{math, ui::Point};
+
+/// Maps `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`, the same
+/// layout as the Canvas API's `setTransform`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// An area in floating point coordinates: left, top, right, bottom, with
+/// the right and bottom edges exclusive
+pub type Area = (f32, f32, f32, f32);
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self { e: x, f: y, ..Self::IDENTITY }
+    }
+
+    /// Fractional scales are fine; pixels grow or shrink to cover whatever
+    /// device pixels their centers land in
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self { a: x, d: y, ..Self::IDENTITY }
+    }
+
+    /// Clockwise on screen, since y points down
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = (math::sin(radians), math::cos(radians));
+        Self { a: cos, b: sin, c: -sin, d: cos, ..Self::IDENTITY }
+    }
+
+    /// Exact rotation by 90° steps, clockwise. Unlike [Transform::rotate],
+    /// there's no rounding error to smear pixels.
+    pub fn quarter_turns(turns: i32) -> Self {
+        let (sin, cos) = match turns.rem_euclid(4) {
+            0 => (0.0, 1.0),
+            1 => (1.0, 0.0),
+            2 => (0.0, -1.0),
+            _ => (-1.0, 0.0),
+        };
+        Self { a: cos, b: sin, c: -sin, d: cos, ..Self::IDENTITY }
+    }
+
+    /// This transform applied about `(x, y)` instead of the origin, e.g. to
+    /// spin something around its center
+    pub fn around(self, (x, y): (f32, f32)) -> Self {
+        Self::translate(x, y) * self * Self::translate(-x, -y)
+    }
+
+    /// Moves the origin, then applies this transform. Reads like the
+    /// Canvas API: `t.translated(10.0, 0.0)` draws 10 local pixels right.
+    pub fn translated(self, x: f32, y: f32) -> Self {
+        self * Self::translate(x, y)
+    }
+
+    pub fn scaled(self, x: f32, y: f32) -> Self {
+        self * Self::scale(x, y)
+    }
+
+    pub fn rotated(self, radians: f32) -> Self {
+        self * Self::rotate(radians)
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// `None` if this squashes everything flat, e.g. a scale of 0
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// The offset, if this only moves things by whole pixels
+    pub fn integer_translation(&self) -> Option<(isize, isize)> {
+        let whole = |v: f32| v == v as isize as f32;
+        if self.a == 1.0 && self.b == 0.0 && self.c == 0.0 && self.d == 1.0 && whole(self.e) && whole(self.f) {
+            Some((self.e as isize, self.f as isize))
+        } else {
+            None
+        }
+    }
+
+    /// Whether rectangles stay rectangles with edges along the axes, so
+    /// drawing can skip per-pixel mapping
+    pub fn is_axis_aligned(&self) -> bool {
+        self.b == 0.0 && self.c == 0.0 || self.a == 0.0 && self.d == 0.0
+    }
+
+    /// The smallest axis-aligned area containing `area` once transformed
+    pub fn bounds(&self, (left, top, right, bottom): Area) -> Area {
+        let corners = [
+            self.apply((left, top)),
+            self.apply((right, top)),
+            self.apply((left, bottom)),
+            self.apply((right, bottom)),
+        ];
+        let mut bounds = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for &(x, y) in &corners {
+            bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+        }
+        bounds
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    /// `outer * inner` applies `inner` first
+    fn mul(self, inner: Transform) -> Transform {
+        Transform {
+            a: self.a * inner.a + self.c * inner.b,
+            b: self.b * inner.a + self.d * inner.b,
+            c: self.a * inner.c + self.c * inner.d,
+            d: self.b * inner.c + self.d * inner.d,
+            e: self.a * inner.e + self.c * inner.f + self.e,
+            f: self.b * inner.e + self.d * inner.f + self.f,
+        }
+    }
+}
+
+/// The inclusive pixel range whose centers fall inside `start..end`, or
+/// `None` if there isn't one on screen
+pub(crate) fn pixel_span(start: f32, end: f32) -> Option<(usize, usize)> {
+    let first = math::ceil(start - 0.5).max(0.0);
+    let last = math::ceil(end - 0.5) - 1.0;
+    if last.is_nan() || last < first {
+        return None;
+    }
+    Some((first as usize, last as usize))
+}
+
+/// The local area covered by the inclusive pixel rect from `topleft` to
+/// `bottomright`
+pub(crate) fn pixel_area((left, top): Point, (right, bottom): Point) -> Area {
+    (left as f32, top as f32, right as f32 + 1.0, bottom as f32 + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::FRAC_PI_2;
+
+    fn assert_near((x, y): (f32, f32), (ex, ey): (f32, f32)) {
+        assert!((x - ex).abs() < 1e-4 && (y - ey).abs() < 1e-4, "({}, {}) != ({}, {})", x, y, ex, ey);
+    }
+
+    #[test]
+    fn test_compose_order() {
+        // scale, then move
+        let t = Transform::translate(10.0, 20.0).scaled(2.0, 3.0);
+        assert_eq!(t.apply((1.0, 1.0)), (12.0, 23.0));
+        assert_near(Transform::rotate(FRAC_PI_2).apply((1.0, 0.0)), (0.0, 1.0));
+        assert_eq!(Transform::quarter_turns(1).apply((1.0, 0.0)), (0.0, 1.0));
+        assert_eq!(Transform::quarter_turns(-2).apply((1.0, 2.0)), (-1.0, -2.0));
+        assert_eq!(Transform::quarter_turns(2).around((5.0, 5.0)).apply((6.0, 5.0)), (4.0, 5.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let t = Transform::translate(3.0, -4.0).rotated(0.3).scaled(2.0, 0.5);
+        let inverse = t.inverse().unwrap();
+        assert_near(inverse.apply(t.apply((7.0, 11.0))), (7.0, 11.0));
+        assert_eq!(Transform::scale(0.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(Transform::translate(3.0, -2.0).integer_translation(), Some((3, -2)));
+        assert_eq!(Transform::translate(0.5, 0.0).integer_translation(), None);
+        assert!(Transform::quarter_turns(1).scaled(2.0, 2.0).is_axis_aligned());
+        assert!(!Transform::rotate(0.1).is_axis_aligned());
+        assert_eq!(Transform::quarter_turns(1).bounds((0.0, 0.0, 4.0, 2.0)), (-2.0, 0.0, 0.0, 4.0));
+    }
+
+    #[test]
+    fn test_pixel_span() {
+        assert_eq!(pixel_span(0.0, 3.0), Some((0, 2)));
+        // centers at 1.5 and 2.5 are inside, 0.5 isn't
+        assert_eq!(pixel_span(1.2, 3.4), Some((1, 2)));
+        assert_eq!(pixel_span(1.6, 2.4), None);
+        assert_eq!(pixel_span(-5.0, 1.0), Some((0, 0)));
+        assert_eq!(pixel_span(-5.0, -1.0), None);
+    }
+}
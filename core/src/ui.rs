@@ -0,0 +1,382 @@
+use alloc::{boxed::Box, rc::Rc, string::String};
+use crate::{
+    font::Font, gradient::{Gradient, Paint}, math, paragraph::{Layout, Paragraph}, pixels::{BlendMode, Color, PixelBuffer, RED}, rich::{RichText, Style}, transform::Transform
+};
+
+/// x, y
+pub type Point = (usize, usize);
+/// topleft, bottomright
+pub type Rect = (Point, Point);
+/// width, height
+pub type Size = Point;
+
+pub fn is_point_in_rect((x, y): Point, rect: Rect) -> bool {
+    let (topleft, bottomright) = rect;
+    let (topleft_x, topleft_y) = topleft;
+    let (bottomright_x, bottomright_y) = bottomright;
+
+    x >= topleft_x && y >= topleft_y && x <= bottomright_x && y <=bottomright_y
+}
+
+/// The overlap of two rects. If they don't overlap, the result's topleft
+/// is past its bottomright, so no point is in it.
+pub fn intersect_rects(a: Rect, b: Rect) -> Rect {
+    let ((a_left, a_top), (a_right, a_bottom)) = a;
+    let ((b_left, b_top), (b_right, b_bottom)) = b;
+
+    (
+        (a_left.max(b_left), a_top.max(b_top)),
+        (a_right.min(b_right), a_bottom.min(b_bottom)),
+    )
+}
+
+pub trait Renderable {
+    fn render(&mut self, buffer: &mut PixelBuffer);
+
+    /// The area that needs repainting because this element's state changed
+    /// since the last frame, if any. Calling this clears it.
+    fn take_invalidated(&mut self) -> Option<Rect> {
+        None
+    }
+}
+pub trait Drawable {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point);
+    fn get_render_size(&self) -> Size;
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    MouseDown,
+    MouseUp
+}
+pub trait GestureHandler: Renderable {
+    fn on_event(&mut self, type_: Gesture);
+    fn get_collision_rect(&self) -> Rect;
+}
+
+pub struct HCenter {
+    child: Box<dyn Drawable>,
+    pos: Point,
+    debug_mode: bool
+}
+impl HCenter {
+    pub fn new(pos: Point, child: Box<dyn Drawable>) -> Self {
+        Self {
+            pos,
+            child,
+            debug_mode: false,
+        }
+    }
+}
+impl Renderable for HCenter {
+    fn render(&mut self, buffer: &mut PixelBuffer) {
+        // find center point, offset child
+        let (child_width, _) = self.child.get_render_size();
+        // let (_, height) = self.get_render_size(buffer);
+        let center_x = self.pos.0 + (buffer.width / 2);
+        // children wider than the buffer start at its left edge
+        let child_x = center_x.saturating_sub(child_width / 2);
+        // self.child.pos = (0,0);
+        self.child.draw(buffer, (child_x, self.pos.1));
+        if self.debug_mode {
+            buffer.set((center_x, self.pos.1), RED);
+            buffer.set((buffer.width.saturating_sub(10), self.pos.1), RED);
+        }
+    }
+}
+
+pub struct Positioned {
+    pos: Point,
+    child: Box<dyn Drawable>,
+}
+impl Positioned {
+    pub fn new(pos: Point, child: Box<dyn Drawable>) -> Self {
+        Self {
+            pos,
+            child,
+        }
+    }
+}
+impl Renderable for Positioned {
+    fn render(&mut self, buffer: &mut PixelBuffer) {
+        self.child.draw(buffer, self.pos);
+    }
+}
+
+/// Draws its child, cut off at `size`. Useful for labels that could
+/// outgrow their container.
+pub struct Clipped {
+    size: Size,
+    child: Box<dyn Drawable>,
+}
+impl Clipped {
+    pub fn new(size: Size, child: Box<dyn Drawable>) -> Self {
+        Self { size, child }
+    }
+}
+impl Drawable for Clipped {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        let (width, height) = self.size;
+        if width == 0 || height == 0 {
+            return;
+        }
+        buffer.push_clip((pos, (pos.0 + width - 1, pos.1 + height - 1)));
+        self.child.draw(buffer, pos);
+        buffer.pop_clip();
+    }
+
+    fn get_render_size(&self) -> Size {
+        let (child_width, child_height) = self.child.get_render_size();
+        (child_width.min(self.size.0), child_height.min(self.size.1))
+    }
+}
+
+/// Draws its child moved, scaled or rotated by `transform` about the
+/// point it's placed at. Nested ones compose.
+pub struct Transformed {
+    transform: Transform,
+    child: Box<dyn Drawable>,
+}
+impl Transformed {
+    pub fn new(transform: Transform, child: Box<dyn Drawable>) -> Self {
+        Self { transform, child }
+    }
+
+    /// e.g. to spin or zoom the child over time
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+}
+impl Drawable for Transformed {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        buffer.push_transform(Transform::translate(pos.0 as f32, pos.1 as f32) * self.transform);
+        self.child.draw(buffer, (0, 0));
+        buffer.pop_transform();
+    }
+
+    /// The size of the transformed child's bounding box, which may stick
+    /// out above or left of where it's placed
+    fn get_render_size(&self) -> Size {
+        let (width, height) = self.child.get_render_size();
+        let (left, top, right, bottom) = self.transform.bounds((0.0, 0.0, width as f32, height as f32));
+        (math::ceil(right - left) as usize, math::ceil(bottom - top) as usize)
+    }
+}
+
+pub struct Text {
+    text: RichText,
+    font: Rc<Font>,
+    blend_mode: BlendMode,
+    /// Worked out up front, so measuring is free
+    layout: Layout,
+}
+impl Text {
+    /// One line per `\n`, never wrapped; see [Text::with_paragraph]
+    pub fn new(text: String, font: &Rc<Font>, scale: usize, color: Color) -> Self {
+        Self::rich(RichText::plain(text, Style::new(color, scale)), font)
+    }
+
+    /// Text whose spans each have their own color, scale, decorations and
+    /// effects
+    pub fn rich(text: RichText, font: &Rc<Font>) -> Self {
+        let layout = Paragraph::new().layout(font, &text);
+        Self {
+            text,
+            font: font.clone(),
+            blend_mode: BlendMode::default(),
+            layout,
+        }
+    }
+
+    /// Wraps, spaces and aligns lines as `paragraph` says
+    pub fn with_paragraph(mut self, paragraph: Paragraph) -> Self {
+        self.layout = paragraph.layout(&self.font, &self.text);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Draws every span in `color`, keeping the rest of their styles
+    pub fn set_color(&mut self, color: Color) {
+        self.text.set_color(color);
+        self.layout.set_color(color);
+    }
+}
+
+impl Drawable for Text {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        buffer.draw_layout(&self.layout, pos, &self.font, self.blend_mode);
+    }
+    fn get_render_size(&self) -> Size {
+        self.layout.size()
+    }
+}
+
+pub struct Rectangle {
+    size: Size,
+    color: Color,
+    /// Drawn instead of `color`, measured from the rectangle's top left
+    gradient: Option<Gradient>,
+    blend_mode: BlendMode,
+}
+impl Rectangle {
+    pub fn new(size: Size, color: Color) -> Self {
+        Self {
+            size,
+            color,
+            gradient: None,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    /// e.g. to advance its animation
+    pub fn gradient_mut(&mut self) -> Option<&mut Gradient> {
+        self.gradient.as_mut()
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+impl Drawable for Rectangle {
+    fn draw(&mut self, buffer: &mut PixelBuffer, pos: Point) {
+        let (width, height) = self.size;
+        if width == 0 || height == 0 {
+            return;
+        }
+        let paint = match &self.gradient {
+            Some(gradient) => Paint::Gradient(gradient),
+            None => Paint::Solid(self.color),
+        };
+        // so the gradient is measured from the rectangle's top left
+        buffer.push_transform(Transform::translate(pos.0 as f32, pos.1 as f32));
+        buffer.fill_rect(((0, 0), (width - 1, height - 1)), paint, self.blend_mode);
+        buffer.pop_transform();
+    }
+
+    fn get_render_size(&self) -> Size {
+        self.size
+    }
+}
+
+pub struct Button {
+    pos: Point,
+    rectangle: Rectangle,
+    text: Text,
+    color: Color,
+    gradient: Option<Gradient>,
+    text_color: Color,
+    // selected_color: Color,
+    // selected_text_color: Color,
+    // is_selected: bool,
+    is_clicked: bool,
+    is_invalidated: bool,
+    margin: usize,
+}
+impl Button {
+    pub fn new(
+        pos: Point,
+        text: String,
+        font: &Rc<Font>,
+        scale: usize,
+        color: Color,
+        text_color: Color,
+        // selected_color: Color,
+        // selected_text_color: Color,
+    ) -> Self {
+        let margin = 5;
+        let text = Text::new(text, font, scale, text_color);
+        let rect_size = (
+            text.get_render_size().0 + (margin * 2),
+            text.get_render_size().1 + (margin * 2)
+        );
+
+        
+        Self {
+            pos,
+            rectangle: Rectangle::new(rect_size, color),
+            text,
+            color,
+            gradient: None,
+            text_color,
+            // selected_color,
+            // selected_text_color,
+            // is_selected: false,
+            is_clicked: false,
+            is_invalidated: false,
+            margin,
+        }
+    }
+
+    /// Fills the background with `gradient` instead of a flat color. Its
+    /// geometry is measured from the button's top left.
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    /// Applies to both the button's background and its label
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.rectangle.blend_mode = blend_mode;
+        self.text.blend_mode = blend_mode;
+        self
+    }
+}
+impl Renderable for Button {
+    fn render(&mut self, buffer: &mut PixelBuffer) {
+        let text_pos: Point = (
+            self.pos.0 + self.margin,
+            self.pos.1 + self.margin,
+        );
+
+        if self.is_clicked {
+            self.rectangle.color = self.color.darken(0.5);
+            self.rectangle.gradient = self.gradient.as_ref().map(|gradient| gradient.map_colors(|color| color.darken(0.5)));
+            self.text.set_color(self.text_color.darken(0.5));
+        } else {
+            self.rectangle.color = self.color;
+            self.rectangle.gradient = self.gradient.clone();
+            self.text.set_color(self.text_color);
+        }
+        self.rectangle.draw(buffer, self.pos);
+        // keep the label inside the button's background
+        let (width, height) = self.rectangle.size;
+        buffer.push_clip((self.pos, (self.pos.0 + width - 1, self.pos.1 + height - 1)));
+        self.text.draw(buffer, text_pos);
+        buffer.pop_clip();
+    }
+
+    fn take_invalidated(&mut self) -> Option<Rect> {
+        if !self.is_invalidated {
+            return None;
+        }
+        self.is_invalidated = false;
+        Some(self.get_collision_rect())
+    }
+}
+impl GestureHandler for Button {
+    fn on_event(&mut self, type_: Gesture) {
+        let was_clicked = self.is_clicked;
+        match type_ {
+            Gesture::MouseDown => self.is_clicked = true,
+            _ => self.is_clicked = false,
+        }
+        self.is_invalidated |= was_clicked != self.is_clicked;
+    }
+    fn get_collision_rect(&self) -> Rect {
+        let (x, y) = self.pos;
+        let (w, h) = self.rectangle.size;
+        (
+            (x, y),
+            (x + w, y + h)
+        )
+    }
+}
\ No newline at end of file
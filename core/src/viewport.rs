@@ -0,0 +1,104 @@
+//! Maps between the browser's CSS pixels, the canvas's device pixels and
+//! the (usually smaller) virtual screen the UI is drawn at.
+//!
+//! The virtual screen is always scaled up by a whole number, so every
+//! virtual pixel is a crisp square of device pixels.
+
+use crate::ui::{Point, Size};
+
+/// How big the virtual screen's pixels are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// One virtual pixel per device pixel
+    Native,
+    /// Each virtual pixel is this many device pixels square
+    Scale(usize),
+    /// The biggest scale that still leaves at least this many rows
+    Height(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// The canvas's backing size, in device pixels
+    pub canvas_size: Size,
+    /// What the UI is rendered at
+    pub virtual_size: Size,
+    /// Device pixels per virtual pixel, in each direction
+    pub scale: usize,
+    device_pixel_ratio: f64,
+}
+
+impl Viewport {
+    /// The viewport for a canvas covering `css_size` CSS pixels
+    pub fn new((css_width, css_height): (f64, f64), device_pixel_ratio: f64, resolution: Resolution) -> Self {
+        let device_pixel_ratio = if device_pixel_ratio > 0.0 { device_pixel_ratio } else { 1.0 };
+        let canvas_size = (
+            (css_width * device_pixel_ratio + 0.5) as usize,
+            (css_height * device_pixel_ratio + 0.5) as usize,
+        );
+        let scale = match resolution {
+            Resolution::Native => 1,
+            Resolution::Scale(scale) => scale,
+            Resolution::Height(rows) => canvas_size.1 / rows.max(1),
+        }
+        .max(1);
+        // round up, so a partial virtual pixel covers any leftover edge
+        let virtual_size = (canvas_size.0.div_ceil(scale), canvas_size.1.div_ceil(scale));
+        Self {
+            canvas_size,
+            virtual_size,
+            scale,
+            device_pixel_ratio,
+        }
+    }
+
+    /// The virtual pixel under a point given in CSS pixels, e.g. from a
+    /// mouse event, or `None` if it's off screen
+    pub fn to_virtual(&self, (css_x, css_y): (f64, f64)) -> Option<Point> {
+        if css_x < 0.0 || css_y < 0.0 {
+            return None;
+        }
+        let device_x = (css_x * self.device_pixel_ratio) as usize;
+        let device_y = (css_y * self.device_pixel_ratio) as usize;
+        let point = (device_x / self.scale, device_y / self.scale);
+        if point.0 >= self.virtual_size.0 || point.1 >= self.virtual_size.1 {
+            return None;
+        }
+        Some(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_uses_device_pixels() {
+        let viewport = Viewport::new((800.0, 600.0), 2.0, Resolution::Native);
+        assert_eq!(viewport.canvas_size, (1600, 1200));
+        assert_eq!(viewport.virtual_size, (1600, 1200));
+        assert_eq!(viewport.to_virtual((10.0, 10.5)), Some((20, 21)));
+    }
+
+    #[test]
+    fn test_scale_rounds_virtual_size_up() {
+        let viewport = Viewport::new((1001.0, 600.0), 1.0, Resolution::Scale(4));
+        assert_eq!((viewport.scale, viewport.virtual_size), (4, (251, 150)));
+        assert_eq!(viewport.to_virtual((1000.0, 599.0)), Some((250, 149)));
+        assert_eq!(viewport.to_virtual((1004.0, 0.0)), None);
+        assert_eq!(viewport.to_virtual((-1.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_height_picks_integer_scale() {
+        // a 4K screen at 150%: 1440 CSS rows are 2160 device rows
+        let viewport = Viewport::new((2560.0, 1440.0), 1.5, Resolution::Height(480));
+        assert_eq!(viewport.canvas_size, (3840, 2160));
+        assert_eq!((viewport.scale, viewport.virtual_size), (4, (960, 540)));
+        assert_eq!(viewport.to_virtual((100.0, 100.0)), Some((37, 37)));
+
+        // never scales below 1, even when the screen is too short
+        let viewport = Viewport::new((300.0, 200.0), 1.0, Resolution::Height(480));
+        assert_eq!((viewport.scale, viewport.virtual_size), (1, (300, 200)));
+    }
+}
@@ -0,0 +1,129 @@
+//! Runs the whole app headlessly, the same way the browser drives it.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+// only the assertions are needed here
+#[allow(dead_code)]
+mod snapshot;
+
+use kevinpthornecom_core::{
+    app::{App, HUD_LAYER},
+    pixels::PixelBuffer,
+    present::{HeadlessPresenter, Presenter},
+    ui::Gesture,
+    viewport::Resolution,
+};
+use snapshot::assert_snapshot;
+
+const SCREEN: (f64, f64) = (640.0, 360.0);
+
+fn run(app: &mut App, presenter: &mut impl Presenter, frames: u32) {
+    for frame in 1..=frames {
+        assert!(app.on_frame(frame * 100, presenter).is_ok());
+    }
+}
+
+#[test]
+fn first_frame() {
+    let mut app = App::new(SCREEN, 1.0);
+    let mut presenter = HeadlessPresenter::new();
+    // too soon after the start for a frame
+    let Ok(()) = app.on_frame(10, &mut presenter);
+    assert_eq!(presenter.frames, 0);
+
+    run(&mut app, &mut presenter, 1);
+    assert_eq!(presenter.frames, 1);
+    assert_eq!(presenter.last_dirty, vec![((0, 0), (639, 359))]);
+    assert_snapshot("app", presenter.frame());
+}
+
+#[test]
+fn idle_frames_only_present_the_stats() {
+    let mut app = App::new(SCREEN, 1.0);
+    let mut presenter = HeadlessPresenter::new();
+    run(&mut app, &mut presenter, 2);
+    assert_eq!(presenter.frames, 2);
+    // the stats line starts at row 344, in the row of dirty tiles from 320
+    assert!(!presenter.last_dirty.is_empty());
+    assert!(presenter.last_dirty.iter().all(|&((_, top), _)| top >= 320), "{:?}", presenter.last_dirty);
+
+    // an animated background changes every pixel
+    app.set_background_animation(true);
+    run(&mut app, &mut presenter, 3);
+    assert_eq!(presenter.last_dirty, vec![((0, 0), (639, 359))]);
+}
+
+#[test]
+fn clicking_the_button_repaints_it() {
+    let mut app = App::new(SCREEN, 1.0);
+    let mut presenter = HeadlessPresenter::new();
+    run(&mut app, &mut presenter, 1);
+    let before = presenter.frame().get((11, 191));
+
+    assert!(!app.on_gesture((600.0, 10.0), Gesture::MouseDown));
+    assert!(app.on_gesture((15.0, 195.0), Gesture::MouseDown));
+    run(&mut app, &mut presenter, 2);
+    assert_ne!(presenter.frame().get((11, 191)), before);
+
+    assert!(app.on_gesture((15.0, 195.0), Gesture::MouseUp));
+    run(&mut app, &mut presenter, 3);
+    assert_eq!(presenter.frame().get((11, 191)), before);
+}
+
+#[test]
+fn phone_viewport() {
+    // 234 virtual pixels across, narrower than the title
+    let mut app = App::new((390.0, 844.0), 3.0);
+    let mut presenter = HeadlessPresenter::new();
+    run(&mut app, &mut presenter, 2);
+    assert_eq!(presenter.frames, 2);
+}
+
+#[test]
+fn hiding_the_hud_layer() {
+    let mut app = App::new(SCREEN, 1.0);
+    let mut presenter = HeadlessPresenter::new();
+    run(&mut app, &mut presenter, 1);
+    // the stats line is the only thing along the bottom
+    let stats_pixels = |frame: &PixelBuffer| (0..640).filter(|&x| frame.get((x, 352)) != frame.get((639, 352))).count();
+    assert_ne!(stats_pixels(presenter.frame()), 0);
+
+    assert!(app.set_layer_visible(HUD_LAYER, false));
+    assert!(!app.set_layer_visible("nope", false));
+    run(&mut app, &mut presenter, 2);
+    assert_eq!(stats_pixels(presenter.frame()), 0);
+}
+
+#[test]
+fn upscaled_frames_match_native_ones() {
+    let mut native = App::new(SCREEN, 1.0);
+    let mut native_presenter = HeadlessPresenter::new();
+    run(&mut native, &mut native_presenter, 1);
+
+    let mut scaled = App::new(SCREEN, 2.0);
+    scaled.set_resolution(Resolution::Scale(2));
+    let mut scaled_presenter = HeadlessPresenter::new();
+    run(&mut scaled, &mut scaled_presenter, 1);
+
+    let (native_frame, scaled_frame) = (native_presenter.frame(), scaled_presenter.frame());
+    assert_eq!((scaled_frame.width, scaled_frame.height), (1280, 720));
+    for y in 0..native_frame.height {
+        for x in 0..native_frame.width {
+            assert_eq!(scaled_frame.get((x * 2 + 1, y * 2 + 1)), native_frame.get((x, y)));
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn file_sequence() {
+    use kevinpthornecom_core::present::{FileSequencePresenter, FrameFormat};
+
+    let dir = std::env::temp_dir().join(format!("kpt_app_frames_{}", std::process::id()));
+    let mut app = App::new(SCREEN, 1.0);
+    let mut presenter = FileSequencePresenter::new(&dir, FrameFormat::Ppm).unwrap();
+    run(&mut app, &mut presenter, 3);
+    assert_eq!(presenter.frames(), 3);
+    assert!(dir.join("frame_00002.ppm").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,227 @@
+#!/usr/bin/env python3
+"""Regenerates the image and font decoder fixtures in this directory.
+
+Only uses the standard library, so the files are produced by an encoder
+that shares no code with the decoders under test. The pixel pattern has
+to match `image::tests::pattern` in src/image/mod.rs, and the glyphs
+`font::tests` in src/font/mod.rs.
+"""
+
+import os
+import struct
+import zlib
+
+WIDTH, HEIGHT = 13, 9
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+ADAM7 = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)]
+
+
+def pattern(x, y):
+    return ((x * 16) % 256, (y * 16) % 256, ((x + y) * 8) % 256, 255 - x * y * 2)
+
+
+def write(name, data):
+    with open(os.path.join(HERE, name), "wb") as f:
+        f.write(data)
+
+
+# PNG
+
+def chunk(kind, data):
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+
+def paeth(a, b, c):
+    p = a + b - c
+    pa, pb, pc = abs(p - a), abs(p - b), abs(p - c)
+    if pa <= pb and pa <= pc:
+        return a
+    return b if pb <= pc else c
+
+
+def filter_rows(rows, bpp):
+    """Filters each row, cycling through every filter type"""
+    out = b""
+    previous = bytes(len(rows[0])) if rows else b""
+    for i, row in enumerate(rows):
+        kind = i % 5
+        filtered = bytearray([kind])
+        for j, value in enumerate(row):
+            left = row[j - bpp] if j >= bpp else 0
+            up = previous[j]
+            up_left = previous[j - bpp] if j >= bpp else 0
+            predictor = [0, left, up, (left + up) // 2, paeth(left, up, up_left)][kind]
+            filtered.append((value - predictor) % 256)
+        out += bytes(filtered)
+        previous = row
+    return out
+
+
+def pack_bits(samples, depth):
+    out = bytearray()
+    accumulator, count = 0, 0
+    for sample in samples:
+        accumulator = (accumulator << depth) | sample
+        count += depth
+        if count == 8:
+            out.append(accumulator)
+            accumulator, count = 0, 0
+    if count:
+        out.append(accumulator << (8 - count))
+    return bytes(out)
+
+
+def png(name, color_type, depth, row_for, bpp, interlaced=False, extra=b""):
+    if interlaced:
+        data = b""
+        for x0, y0, dx, dy in ADAM7:
+            xs = list(range(x0, WIDTH, dx))
+            ys = list(range(y0, HEIGHT, dy))
+            if xs and ys:
+                data += filter_rows([row_for(y, xs) for y in ys], bpp)
+    else:
+        data = filter_rows([row_for(y, list(range(WIDTH))) for y in range(HEIGHT)], bpp)
+    header = struct.pack(">IIBBBBB", WIDTH, HEIGHT, depth, color_type, 0, 0, 1 if interlaced else 0)
+    write(name, b"\x89PNG\r\n\x1a\n" + chunk(b"IHDR", header) + extra
+          + chunk(b"IDAT", zlib.compress(data, 9)) + chunk(b"IEND", b""))
+
+
+png("rgba8.png", 6, 8, lambda y, xs: bytes(c for x in xs for c in pattern(x, y)), 4)
+png("rgb16_interlaced.png", 2, 16,
+    lambda y, xs: b"".join(struct.pack(">HHH", *(c * 257 for c in pattern(x, y)[:3])) for x in xs), 6,
+    interlaced=True)
+png("indexed4.png", 3, 4, lambda y, xs: pack_bits([(x + y) % 3 for x in xs], 4), 1,
+    extra=chunk(b"PLTE", bytes([255, 0, 0, 0, 255, 0, 0, 0, 255])) + chunk(b"tRNS", bytes([255, 128, 0])))
+png("gray1.png", 0, 1, lambda y, xs: pack_bits([1 - ((x ^ y) & 1) for x in xs], 1), 1)
+
+
+# QOI
+
+def qoi(name, pixels):
+    out = bytearray(b"qoif" + struct.pack(">IIBB", WIDTH, HEIGHT, 4, 0))
+    index = [(0, 0, 0, 0)] * 64
+    previous = (0, 0, 0, 255)
+    run = 0
+    for i, px in enumerate(pixels):
+        if px == previous:
+            run += 1
+            if run == 62 or i == len(pixels) - 1:
+                out.append(0xC0 | (run - 1))
+                run = 0
+            continue
+        if run:
+            out.append(0xC0 | (run - 1))
+            run = 0
+        h = (px[0] * 3 + px[1] * 5 + px[2] * 7 + px[3] * 11) % 64
+        if index[h] == px:
+            out.append(h)
+        else:
+            index[h] = px
+            if px[3] == previous[3]:
+                dr = (px[0] - previous[0] + 128) % 256 - 128
+                dg = (px[1] - previous[1] + 128) % 256 - 128
+                db = (px[2] - previous[2] + 128) % 256 - 128
+                dr_dg, db_dg = dr - dg, db - dg
+                if -2 <= dr <= 1 and -2 <= dg <= 1 and -2 <= db <= 1:
+                    out.append(0x40 | (dr + 2) << 4 | (dg + 2) << 2 | (db + 2))
+                elif -32 <= dg <= 31 and -8 <= dr_dg <= 7 and -8 <= db_dg <= 7:
+                    out += bytes([0x80 | (dg + 32), (dr_dg + 8) << 4 | (db_dg + 8)])
+                else:
+                    out += bytes([0xFE, *px[:3]])
+            else:
+                out += bytes([0xFF, *px])
+        previous = px
+    write(name, bytes(out) + bytes([0] * 7 + [1]))
+
+
+# runs of repeated pixels and revisited colors exercise the run and index ops
+qoi("pattern.qoi", [pattern(x, y) for y in range(HEIGHT) for x in range(WIDTH)])
+
+
+# BMP
+
+def bmp(name, bits, rows, info, palette=b"", top_down=False):
+    stride = (WIDTH * bits + 31) // 32 * 4
+    data = b"".join(row.ljust(stride, b"\0") for row in (rows if top_down else reversed(rows)))
+    offset = 14 + len(info) + len(palette)
+    write(name, b"BM" + struct.pack("<IHHI", offset + len(data), 0, 0, offset) + info + palette + data)
+
+
+def info_header(bits, compression, top_down=False, colors=0, size=40):
+    height = -HEIGHT if top_down else HEIGHT
+    return struct.pack("<IiiHHIIiiII", size, WIDTH, height, 1, bits, compression, 0, 2835, 2835, colors, 0)
+
+
+bmp("rgb24.bmp", 24,
+    [b"".join(bytes(pattern(x, y)[2::-1]) for x in range(WIDTH)) for y in range(HEIGHT)],
+    info_header(24, 0))
+
+masks = (0x000000FF, 0x0000FF00, 0x00FF0000, 0xFF000000)
+v4 = info_header(32, 3, top_down=True, size=108) + struct.pack("<IIII", *masks) + bytes(108 - 56)
+bmp("bitfields32.bmp", 32,
+    [b"".join(struct.pack("<I", sum(c << (8 * i) for i, c in enumerate(pattern(x, y)))) for x in range(WIDTH))
+     for y in range(HEIGHT)],
+    v4, top_down=True)
+
+bmp("indexed4.bmp", 4, [pack_bits([(x + y) % 3 for x in range(WIDTH)], 4) for y in range(HEIGHT)],
+    info_header(4, 0, colors=3), palette=bytes([0, 0, 255, 0, 0, 255, 0, 0, 255, 0, 0, 0]))
+
+# deflate stream with a dynamic huffman block, for the inflate tests
+text = b"the quick brown fox jumps over the lazy dog"
+write("dynamic.zlib", zlib.compress(bytes(text[(i * i * 7 + i * 13) % len(text)] for i in range(4000)), 9))
+
+
+# Fonts
+
+GLYPHS = {
+    "A": ["..X...", ".X.X..", "X...X.", "X...X.", "XXXXX.", "X...X.", "......", "......"],
+    "\u00e9": ["...X..", "..X...", ".XXX..", "XXXXX.", "X.....", ".XXXX.", "......", "......"],
+    "j": ["....X.", "......", "....X.", "....X.", "....X.", "X...X.", ".XXX..", "......"],
+    "\ufffd": ["XXXXX.", "X...X.", "X.X.X.", "X...X.", "XXXXX.", "......", "......", "......"],
+}
+
+
+def glyph_bytes(rows, width):
+    """Rows padded to whole bytes, leftmost pixel in the top bit"""
+    return b"".join(pack_bits([int(c == "X") for c in row.ljust(width, ".")], 1) for row in rows)
+
+
+# PSF1 without a unicode table, so glyphs are in Latin-1 order
+psf1 = [bytes(8)] * 256
+for c, rows in GLYPHS.items():
+    if ord(c) < 256:
+        psf1[ord(c)] = glyph_bytes(rows, 8)
+write("font8x8.psf", bytes([0x36, 0x04, 0, 8]) + b"".join(psf1))
+
+# PSF2 with a unicode table: A also stands in for Greek capital alpha, and
+# the e acute also covers the sequence e + combining acute accent
+order = ["\ufffd", "A", "\u00e9", "j"]
+table = {"A": "A\u0391".encode(), "\u00e9": "\u00e9".encode() + b"\xfe" + "e\u0301".encode()}
+header = struct.pack("<IIIIIIII", 0x864AB572, 0, 32, 1, len(order), 8, 8, 6)
+write("font6x8.psfu", header + b"".join(glyph_bytes(GLYPHS[c], 6) for c in order)
+      + b"".join(table.get(c, c.encode()) + b"\xff" for c in order))
+
+
+def bdf_glyph(name, encoding, rows):
+    """Cropped to its ink, the way BDF files usually store glyphs"""
+    ink = [(x, y) for y, row in enumerate(rows) for x, c in enumerate(row) if c == "X"]
+    left, right = min(x for x, _ in ink), max(x for x, _ in ink)
+    top, bottom = min(y for _, y in ink), max(y for _, y in ink)
+    cropped = [row[left:right + 1] for row in rows[top:bottom + 1]]
+    width = right - left + 1
+    # the baseline is 6 rows down, and y goes up
+    lines = [f"STARTCHAR {name}", f"ENCODING {encoding}", "SWIDTH 500 0", "DWIDTH 6 0",
+             f"BBX {width} {len(cropped)} {left} {6 - bottom - 1}", "BITMAP"]
+    lines += [glyph_bytes([row], width).hex().upper() for row in cropped]
+    return lines + ["ENDCHAR"]
+
+
+bdf = ["STARTFONT 2.1", "COMMENT made by generate.py", "FONT -fixture-medium-r-normal--8-80-75-75-c-60-iso10646-1",
+       "SIZE 8 75 75", "FONTBOUNDINGBOX 6 8 0 -2", "STARTPROPERTIES 3", "FONT_ASCENT 6", "FONT_DESCENT 2",
+       "DEFAULT_CHAR 65533", "ENDPROPERTIES", "CHARS 5"]
+for c in ["A", "\u00e9", "j", "\ufffd"]:
+    bdf += bdf_glyph(f"U+{ord(c):04X}", ord(c), GLYPHS[c])
+# unencoded glyphs are skipped
+bdf += bdf_glyph("unencoded", "-1 300", GLYPHS["A"])
+write("font6x8.bdf", ("\n".join(bdf + ["ENDFONT"]) + "\n").encode())
@@ -0,0 +1,103 @@
+//! Golden-image snapshot testing. Scenes are rendered natively into a
+//! [PixelBuffer] and compared pixel for pixel with PNGs checked in under
+//! `tests/golden/`.
+//!
+//! When a snapshot doesn't match, the actual render and a diff image (red
+//! where pixels differ, the dimmed golden elsewhere) are written to
+//! `target/tmp/snapshots/`. If the change was intended, re-run with
+//! `UPDATE_SNAPSHOTS=1` to overwrite the goldens, and review them like code.
+
+use std::{env, fs, path::PathBuf};
+
+use kevinpthornecom_core::{
+    image::png,
+    pixels::{Color, PixelBuffer},
+    ui::{Renderable, Size},
+};
+
+pub const BACKGROUND: Color = Color::rgb(24, 24, 32);
+const DIFF_COLOR: Color = Color::rgb(255, 0, 0);
+
+/// Renders `elements` in order onto a fresh buffer filled with [BACKGROUND]
+pub fn render((width, height): Size, elements: &mut [Box<dyn Renderable>]) -> PixelBuffer {
+    let mut buffer = PixelBuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            buffer.set((x, y), BACKGROUND);
+        }
+    }
+    for element in elements {
+        element.render(&mut buffer);
+    }
+    buffer
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"))
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("snapshots")
+}
+
+/// Every pixel that differs is [DIFF_COLOR]; the rest is the golden image,
+/// dimmed so the differences stand out.
+fn diff_image(expected: &PixelBuffer, actual: &PixelBuffer) -> (PixelBuffer, usize) {
+    let width = expected.width.max(actual.width);
+    let height = expected.height.max(actual.height);
+    let mut diff = PixelBuffer::new(width, height);
+    let mut differing = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let (e, a) = (expected.get((x, y)), actual.get((x, y)));
+            let color = match e {
+                Some(Color { r, g, b, .. }) if e == a => {
+                    let gray = ((r as u16 + g as u16 + b as u16) / 12) as u8;
+                    Color::rgb(gray, gray, gray)
+                }
+                _ => {
+                    differing += 1;
+                    DIFF_COLOR
+                }
+            };
+            diff.set((x, y), color);
+        }
+    }
+    (diff, differing)
+}
+
+pub fn assert_snapshot(name: &str, actual: &PixelBuffer) {
+    let golden = golden_path(name);
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        fs::write(&golden, png::encode(actual)).unwrap();
+        return;
+    }
+
+    let bytes = fs::read(&golden).unwrap_or_else(|_| {
+        panic!("no golden image at {}; run with UPDATE_SNAPSHOTS=1 to create it", golden.display())
+    });
+    let expected = png::decode(&bytes).expect("golden image should decode");
+    let (diff, differing) = diff_image(&expected, actual);
+    if differing == 0 {
+        return;
+    }
+
+    let dir = output_dir();
+    fs::create_dir_all(&dir).unwrap();
+    let actual_path = dir.join(format!("{name}.actual.png"));
+    let diff_path = dir.join(format!("{name}.diff.png"));
+    fs::write(&actual_path, png::encode(actual)).unwrap();
+    fs::write(&diff_path, png::encode(&diff)).unwrap();
+    panic!(
+        "snapshot '{}' differs in {} pixels ({}x{} vs golden {}x{})\n  actual: {}\n  diff:   {}",
+        name,
+        differing,
+        actual.width,
+        actual.height,
+        expected.width,
+        expected.height,
+        actual_path.display(),
+        diff_path.display(),
+    );
+}
@@ -0,0 +1,318 @@
+//! Renders UI scenes natively and checks them against golden images.
+//! See tests/snapshot/mod.rs for how to update the goldens.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+mod snapshot;
+
+use std::rc::Rc;
+
+use kevinpthornecom_core::{
+    font::{self, Font},
+    gradient::{Gradient, Spread},
+    image::{self, Image},
+    palette::{Dither, IndexedBuffer, Palette, PICO_8},
+    paragraph::{Align, Paragraph},
+    postfx::{Bloom, ChromaticOffset, Filter, FilterChain, Pixelate, Scanlines, Vignette},
+    pixels::{BlendMode, Color, PixelBuffer, Sampling, BLACK, BLUE, GREEN, RED, WHITE},
+    rich::{RichText, Style},
+    shapes::{Circle, Ellipse, Line, Polygon, RoundedRectangle},
+    transform::Transform,
+    ui::{Button, Clipped, Gesture, GestureHandler, HCenter, Positioned, Rectangle, Renderable, Text, Transformed},
+};
+use snapshot::{assert_snapshot, render};
+
+fn font() -> Rc<Font> {
+    Rc::new(Font::builtin())
+}
+
+fn text(text: &str, scale: usize, color: Color) -> Box<Text> {
+    Box::new(Text::new(text.to_string(), &font(), scale, color))
+}
+
+#[test]
+fn title() {
+    let buffer = render((340, 40), &mut [Box::new(HCenter::new((0, 10), text("KEVIN P. THORNE", 4, WHITE)))]);
+    assert_snapshot("title", &buffer);
+}
+
+#[test]
+fn text_scales() {
+    let pangram = "THE QUICK, BROWN FOX JUMPS OVER THE LAZY DOG.";
+    let buffer = render(
+        (420, 70),
+        &mut [
+            Box::new(Positioned::new((2, 2), text(pangram, 1, GREEN))),
+            Box::new(Positioned::new((2, 12), text(pangram, 2, RED))),
+            Box::new(Positioned::new((2, 27), text("0123456789 -.,", 3, BLUE))),
+            Box::new(Positioned::new((2, 47), text("!?", 3, WHITE))),
+        ],
+    );
+    assert_snapshot("text_scales", &buffer);
+}
+
+#[test]
+fn paragraphs() {
+    let prose = "Pixels are drawn by hand, one span at a time.\nNothing here touches the DOM; supercalifragilisticexpialidocious words get hyphens.";
+    let paragraph = |align: Align| Paragraph::new().with_max_width(150).with_line_spacing(2).with_align(align);
+    let column = |align: Align| Box::new(Text::new(prose.to_string(), &font(), 1, WHITE).with_paragraph(paragraph(align)));
+    let buffer = render(
+        (330, 140),
+        &mut [
+            Box::new(Positioned::new((4, 4), column(Align::Left))),
+            Box::new(Positioned::new((170, 4), column(Align::Right))),
+            Box::new(Positioned::new((4, 74), column(Align::Center))),
+            Box::new(Positioned::new((170, 74), column(Align::Justify))),
+        ],
+    );
+    assert_snapshot("paragraphs", &buffer);
+}
+
+#[test]
+fn rich_text() {
+    let markup = "[shadow=#08f]SHADOW[/shadow] [outline=#f0f]OUTLINE[/outline] [b]BOLD[/b]\n\
+                  [u]UNDERLINED[/u] [s]STRUCK[/s] [color=#f80][scale=3]BIG[/scale] [u]SMALL[/u][/color]";
+    let text = RichText::parse(markup, Style::new(WHITE, 2)).unwrap();
+    let buffer = render(
+        (320, 50),
+        &mut [Box::new(Positioned::new((4, 4), Box::new(Text::rich(text, &font()).with_paragraph(Paragraph::new().with_line_spacing(3)))))],
+    );
+    assert_snapshot("rich_text", &buffer);
+}
+
+#[test]
+fn printable_ascii() {
+    // monospaced, so the glyphs line up in a chart
+    let mono = Rc::new(Font::builtin().with_monospace(true));
+    let ascii: String = (' '..='~').collect();
+    let mut lines: Vec<Box<dyn Renderable>> = vec![];
+    for (i, line) in ascii.as_bytes().chunks(32).enumerate() {
+        let line = core::str::from_utf8(line).unwrap();
+        lines.push(Box::new(Positioned::new((2, 2 + i * 14), Box::new(Text::new(line.to_string(), &mono, 2, WHITE)))));
+    }
+    let buffer = render((390, 44), &mut lines);
+    assert_snapshot("printable_ascii", &buffer);
+}
+
+#[test]
+fn loaded_fonts() {
+    let bdf = Rc::new(font::decode(include_bytes!("fixtures/font6x8.bdf")).unwrap());
+    let psf = Rc::new(font::decode(include_bytes!("fixtures/font6x8.psfu")).unwrap());
+    let buffer = render(
+        (80, 40),
+        &mut [
+            Box::new(Positioned::new((2, 2), Box::new(Text::new("Aj\u{e9}?".to_string(), &bdf, 2, WHITE)))),
+            Box::new(Positioned::new((2, 22), Box::new(Text::new("A\u{391}j?".to_string(), &psf, 2, GREEN)))),
+        ],
+    );
+    assert_snapshot("loaded_fonts", &buffer);
+}
+
+#[test]
+fn buttons() {
+    let mut pressed = Button::new((100, 10), "PRESSED".to_string(), &font(), 2, Color::rgb(120, 120, 120), WHITE);
+    pressed.on_event(Gesture::MouseDown);
+    let buffer = render(
+        (200, 40),
+        &mut [
+            Box::new(Button::new((10, 10), "BOOP".to_string(), &font(), 2, Color::rgb(120, 120, 120), WHITE)),
+            Box::new(pressed),
+        ],
+    );
+    assert_snapshot("buttons", &buffer);
+}
+
+#[test]
+fn shapes() {
+    let buffer = render(
+        (160, 60),
+        &mut [
+            Box::new(Positioned::new((5, 5), Box::new(Line::new((0, 0), (30, 12), WHITE)))),
+            Box::new(Positioned::new((5, 20), Box::new(Line::new((0, 12), (30, 0), WHITE).anti_aliased()))),
+            Box::new(Positioned::new((40, 5), Box::new(Circle::new(10, GREEN)))),
+            Box::new(Positioned::new((40, 30), Box::new(Ellipse::filled((12, 8), RED)))),
+            Box::new(Positioned::new((70, 5), Box::new(Polygon::filled(vec![(0, 20), (12, 0), (24, 20)], BLUE)))),
+            Box::new(Positioned::new((70, 30), Box::new(Polygon::new(vec![(0, 0), (24, 4), (10, 20)], WHITE)))),
+            Box::new(Positioned::new((100, 5), Box::new(RoundedRectangle::filled((50, 20), 6, Color::rgb(200, 120, 40))))),
+            Box::new(Positioned::new((100, 32), Box::new(RoundedRectangle::new((50, 20), 4, WHITE)))),
+        ],
+    );
+    assert_snapshot("shapes", &buffer);
+}
+
+#[test]
+fn blend_modes() {
+    let modes = [BlendMode::Replace, BlendMode::Over, BlendMode::Multiply, BlendMode::Screen, BlendMode::Additive];
+    let mut elements: Vec<Box<dyn Renderable>> = vec![];
+    for (i, mode) in modes.iter().enumerate() {
+        let x = 5 + i * 30;
+        elements.push(Box::new(Positioned::new((x, 5), Box::new(Rectangle::new((20, 20), Color::rgb(200, 60, 60))))));
+        elements.push(Box::new(Positioned::new(
+            (x + 8, 13),
+            Box::new(Rectangle::new((20, 20), Color::new(60, 120, 220, 160)).with_blend_mode(*mode)),
+        )));
+    }
+    let buffer = render((155, 40), &mut elements);
+    assert_snapshot("blend_modes", &buffer);
+}
+
+#[test]
+fn clipped_text() {
+    let buffer = render(
+        (120, 20),
+        &mut [
+            Box::new(Positioned::new((5, 5), Box::new(Clipped::new((60, 10), text("TRUNCATED LABEL", 2, WHITE))))),
+            Box::new(Positioned::new((5, 5), Box::new(Rectangle::new((1, 10), RED)))),
+            Box::new(Positioned::new((65, 5), Box::new(Rectangle::new((1, 10), RED)))),
+        ],
+    );
+    assert_snapshot("clipped_text", &buffer);
+}
+
+#[test]
+fn images() {
+    let bitmap = || image::decode(include_bytes!("fixtures/rgba8.png")).unwrap();
+    let buffer = render(
+        (100, 40),
+        &mut [
+            Box::new(Positioned::new((2, 2), Box::new(Image::new(bitmap())))),
+            Box::new(Positioned::new((20, 2), Box::new(Image::new(bitmap()).with_size((39, 27))))),
+            Box::new(Positioned::new(
+                (62, 2),
+                Box::new(Image::new(bitmap()).with_size((36, 36)).with_sampling(Sampling::Bilinear).with_alpha(200)),
+            )),
+        ],
+    );
+    assert_snapshot("images", &buffer);
+}
+
+#[test]
+fn transforms() {
+    let bitmap = image::decode(include_bytes!("fixtures/rgba8.png")).unwrap();
+    let spin = |radians: f32, (width, height): (usize, usize)| {
+        Transform::rotate(radians).around((width as f32 / 2.0, height as f32 / 2.0))
+    };
+    let buffer = render(
+        (120, 80),
+        &mut [
+            Box::new(Positioned::new((4, 4), Box::new(Transformed::new(Transform::scale(1.5, 1.5), text("ZOOM", 1, WHITE))))),
+            Box::new(Positioned::new((114, 4), Box::new(Transformed::new(Transform::quarter_turns(1), text("UP", 2, GREEN))))),
+            Box::new(Positioned::new(
+                (10, 30),
+                Box::new(Transformed::new(
+                    spin(0.4, (39, 27)),
+                    Box::new(Image::new(bitmap).with_size((39, 27)).with_sampling(Sampling::Bilinear)),
+                )),
+            )),
+            // nested: moved, then spun about its own center
+            Box::new(Positioned::new(
+                (70, 40),
+                Box::new(Transformed::new(
+                    Transform::translate(5.0, 5.0),
+                    Box::new(Transformed::new(spin(0.8, (20, 20)), Box::new(Rectangle::new((20, 20), RED)))),
+                )),
+            )),
+            Box::new(Positioned::new(
+                (60, 10),
+                Box::new(Transformed::new(
+                    Transform::scale(2.0, 1.0).rotated(-0.3),
+                    Box::new(Circle::new(6, Color::rgb(255, 200, 0))),
+                )),
+            )),
+        ],
+    );
+    assert_snapshot("transforms", &buffer);
+}
+
+#[test]
+fn gradients() {
+    let sunset = || {
+        Gradient::linear_across((60, 30), 0.5)
+            .with_stop(0.0, Color::rgb(40, 0, 80))
+            .with_stop(0.6, Color::rgb(230, 80, 60))
+            .with_stop(1.0, Color::rgb(255, 220, 100))
+    };
+    let rainbow = Gradient::conic((15.0, 15.0), 0.0)
+        .with_stop(0.0, RED)
+        .with_stop(1.0 / 3.0, GREEN)
+        .with_stop(2.0 / 3.0, BLUE)
+        .with_stop(1.0, RED);
+    let glow = Gradient::radial((12.0, 12.0), 12.0).with_stop(0.0, WHITE).with_stop(1.0, Color::new(255, 200, 0, 0));
+    let stripes = Gradient::linear((0.0, 0.0), (8.0, 8.0))
+        .with_stop(0.0, WHITE)
+        .with_stop(0.5, WHITE)
+        .with_stop(0.5, BLACK)
+        .with_stop(1.0, BLACK)
+        .with_spread(Spread::Repeat);
+    let mut scrolled = Rectangle::new((30, 30), WHITE).with_gradient(stripes.clone().scrolling(0.25));
+    scrolled.gradient_mut().unwrap().set_tick(1);
+    let buffer = render(
+        (200, 80),
+        &mut [
+            Box::new(Positioned::new((5, 5), Box::new(Rectangle::new((60, 30), WHITE).with_gradient(sunset())))),
+            Box::new(Positioned::new((70, 5), Box::new(Circle::filled(15, WHITE).with_gradient(rainbow)))),
+            Box::new(Positioned::new((105, 8), Box::new(Circle::filled(12, WHITE).with_gradient(glow)))),
+            Box::new(Positioned::new((135, 5), Box::new(Rectangle::new((30, 30), WHITE).with_gradient(stripes)))),
+            Box::new(Positioned::new((168, 5), Box::new(scrolled))),
+            Box::new(Positioned::new(
+                (5, 45),
+                Box::new(Transformed::new(
+                    Transform::rotate(0.3).around((30.0, 12.0)),
+                    Box::new(RoundedRectangle::filled((60, 25), 8, WHITE).with_gradient(sunset())),
+                )),
+            )),
+            Box::new(Button::new((80, 45), "HERO".to_string(), &font(), 2, WHITE, WHITE).with_gradient(sunset())),
+        ],
+    );
+    assert_snapshot("gradients", &buffer);
+}
+
+#[test]
+fn dithering() {
+    // a hue sweep over a gray ramp, quantized three ways
+    let mut source = PixelBuffer::new(96, 24);
+    for y in 0..24 {
+        for x in 0..96 {
+            let color = Color::from_hsl(x as f32 * 3.75, 0.8, 0.5).lerp(Color::rgb(0, 0, 0), y as f32 / 30.0);
+            source.set((x, y), color);
+        }
+    }
+    let mut indexed = IndexedBuffer::new(96, 72, Palette::new(PICO_8.to_vec()));
+    for (i, dither) in [Dither::None, Dither::Ordered, Dither::FloydSteinberg].iter().enumerate() {
+        indexed.quantize(&source, ((0, 0), (95, 23)), (0, i * 24), *dither);
+    }
+    let mut buffer = PixelBuffer::new(96, 72);
+    indexed.resolve(&mut buffer);
+    assert_snapshot("dithering", &buffer);
+}
+
+#[test]
+fn post_processing() {
+    let scene = || {
+        render(
+            (120, 48),
+            &mut [
+                Box::new(Positioned::new((6, 6), text("CRT", 4, WHITE))),
+                Box::new(Positioned::new((80, 8), Box::new(Circle::filled(12, Color::rgb(255, 160, 40))))),
+                Box::new(Positioned::new((6, 34), text("SCANLINES", 1, GREEN))),
+            ],
+        )
+    };
+    let filters: Vec<(&str, Box<dyn Filter>)> = vec![
+        ("scanlines", Box::new(Scanlines::default())),
+        ("bloom", Box::new(Bloom::default())),
+        ("vignette", Box::new(Vignette::default())),
+        ("chromatic", Box::new(ChromaticOffset { offset: 2 })),
+        ("pixelate", Box::new(Pixelate { size: 3 })),
+    ];
+    for (name, filter) in filters {
+        let mut chain = FilterChain::new().with(name, filter);
+        let mut output = PixelBuffer::new(120, 48);
+        chain.process(&scene(), &mut output);
+        assert_snapshot(&format!("postfx_{}", name), &output);
+    }
+
+    let mut output = PixelBuffer::new(120, 48);
+    FilterChain::crt().process(&scene(), &mut output);
+    assert_snapshot("postfx_crt", &output);
+}
@@ -1,4 +1,11 @@
-import * as wasm from "kevinpthornecom_wasm";
+// A module with one function using a SIMD instruction. Only browsers with
+// wasm SIMD consider it valid, and the others get the scalar build.
+const SIMD_PROBE = new Uint8Array([
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 10, 1, 8, 0, 65, 0, 253, 15, 253, 98, 11,
+]);
+const wasm = await (WebAssembly.validate(SIMD_PROBE)
+    ? import("kevinpthornecom_wasm_simd")
+    : import("kevinpthornecom_wasm"));
 
 document.body.setAttribute(
     "style", 
@@ -7,11 +14,10 @@ document.body.setAttribute(
 
 var canvasElement = document.createElement("canvas");
 canvasElement.id = "theCanvas";
-canvasElement.width = window.innerWidth;
-canvasElement.height = window.innerHeight;
+// the app sizes the canvas's backing store in device pixels itself
 canvasElement.setAttribute(
     "style",
-    "display: block;"
+    "display: block; width: 100vw; height: 100vh;"
 );
 document.body.appendChild(canvasElement);
 
@@ -26,8 +32,6 @@ canvasElement.onmouseup = (event) => {
     app.on_click(event);
 };
 onresize = (event) => {
-    canvasElement.width = window.innerWidth;
-    canvasElement.height = window.innerHeight;
     app.on_resize();
 };
 function render(timeStamp) {
@@ -7,6 +7,12 @@ module.exports = {
   experiments: {
     asyncWebAssembly: true,
   },
+  resolve: {
+    alias: {
+      // the same bindings built with wasm SIMD, see src/index.js
+      kevinpthornecom_wasm_simd: path.resolve(__dirname, '../wasm/pkg-simd'),
+    },
+  },
   plugins: [
     new HtmlWebpackPlugin({
       title: 'Kevin P. Thorne',
@@ -0,0 +1,237 @@
+//! Runs the site natively: the same [App] the browser hosts, driven by a
+//! script of input events instead of a person, with every frame written to
+//! disk and optionally previewed in the terminal.
+//!
+//! ```text
+//! cargo run -p kevinpthornecom_runner -- --script runner/scripts/boop.txt --preview
+//! ```
+
+mod preview;
+mod script;
+
+use std::{env, fs, io, io::Write, path::PathBuf, process};
+
+use kevinpthornecom_core::{
+    app::{App, FPS},
+    pixels::PixelBuffer,
+    present::{FileSequencePresenter, FrameFormat, Presenter},
+    tiles::Threads,
+    ui::{Gesture, Rect, Size},
+    viewport::Resolution,
+};
+use script::{parse_resolution, parse_script, Command};
+
+const USAGE: &str = "\
+usage: runner [options]
+
+  --script <file>         input to play back; see runner/src/script.rs
+  --frames <count>        frames to render when there's no script [1]
+  --size <width>x<height> screen size in CSS pixels [640x360]
+  --dpr <ratio>           device pixel ratio [1]
+  --resolution <res>      native, scale:<n> or height:<n> [the site's default]
+  --out <dir>             where to write frames [target/frames]
+  --ppm                   write PPMs instead of PNGs
+  --preview [columns]     draw each frame in the terminal [80]
+  --threads <count>       threads to rasterize with [one per core]";
+
+/// Just over a frame's worth, so every step renders
+const FRAME_MILLIS: u32 = 1000 / FPS + 1;
+
+struct Options {
+    script: Option<PathBuf>,
+    frames: usize,
+    size: (f64, f64),
+    device_pixel_ratio: f64,
+    resolution: Option<Resolution>,
+    out: PathBuf,
+    format: FrameFormat,
+    preview: Option<usize>,
+    /// One per core if unset
+    threads: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            script: None,
+            frames: 1,
+            size: (640.0, 360.0),
+            device_pixel_ratio: 1.0,
+            resolution: None,
+            out: PathBuf::from("target/frames"),
+            format: FrameFormat::Png,
+            preview: None,
+            threads: None,
+        }
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--script" => options.script = Some(PathBuf::from(value("--script")?)),
+            "--frames" => {
+                let frames = value("--frames")?;
+                options.frames = frames.parse().map_err(|_| format!("invalid frame count {:?}", frames))?;
+            }
+            "--size" => {
+                let size = value("--size")?;
+                let parsed = size.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                options.size = parsed.ok_or_else(|| format!("invalid size {:?}, expected e.g. 640x360", size))?;
+            }
+            "--dpr" => {
+                let dpr = value("--dpr")?;
+                options.device_pixel_ratio = dpr.parse().map_err(|_| format!("invalid device pixel ratio {:?}", dpr))?;
+            }
+            "--resolution" => options.resolution = Some(parse_resolution(&value("--resolution")?)?),
+            "--out" => options.out = PathBuf::from(value("--out")?),
+            "--ppm" => options.format = FrameFormat::Ppm,
+            "--preview" => {
+                // the width is optional
+                let columns = args.next_if(|next| !next.starts_with("--"));
+                options.preview = Some(match columns {
+                    Some(columns) => columns.parse().map_err(|_| format!("invalid column count {:?}", columns))?,
+                    None => 80,
+                });
+            }
+            "--threads" => {
+                let threads = value("--threads")?;
+                let count = threads.parse().ok().filter(|&count| count > 0);
+                options.threads = Some(count.ok_or_else(|| format!("invalid thread count {:?}", threads))?);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {:?}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// Writes frames to disk, and draws them in the terminal too if asked
+struct Output {
+    files: FileSequencePresenter,
+    preview: Option<usize>,
+}
+
+impl Presenter for Output {
+    type Error = io::Error;
+
+    fn resize(&mut self, size: Size) -> io::Result<()> {
+        self.files.resize(size)
+    }
+
+    fn present(&mut self, frame: &PixelBuffer, dirty: &[Rect]) -> io::Result<()> {
+        self.files.present(frame, dirty)?;
+        if let Some(columns) = self.preview {
+            let mut stdout = io::stdout().lock();
+            // home the cursor, so frames draw over each other
+            write!(stdout, "\x1b[H\x1b[2J{}", preview::half_blocks(self.files.frame(), columns))?;
+            writeln!(stdout, "frame {}", self.files.frames() - 1)?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays `commands` against `app`, keeping its clock ticking one frame per
+/// step
+struct Runner {
+    app: App,
+    output: Output,
+    time: u32,
+}
+
+impl Runner {
+    fn frames(&mut self, count: usize) -> io::Result<()> {
+        for _ in 0..count {
+            self.time += FRAME_MILLIS;
+            self.app.on_frame(self.time, &mut self.output)?;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::Frame(count) => self.frames(count).map_err(|err| err.to_string())?,
+            Command::Down(point) => {
+                self.app.on_gesture(point, Gesture::MouseDown);
+            }
+            Command::Up(point) => {
+                self.app.on_gesture(point, Gesture::MouseUp);
+            }
+            Command::Click(point) => {
+                self.app.on_gesture(point, Gesture::MouseDown);
+                self.frames(1).map_err(|err| err.to_string())?;
+                self.app.on_gesture(point, Gesture::MouseUp);
+            }
+            Command::Resize(size, dpr) => self.app.resize(size, dpr),
+            Command::Resolution(resolution) => self.app.set_resolution(resolution),
+            Command::Crt(enabled) => self.app.set_crt_effect(enabled),
+            Command::Filter(name, enabled) => {
+                if !self.app.set_filter_enabled(&name, enabled) {
+                    return Err(format!("no filter called {:?}", name));
+                }
+            }
+            Command::Param(filter, param, value) => {
+                if !self.app.set_filter_param(&filter, &param, value) {
+                    return Err(format!("no parameter {:?} on filter {:?}", param, filter));
+                }
+            }
+            Command::Palette(colors) => self.app.set_palette(&colors).map_err(|err| err.to_string())?,
+            Command::Layer(name, visible) => {
+                if !self.app.set_layer_visible(&name, visible) {
+                    return Err(format!("no layer called {:?}", name));
+                }
+            }
+            Command::Opacity(name, opacity) => {
+                if !self.app.set_layer_opacity(&name, opacity) {
+                    return Err(format!("no layer called {:?}", name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("runner: {}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("runner: {}\n\n{}", err, USAGE);
+        process::exit(2);
+    });
+    let commands = match &options.script {
+        Some(path) => {
+            let script = fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)));
+            parse_script(&script).unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)))
+        }
+        None => vec![Command::Frame(options.frames)],
+    };
+
+    let mut app = App::new(options.size, options.device_pixel_ratio);
+    if let Some(resolution) = options.resolution {
+        app.set_resolution(resolution);
+    }
+    app.set_tile_executor(Box::new(options.threads.map_or_else(Threads::available, Threads::new)));
+    let files = FileSequencePresenter::new(&options.out, options.format).unwrap_or_else(|err| fail(err));
+    let mut runner = Runner {
+        app,
+        output: Output {
+            files,
+            preview: options.preview,
+        },
+        time: 0,
+    };
+    for command in commands {
+        runner.run(command).unwrap_or_else(|err| fail(err));
+    }
+    eprintln!("wrote {} frames to {}", runner.output.files.frames(), options.out.display());
+}
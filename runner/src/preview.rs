@@ -0,0 +1,59 @@
+//! Draws frames in a truecolor terminal using half blocks: each character
+//! cell is two pixels tall, the top one in the foreground color and the
+//! bottom one in the background color.
+
+use std::fmt::Write;
+
+use kevinpthornecom_core::pixels::{Color, PixelBuffer};
+
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// `frame` scaled (nearest neighbour) to `columns` characters wide, keeping
+/// its aspect ratio. Ends with the colors reset.
+pub fn half_blocks(frame: &PixelBuffer, columns: usize) -> String {
+    let mut out = String::new();
+    if frame.width == 0 || frame.height == 0 || columns == 0 {
+        return out;
+    }
+    let rows = (frame.height * columns).div_ceil(frame.width * 2).max(1);
+    let sample = |column: usize, pixel_row: usize| -> Color {
+        let x = column * frame.width / columns;
+        let y = (pixel_row * frame.height / (rows * 2)).min(frame.height - 1);
+        frame.get((x, y)).unwrap_or_default()
+    };
+    for row in 0..rows {
+        for column in 0..columns {
+            let (top, bottom) = (sample(column, row * 2), sample(column, row * 2 + 1));
+            let _ = write!(
+                out,
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m{}",
+                top.r, top.g, top.b, bottom.r, bottom.g, bottom.b, UPPER_HALF_BLOCK
+            );
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kevinpthornecom_core::pixels::{RED, WHITE};
+
+    #[test]
+    fn test_half_blocks() {
+        let mut frame = PixelBuffer::new(4, 4);
+        // top half white, bottom half red
+        for y in 0..4 {
+            for x in 0..4 {
+                frame.set((x, y), if y < 2 { WHITE } else { RED });
+            }
+        }
+        let preview = half_blocks(&frame, 2);
+        let lines: Vec<_> = preview.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].matches(UPPER_HALF_BLOCK).count(), 2);
+        assert!(lines[0].starts_with("\x1b[38;2;255;255;255m\x1b[48;2;255;0;0m\u{2580}"));
+        assert!(lines[0].ends_with("\x1b[0m"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Scripted input for the runner. One command per line; blank lines and
+//! lines starting with `#` are ignored. Points are in CSS pixels, like the
+//! browser's events.
+//!
+//! ```text
+//! frame [count]                     render `count` frames (default 1)
+//! down <x> <y>                      press the mouse
+//! up <x> <y>                        release the mouse
+//! click <x> <y>                     press, render a frame, release
+//! resize <width> <height> [dpr]     resize the screen, in CSS pixels
+//! resolution <resolution>           `native`, `scale:<n>` or `height:<n>`
+//! crt on|off                        toggle the CRT preset
+//! filter <name> on|off              toggle one filter
+//! param <filter> <param> <value>    set a filter parameter
+//! palette [colors...]               indexed color, or full color if empty
+//! layer <name> on|off               show or hide a layer
+//! opacity <name> <0-255>            set a layer's opacity
+//! ```
+
+use std::{error::Error, fmt, str::{FromStr, SplitWhitespace}};
+
+use kevinpthornecom_core::viewport::Resolution;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Frame(usize),
+    Down((f64, f64)),
+    Up((f64, f64)),
+    Click((f64, f64)),
+    Resize((f64, f64), f64),
+    Resolution(Resolution),
+    Crt(bool),
+    Filter(String, bool),
+    Param(String, String, f32),
+    Palette(String),
+    Layer(String, bool),
+    Opacity(String, u8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// 1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+/// `native`, `scale:<n>` or `height:<n>`
+pub fn parse_resolution(s: &str) -> Result<Resolution, String> {
+    let (kind, value) = match s.split_once(':') {
+        Some((kind, value)) => (kind, Some(value)),
+        None => (s, None),
+    };
+    let number = || -> Result<usize, String> {
+        let value = value.ok_or_else(|| format!("{} needs a value, e.g. {}:2", kind, kind))?;
+        value.parse().map_err(|_| format!("invalid number {:?}", value))
+    };
+    match kind {
+        "native" if value.is_none() => Ok(Resolution::Native),
+        "scale" => Ok(Resolution::Scale(number()?)),
+        "height" => Ok(Resolution::Height(number()?)),
+        _ => Err(format!("unknown resolution {:?}", s)),
+    }
+}
+
+fn parse<T: FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing {}", what))?;
+    arg.parse().map_err(|_| format!("invalid {} {:?}", what, arg))
+}
+
+fn parse_switch(arg: Option<&str>) -> Result<bool, String> {
+    match arg {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err("expected on or off".to_string()),
+    }
+}
+
+fn parse_point(args: &mut SplitWhitespace) -> Result<(f64, f64), String> {
+    Ok((parse(args.next(), "x")?, parse(args.next(), "y")?))
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut args = line.split_whitespace();
+    let name = args.next().unwrap_or_default();
+    let command = match name {
+        "frame" => Command::Frame(match args.next() {
+            Some(count) => parse(Some(count), "count")?,
+            None => 1,
+        }),
+        "down" => Command::Down(parse_point(&mut args)?),
+        "up" => Command::Up(parse_point(&mut args)?),
+        "click" => Command::Click(parse_point(&mut args)?),
+        "resize" => {
+            let size = (parse(args.next(), "width")?, parse(args.next(), "height")?);
+            let dpr = match args.next() {
+                Some(dpr) => parse(Some(dpr), "device pixel ratio")?,
+                None => 1.0,
+            };
+            Command::Resize(size, dpr)
+        }
+        "resolution" => Command::Resolution(parse_resolution(args.next().ok_or("missing resolution")?)?),
+        "crt" => Command::Crt(parse_switch(args.next())?),
+        "filter" => Command::Filter(parse(args.next(), "filter")?, parse_switch(args.next())?),
+        "param" => Command::Param(parse(args.next(), "filter")?, parse(args.next(), "param")?, parse(args.next(), "value")?),
+        "layer" => Command::Layer(parse(args.next(), "layer")?, parse_switch(args.next())?),
+        "opacity" => Command::Opacity(parse(args.next(), "layer")?, parse(args.next(), "opacity")?),
+        // everything else on the line is colors
+        "palette" => return Ok(Command::Palette(args.collect::<Vec<_>>().join(" "))),
+        _ => return Err(format!("unknown command {:?}", name)),
+    };
+    match args.next() {
+        Some(extra) => Err(format!("unexpected {:?}", extra)),
+        None => Ok(command),
+    }
+}
+
+pub fn parse_script(script: &str) -> Result<Vec<Command>, ScriptError> {
+    let mut commands = vec![];
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let command = parse_command(line).map_err(|message| ScriptError { line: i + 1, message })?;
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = "
+            # press BOOP
+            resize 640 360 2
+            resolution scale:2
+            down 15 195
+            frame 3
+            up 15.5 195
+            palette #000 #fff
+            filter bloom off
+            param bloom strength 0.5
+            layer hud off
+            opacity content 128
+        ";
+        assert_eq!(
+            parse_script(script),
+            Ok(vec![
+                Command::Resize((640.0, 360.0), 2.0),
+                Command::Resolution(Resolution::Scale(2)),
+                Command::Down((15.0, 195.0)),
+                Command::Frame(3),
+                Command::Up((15.5, 195.0)),
+                Command::Palette("#000 #fff".to_string()),
+                Command::Filter("bloom".to_string(), false),
+                Command::Param("bloom".to_string(), "strength".to_string(), 0.5),
+                Command::Layer("hud".to_string(), false),
+                Command::Opacity("content".to_string(), 128),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let error = |script: &str| parse_script(script).unwrap_err();
+        assert_eq!(error("frame\nclick 1").to_string(), "line 2: missing y");
+        assert_eq!(error("jump").message, "unknown command \"jump\"");
+        assert_eq!(error("frame 1 2").message, "unexpected \"2\"");
+        assert_eq!(error("crt maybe").message, "expected on or off");
+        assert_eq!(error("opacity hud 300").message, "invalid opacity \"300\"");
+        assert_eq!(error("resolution height").message, "height needs a value, e.g. height:2");
+    }
+}
@@ -1,24 +1,16 @@
-mod bitset;
-mod pixels;
-mod font;
-mod ui;
+//! Browser bindings for the renderer in `kevinpthornecom_core`: a
+//! [CanvasApp] JS can construct on a canvas, and translation of DOM events
+//! into the core's types.
+
 mod utils;
 
-use font::GLYPH_SIZE;
-use pixels::PixelBuffer;
-use pixels::GREEN;
-use pixels::RED;
-use pixels::BLUE;
-use pixels::WHITE;
-use ui::is_point_in_rect;
-use ui::Button;
-use ui::Gesture;
-use ui::GestureHandler;
-use ui::HCenter;
-use ui::Positioned;
-use ui::Rectangle;
-use ui::Renderable;
-use ui::Text;
+use kevinpthornecom_core::app::App;
+use kevinpthornecom_core::pixels::{PixelBuffer, NUM_CHANNELS};
+use kevinpthornecom_core::present::Presenter;
+use kevinpthornecom_core::ui::Gesture;
+use kevinpthornecom_core::ui::Rect;
+use kevinpthornecom_core::ui::Size;
+use kevinpthornecom_core::viewport::Resolution;
 use wasm_bindgen::prelude::*;
 use web_sys::window;
 use web_sys::MouseEvent;
@@ -45,125 +37,164 @@ pub fn greet() {
     alert("Hello, kevinpthornecom_wasm!!!");
 }
 
-const FPS: u32 = 15;
+fn gesture_of(event: &MouseEvent) -> Option<Gesture> {
+    match event.type_().as_str() {
+        "mousedown" => Some(Gesture::MouseDown),
+        "mouseup" => Some(Gesture::MouseUp),
+        _ => None,
+    }
+}
+
+/// The browser window's size in CSS pixels, and its device pixel ratio
+fn window_size() -> ((f64, f64), f64) {
+    let window = window().unwrap();
+    let width = window.inner_width().unwrap().as_f64().unwrap();
+    let height = window.inner_height().unwrap().as_f64().unwrap();
+    ((width, height), window.device_pixel_ratio())
+}
+
+/// Draws frames onto a canvas through its 2D context
+pub struct Canvas2dPresenter {
+    canvas: web_sys::HtmlCanvasElement,
+    /// The pixels of one dirty rect at a time, kept to save reallocating
+    scratch: Vec<u8>,
+}
+
+impl Canvas2dPresenter {
+    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Self {
+        Self { canvas, scratch: Vec::new() }
+    }
+}
+
+impl Presenter for Canvas2dPresenter {
+    type Error = JsValue;
+
+    fn resize(&mut self, (width, height): Size) -> Result<(), JsValue> {
+        self.canvas.set_width(width as u32);
+        self.canvas.set_height(height as u32);
+        Ok(())
+    }
+
+    fn present(&mut self, frame: &PixelBuffer, dirty: &[Rect]) -> Result<(), JsValue> {
+        let context = self
+            .canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("canvas has no 2d context"))?
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+        let row_len = frame.width * NUM_CHANNELS;
+        // only the dirty pixels cross over to JS, one small ImageData per rect
+        for &((left, top), (right, bottom)) in dirty {
+            let width = right - left + 1;
+            self.scratch.clear();
+            for row in frame.data_as_ref().chunks_exact(row_len).take(bottom + 1).skip(top) {
+                self.scratch.extend_from_slice(&row[left * NUM_CHANNELS..(right + 1) * NUM_CHANNELS]);
+            }
+            let clamped_data = wasm_bindgen::Clamped(&self.scratch[..]);
+            let image_data = web_sys::ImageData::new_with_u8_clamped_array(clamped_data, width as u32)?;
+            context.put_image_data(&image_data, left as f64, top as f64)?;
+        }
+        Ok(())
+    }
+}
 
 #[wasm_bindgen]
 pub struct CanvasApp {
-    canvas: web_sys::HtmlCanvasElement,
-    screenbuff: PixelBuffer,
-    tick: usize,
-    last_frame_time: u32,
-    ui_elements: Vec<Box<dyn Renderable>>,
-    // todo fix
-    gesturehandler_ui_elements: Vec<Box<dyn GestureHandler>>,
+    app: App,
+    presenter: Canvas2dPresenter,
 }
 
 #[wasm_bindgen]
 impl CanvasApp {
     pub fn new(canvas: web_sys::HtmlCanvasElement) -> Result<CanvasApp, JsValue> {
-        let width = window().unwrap().inner_width().unwrap().as_f64().unwrap() as usize;
-        let height = window().unwrap().inner_height().unwrap().as_f64().unwrap() as usize;
-        
-        // TODO rip this out of the constructor
-        let test_text: String = "ABCDEFGHIJKLMNOPQRSTUVWXYZ 0123456789 !@#$%^&*()[]{}\\|;':\",./<>?-=_+`~".to_string();
-        let test_2: String = "THE QUICK, BROWN FOX JUMPS OVER THE LAZY DOG.".to_string();
+        let (css_size, device_pixel_ratio) = window_size();
         let instance = Self {
-            canvas,
-            screenbuff: PixelBuffer::new(width, height),
-            tick: 0,
-            last_frame_time: 0,
-            ui_elements: vec![
-                Box::new(HCenter::new((0, 10), Box::new(Text::new("KEVIN P. THORNE".to_string(), 4, WHITE)))),
-                Box::new(Positioned::new((0, 100), Box::new(Text::new(test_text.clone().to_string(), 1, GREEN)))),
-                Box::new(Positioned::new((0, 110), Box::new(Text::new(test_text.clone().to_string(), 2, RED)))),
-                Box::new(Positioned::new((0, 125), Box::new(Text::new(test_text.clone().to_string(), 3, BLUE)))),
-                Box::new(Positioned::new((0, 145), Box::new(Text::new(test_text.clone().to_string(), 4, GREEN)))),
-                Box::new(Positioned::new((0, 170), Box::new(Text::new(test_2.clone().to_string(), 3, GREEN)))),
-                Box::new(Positioned::new((10, 220), Box::new(Rectangle::new((10, 10), WHITE)))),
-            ],
-            gesturehandler_ui_elements: vec![
-                Box::new(Button::new((10, 190), "BOOP".to_string(), 3, (120, 120, 120, 255), WHITE)),
-            ],
+            app: App::new(css_size, device_pixel_ratio),
+            presenter: Canvas2dPresenter::new(canvas),
         };
         log("canvas app loaded");
         Ok(instance)
     }
 
+    /// Resizes the canvas to fill the window in device pixels, and the
+    /// virtual screen to match
     pub fn on_resize(&mut self) {
-        let width = window().unwrap().inner_width().unwrap().as_f64().unwrap() as usize;
-        let height = window().unwrap().inner_height().unwrap().as_f64().unwrap() as usize;
-        self.screenbuff = PixelBuffer::new(width, height);
-        self.last_frame_time = 0;
+        let (css_size, device_pixel_ratio) = window_size();
+        self.app.resize(css_size, device_pixel_ratio);
     }
 
-    pub fn on_click(&mut self, event: MouseEvent) {
-        // log(event.page_x());
-        let event_point: (usize, usize) = (event.page_x() as usize, event.page_y() as usize);
-        for gesture_handler in self.gesturehandler_ui_elements.iter_mut().rev() {
-            if is_point_in_rect(event_point, gesture_handler.get_collision_rect()) {
-                console_log!("clicked on something! {:?}", event.type_());
-                if let Some(gesture) = Gesture::of(event.clone()) {
-                    gesture_handler.on_event(gesture);
-                }
-            }
-        }
+    /// Draws at one virtual pixel per device pixel
+    pub fn set_native_resolution(&mut self) {
+        self.app.set_resolution(Resolution::Native);
     }
 
-    /// requestAnimationFrame usually calls as fast as the display is
-    /// configured for (i.e. 60 or 120Hz). This regulates drawing to 
-    /// [FPS]
-    pub fn on_frame(&mut self, timestamp: u32) -> Result<(), JsValue> {
-        let elapsed = timestamp - self.last_frame_time;
-        if elapsed > (1000 / FPS) {
-            let delta = elapsed - (1000 / FPS);
-            let _ = self.render(delta);
-            self.last_frame_time = timestamp;
-            self.tick += 1;
-        }
-        Ok(())
+    /// Draws each virtual pixel as a `scale`x`scale` square of device pixels
+    pub fn set_pixel_scale(&mut self, scale: usize) {
+        self.app.set_resolution(Resolution::Scale(scale));
     }
 
-    fn render(&mut self, delta_time: u32) -> Result<(), JsValue> {
-        let tick_str: String = self.tick.to_string();
-        let frametime_str: String = delta_time.to_string();
-        let stats_str = format!("FRAME {tick_str} . FRAMETIME {frametime_str}");
+    /// Draws at the biggest whole scale that leaves at least `height` rows
+    pub fn set_virtual_height(&mut self, height: usize) {
+        self.app.set_resolution(Resolution::Height(height));
+    }
 
-        // fancy gradient background
-        for y in 0..self.screenbuff.height {
-            for x in 0..self.screenbuff.width {
-                let blue = (255.0 * ((1.0 / 255.0) * 3.14 * self.tick as f32).sin() + 255.0) as u8;
-                self.screenbuff.set((x, y), (0, 0, blue, 255));
-            }
-        }
+    /// Turns the CRT post-processing preset on or off, e.g. per page
+    pub fn set_crt_effect(&mut self, enabled: bool) {
+        self.app.set_crt_effect(enabled);
+    }
 
-        // render UI elements
-        for e in &mut self.ui_elements {
-            e.render(&mut self.screenbuff);
-        }
-        for e in &mut self.gesturehandler_ui_elements {
-            e.render(&mut self.screenbuff);
-        }
-        self.screenbuff.render_text(&stats_str, (0, self.screenbuff.height - (GLYPH_SIZE * 2)), GREEN, 2);
+    /// Rolls the background gradient down the screen, which costs a full
+    /// upload every frame
+    pub fn set_background_animation(&mut self, enabled: bool) {
+        self.app.set_background_animation(enabled);
+    }
 
-        let clamped_data = wasm_bindgen::Clamped(self.screenbuff.data_as_ref());
-        let image_data =
-            web_sys::ImageData::new_with_u8_clamped_array(clamped_data, self.screenbuff.width as u32).unwrap();
-        let _ = self
-            .canvas
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::CanvasRenderingContext2d>()
-            .unwrap()
-            .put_image_data(&image_data, 0.0, 0.0);
+    /// Returns false if there's no filter called `name`
+    pub fn set_filter_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        self.app.set_filter_enabled(name, enabled)
+    }
 
-        // self.tick += 1;
+    /// e.g. `set_filter_param("bloom", "strength", 0.8)`. Returns false if
+    /// there's no such filter or parameter.
+    pub fn set_filter_param(&mut self, filter: &str, param: &str, value: f32) -> bool {
+        self.app.set_filter_param(filter, param, value)
+    }
 
-        Ok(())
+    /// Switches to indexed color with the given hex colors, e.g.
+    /// `"#000 #fff"`, or back to full color with an empty string
+    pub fn set_palette(&mut self, colors: &str) -> Result<(), JsValue> {
+        self.app.set_palette(colors).map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    /// e.g. `set_layer_visible("hud", false)`. Returns false if there's no
+    /// such layer.
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) -> bool {
+        self.app.set_layer_visible(name, visible)
+    }
+
+    /// 0 is see-through, 255 opaque. Returns false if there's no such layer.
+    pub fn set_layer_opacity(&mut self, name: &str, opacity: u8) -> bool {
+        self.app.set_layer_opacity(name, opacity)
+    }
+
+    pub fn on_click(&mut self, event: MouseEvent) {
+        let Some(gesture) = gesture_of(&event) else {
+            return;
+        };
+        if self.app.on_gesture((event.page_x() as f64, event.page_y() as f64), gesture) {
+            console_log!("clicked on something! {:?}", event.type_());
+        }
+    }
+
+    /// requestAnimationFrame usually calls as fast as the display is
+    /// configured for (i.e. 60 or 120Hz). The app regulates drawing to
+    /// [FPS](kevinpthornecom_core::app::FPS)
+    pub fn on_frame(&mut self, timestamp: u32) -> Result<(), JsValue> {
+        self.app.on_frame(timestamp, &mut self.presenter)
     }
 }
 
 #[wasm_bindgen]
 pub fn init(canvas: web_sys::HtmlCanvasElement) -> Result<CanvasApp, JsValue> {
-    Ok(CanvasApp::new(canvas)?)
+    utils::set_panic_hook();
+    CanvasApp::new(canvas)
 }